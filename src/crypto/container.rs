@@ -0,0 +1,723 @@
+use aes_gcm::{
+  aead::{rand_core::RngCore, Aead, KeyInit, OsRng, Payload},
+  AeadCore, Aes256Gcm, Key,
+};
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use zeroize::Zeroizing;
+
+use super::{envelope::Envelope, stream, AES_256_LEN_BYTES, NONCE_LEN_BYTES};
+
+/// Every container starts with these bytes
+pub const MAGIC: [u8; 4] = *b"ENCA";
+/// Container format version for a single wrapped data key kept outside the container
+pub const FORMAT_VERSION: u8 = 1;
+/// Container format version with recipient stanzas following the header, see `Envelope`
+pub const ENVELOPE_FORMAT_VERSION: u8 = 2;
+/// Envelope container version whose stanzas are followed by an optional encrypted signature
+pub const SIGNED_ENVELOPE_FORMAT_VERSION: u8 = 3;
+/// key ids are a truncated sha256 fingerprint
+pub const KEY_ID_LEN_BYTES: usize = 16;
+/// domain separation for key id fingerprints
+const KEY_ID_CONTEXT: &[u8] = b"encryption-app key id v1";
+
+/// Size of the fixed part of the header: magic, version, aead, key wrap, key id length
+const FIXED_HEADER_LEN_BYTES: usize = MAGIC.len() + 4;
+/// Size of the kdf params before the salt: m_cost, t_cost, p_cost, salt length
+const FIXED_KDF_PARAMS_LEN_BYTES: usize = 3 * 4 + 1;
+
+/// Container layout (integers are big endian)
+///
+/// ```text
+/// magic        4 bytes  "ENCA"
+/// version      1 byte
+/// aead         1 byte   AeadAlgorithm
+/// key wrap     1 byte   KeyWrapMethod
+/// key id len   1 byte
+/// key id       key id len bytes
+/// kdf          1 byte   KdfAlgorithm, 0 when absent
+/// kdf params   only when kdf != 0: m_cost u32, t_cost u32, p_cost u32, salt len u8, salt
+/// stanzas      only for version 2 and 3: recipient stanzas (and for 3 a signature), see `Envelope`
+/// payload      aead specific, for aes-256-gcm: nonce || ciphertext || tag,
+///              for streamed aes-256-gcm see `stream::Preamble`
+/// ```
+///
+/// The header (everything before the stanzas) is passed to the AEAD as associated data,
+/// so it can't be edited without breaking decryption.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerHeader {
+  pub version: u8,
+  pub aead: AeadAlgorithm,
+  pub key_wrap: KeyWrapMethod,
+  /// Identifies the key material needed to open the container (see `key_id`)
+  pub key_id: Vec<u8>,
+  pub kdf: Option<KdfParams>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AeadAlgorithm {
+  /// single message aes-256-gcm, payload is nonce || ciphertext || tag
+  Aes256Gcm = 1,
+  /// aes-256-gcm over fixed size segments, can be written and read with bounded memory
+  Aes256GcmStream = 2,
+}
+
+/// How the data key for a container is protected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum KeyWrapMethod {
+  /// data key is handed back to the caller as is
+  Raw = 0,
+  /// data key is wrapped with the legacy built-in key wrapping key, only read these days
+  BuiltinKek = 1,
+  /// data key is split into shamir shares
+  Shamir = 2,
+  /// data key is wrapped with the legacy built-in key wrapping key and then split into shamir shares
+  ShamirBuiltinKek = 3,
+  /// data key is wrapped with a key derived from a passphrase, kdf params are in the header
+  Passphrase = 4,
+  /// data key is wrapped with a keystore master key, the key id is the master key id
+  MasterKey = 5,
+  /// data key is wrapped with a keystore master key and then split into shamir shares
+  ShamirMasterKey = 6,
+  /// data key is wrapped to an x25519 public key, the key id is the public key id
+  X25519 = 7,
+  /// data key is wrapped once per recipient in the stanzas after the header, the key id is a random file id
+  Recipients = 8,
+  /// data key is wrapped with the key of a project vault, only in stanzas, the key id is the vault key id
+  VaultKey = 9,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum KdfAlgorithm {
+  Argon2id = 1,
+}
+
+/// Parameters needed to re-derive a key from a secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+  pub algorithm: KdfAlgorithm,
+  pub m_cost: u32,
+  pub t_cost: u32,
+  pub p_cost: u32,
+  pub salt: Vec<u8>,
+}
+
+impl TryFrom<u8> for AeadAlgorithm {
+  type Error = anyhow::Error;
+
+  fn try_from(value: u8) -> anyhow::Result<Self> {
+    match value {
+      1 => Ok(Self::Aes256Gcm),
+      2 => Ok(Self::Aes256GcmStream),
+      x => Err(anyhow!("Unknown aead algorithm id: {x}")),
+    }
+  }
+}
+
+impl TryFrom<u8> for KeyWrapMethod {
+  type Error = anyhow::Error;
+
+  fn try_from(value: u8) -> anyhow::Result<Self> {
+    match value {
+      0 => Ok(Self::Raw),
+      1 => Ok(Self::BuiltinKek),
+      2 => Ok(Self::Shamir),
+      3 => Ok(Self::ShamirBuiltinKek),
+      4 => Ok(Self::Passphrase),
+      5 => Ok(Self::MasterKey),
+      6 => Ok(Self::ShamirMasterKey),
+      7 => Ok(Self::X25519),
+      8 => Ok(Self::Recipients),
+      9 => Ok(Self::VaultKey),
+      x => Err(anyhow!("Unknown key wrap method id: {x}")),
+    }
+  }
+}
+
+impl TryFrom<u8> for KdfAlgorithm {
+  type Error = anyhow::Error;
+
+  fn try_from(value: u8) -> anyhow::Result<Self> {
+    match value {
+      1 => Ok(Self::Argon2id),
+      x => Err(anyhow!("Unknown kdf algorithm id: {x}")),
+    }
+  }
+}
+
+impl KdfParams {
+  /// Appends algorithm id, costs and salt
+  pub(super) fn encode_into(&self, res: &mut Vec<u8>) -> anyhow::Result<()> {
+    let salt_len = u8::try_from(self.salt.len())
+      .map_err(|_| anyhow!("Kdf salt too long: {} bytes", self.salt.len()))?;
+    res.push(self.algorithm as u8);
+    res.extend(self.m_cost.to_be_bytes());
+    res.extend(self.t_cost.to_be_bytes());
+    res.extend(self.p_cost.to_be_bytes());
+    res.push(salt_len);
+    res.extend(&self.salt);
+    Ok(())
+  }
+
+  /// Reads costs and salt, the algorithm id has already been consumed
+  pub(super) fn decode_from(algorithm: KdfAlgorithm, parser: &mut Parser<'_>) -> anyhow::Result<Self> {
+    let m_cost = parser.u32()?;
+    let t_cost = parser.u32()?;
+    let p_cost = parser.u32()?;
+    let salt_len = parser.u8()? as usize;
+    let salt = parser.take(salt_len)?.to_vec();
+    Ok(Self { algorithm, m_cost, t_cost, p_cost, salt })
+  }
+}
+
+/// Fingerprint of some key material, used to tell which key opens a container
+/// without revealing the key itself
+pub fn key_id(key_material: &[u8]) -> Vec<u8> {
+  let mut hasher = Sha256::new();
+  hasher.update(KEY_ID_CONTEXT);
+  hasher.update(key_material);
+  hasher.finalize()[..KEY_ID_LEN_BYTES].to_vec()
+}
+
+impl ContainerHeader {
+  pub fn new(key_wrap: KeyWrapMethod, key_id: Vec<u8>) -> Self {
+    Self {
+      version: FORMAT_VERSION,
+      aead: AeadAlgorithm::Aes256Gcm,
+      key_wrap,
+      key_id,
+      kdf: None,
+    }
+  }
+
+  /// Header for a container opened through recipient stanzas, each container gets a random id
+  pub fn envelope() -> Self {
+    let mut file_id = vec![0u8; KEY_ID_LEN_BYTES];
+    OsRng.fill_bytes(&mut file_id);
+
+    Self {
+      version: SIGNED_ENVELOPE_FORMAT_VERSION,
+      aead: AeadAlgorithm::Aes256GcmStream,
+      key_wrap: KeyWrapMethod::Recipients,
+      key_id: file_id,
+      kdf: None,
+    }
+  }
+
+  /// True when recipient stanzas follow the header
+  pub fn is_envelope(&self) -> bool {
+    self.version >= ENVELOPE_FORMAT_VERSION
+  }
+
+  /// True when the stanzas have room for a sign-then-encrypt signature
+  pub fn has_signature_section(&self) -> bool {
+    self.version >= SIGNED_ENVELOPE_FORMAT_VERSION
+  }
+
+  /// Errors unless `key_material` has the fingerprint recorded in the header
+  pub fn check_key_id(&self, key_material: &[u8]) -> anyhow::Result<()> {
+    if key_id(key_material) == self.key_id {
+      Ok(())
+    } else {
+      Err(anyhow!("Key does not belong to this container (key id {})", hex::encode(&self.key_id)))
+    }
+  }
+
+  pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+    let key_id_len = u8::try_from(self.key_id.len())
+      .map_err(|_| anyhow!("Key id too long: {} bytes", self.key_id.len()))?;
+
+    let mut res = Vec::with_capacity(FIXED_HEADER_LEN_BYTES + self.key_id.len() + 1);
+    res.extend(MAGIC);
+    res.push(self.version);
+    res.push(self.aead as u8);
+    res.push(self.key_wrap as u8);
+    res.push(key_id_len);
+    res.extend(&self.key_id);
+
+    match &self.kdf {
+      None => res.push(0),
+      Some(kdf) => kdf.encode_into(&mut res)?,
+    }
+
+    Ok(res)
+  }
+
+  /// Parses a header from the front of `bytes`, returns the header and its encoded length
+  pub fn decode(bytes: &[u8]) -> anyhow::Result<(Self, usize)> {
+    let mut parser = Parser::new(bytes);
+
+    if parser.take(MAGIC.len())? != MAGIC {
+      return Err(anyhow!("Not an encrypted container (bad magic)"));
+    }
+    let version = parser.u8()?;
+    if !(FORMAT_VERSION..=SIGNED_ENVELOPE_FORMAT_VERSION).contains(&version) {
+      return Err(anyhow!("Unsupported container version: {version}"));
+    }
+    let aead = AeadAlgorithm::try_from(parser.u8()?)?;
+    let key_wrap = KeyWrapMethod::try_from(parser.u8()?)?;
+    let key_id_len = parser.u8()? as usize;
+    let key_id = parser.take(key_id_len)?.to_vec();
+
+    let kdf = match parser.u8()? {
+      0 => None,
+      x => Some(KdfParams::decode_from(KdfAlgorithm::try_from(x)?, &mut parser)?),
+    };
+
+    Ok((Self { version, aead, key_wrap, key_id, kdf }, parser.pos()))
+  }
+
+  /// Reads a header from the front of `reader` without consuming any of the payload,
+  /// returns the header and its encoded bytes
+  pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<(Self, Vec<u8>)> {
+    let mut bytes = Vec::new();
+    read_more(reader, &mut bytes, FIXED_HEADER_LEN_BYTES).await?;
+    if bytes[..MAGIC.len()] != MAGIC {
+      return Err(anyhow!("Not an encrypted container (bad magic)"));
+    }
+    let key_id_len = bytes[FIXED_HEADER_LEN_BYTES - 1] as usize;
+    read_more(reader, &mut bytes, key_id_len).await?;
+    read_kdf(reader, &mut bytes).await?;
+
+    let (header, len) = Self::decode(&bytes)?;
+    if len != bytes.len() {
+      return Err(anyhow!("Malformed container header"));
+    }
+    Ok((header, bytes))
+  }
+}
+
+/// Appends the next `len` bytes of `reader` to `bytes`
+pub(super) async fn read_more<R: AsyncRead + Unpin>(reader: &mut R, bytes: &mut Vec<u8>, len: usize) -> anyhow::Result<()> {
+  let start = bytes.len();
+  bytes.resize(start + len, 0);
+  reader.read_exact(&mut bytes[start..]).await
+    .map_err(|e| anyhow!("Unable to read container header: {e}"))?;
+  Ok(())
+}
+
+/// Appends an encoded kdf algorithm and, if present, its params
+pub(super) async fn read_kdf<R: AsyncRead + Unpin>(reader: &mut R, bytes: &mut Vec<u8>) -> anyhow::Result<()> {
+  read_more(reader, bytes, 1).await?;
+  if bytes[bytes.len() - 1] != 0 {
+    read_more(reader, bytes, FIXED_KDF_PARAMS_LEN_BYTES).await?;
+    let salt_len = bytes[bytes.len() - 1] as usize;
+    read_more(reader, bytes, salt_len).await?;
+  }
+  Ok(())
+}
+
+/// Writes containers for a given header
+pub struct ContainerWriter {
+  header: ContainerHeader,
+  envelope: Option<Envelope>,
+}
+
+impl ContainerWriter {
+  pub fn new(header: ContainerHeader) -> Self {
+    Self { header, envelope: None }
+  }
+
+  /// Recipient stanzas to write after the header, the header must be an envelope header
+  pub fn envelope(mut self, envelope: Envelope) -> Self {
+    self.envelope = Some(envelope);
+    self
+  }
+
+  /// Encoded header and everything written before the payload
+  fn prefix(&self, data_key: &[u8]) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+    let header_bytes = self.header.encode()?;
+    let mut prefix = header_bytes.clone();
+    match (&self.envelope, self.header.is_envelope()) {
+      (None, false) => {}
+      (Some(envelope), true) if envelope.has_signature_section() != self.header.has_signature_section() => {
+        return Err(anyhow!("Recipient stanzas don't match the container version"));
+      }
+      (Some(envelope), true) => prefix.extend(envelope.encode(&header_bytes, data_key)?),
+      (None, true) => return Err(anyhow!("Envelope container needs recipient stanzas")),
+      (Some(_), false) => return Err(anyhow!("Recipient stanzas need an envelope header")),
+    }
+    Ok((header_bytes, prefix))
+  }
+
+  /// Encrypts `plaintext` with `data_key`, returns header || stanzas || payload
+  pub fn seal(&self, data_key: &[u8], plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let (header_bytes, mut res) = self.prefix(data_key)?;
+
+    match self.header.aead {
+      AeadAlgorithm::Aes256Gcm => {
+        let key = aes_key(data_key)?;
+        let cipher = Aes256Gcm::new(key);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher.encrypt(&nonce, Payload { msg: plaintext, aad: &header_bytes })
+          .map_err(|e| anyhow!("Unable to encrypt data: {e}"))?;
+
+        res.reserve(nonce.len() + ciphertext.len());
+        res.extend(nonce);
+        res.extend(ciphertext);
+        Ok(res)
+      }
+      AeadAlgorithm::Aes256GcmStream => {
+        let payload = stream::seal(aes_key(data_key)?, &header_bytes, plaintext)?;
+
+        res.extend(payload);
+        Ok(res)
+      }
+    }
+  }
+
+  /// Encrypts everything `reader` yields and writes header || payload to `writer`
+  ///
+  /// Only streamed aeads keep memory bounded, others read the whole input first.
+  /// returns the number of plaintext bytes
+  pub async fn seal_stream<R, W>(&self, data_key: &[u8], reader: &mut R, writer: &mut W) -> anyhow::Result<u64>
+  where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+  {
+    match self.header.aead {
+      AeadAlgorithm::Aes256Gcm => {
+        let mut plaintext = Zeroizing::new(Vec::new());
+        reader.read_to_end(&mut plaintext).await?;
+        writer.write_all(&self.seal(data_key, &plaintext)?).await?;
+        writer.flush().await?;
+        Ok(plaintext.len() as u64)
+      }
+      AeadAlgorithm::Aes256GcmStream => {
+        let (header_bytes, prefix) = self.prefix(data_key)?;
+        let key = aes_key(data_key)?;
+        writer.write_all(&prefix).await?;
+        stream::seal_stream(key, &header_bytes, reader, writer).await
+      }
+    }
+  }
+}
+
+/// Parses a container and gives access to its header before decrypting
+pub struct ContainerReader<'a> {
+  header: ContainerHeader,
+  header_bytes: &'a [u8],
+  #[allow(dead_code)]
+  envelope: Option<Envelope>,
+  payload: &'a [u8],
+}
+
+impl<'a> ContainerReader<'a> {
+  pub fn parse(bytes: &'a [u8]) -> anyhow::Result<Self> {
+    let (header, header_len) = ContainerHeader::decode(bytes)?;
+    let (header_bytes, mut payload) = bytes.split_at(header_len);
+    let envelope = if header.is_envelope() {
+      let (envelope, envelope_len) = Envelope::decode(payload, header.has_signature_section())?;
+      payload = &payload[envelope_len..];
+      Some(envelope)
+    } else {
+      None
+    };
+
+    Ok(Self { header, header_bytes, envelope, payload })
+  }
+
+  pub fn header(&self) -> &ContainerHeader {
+    &self.header
+  }
+
+  /// The encoded header, exactly as authenticated by the AEAD
+  #[allow(dead_code)]
+  pub fn header_bytes(&self) -> &[u8] {
+    self.header_bytes
+  }
+
+  /// Recipient stanzas, only present in envelope containers
+  #[allow(dead_code)]
+  pub fn envelope(&self) -> Option<&Envelope> {
+    self.envelope.as_ref()
+  }
+
+  pub fn open(&self, data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+    open_payload(&self.header, self.header_bytes, self.payload, data_key)
+  }
+}
+
+/// Reads a container from an async reader, the header first so the key can be looked up
+pub struct ContainerStreamReader {
+  header: ContainerHeader,
+  header_bytes: Vec<u8>,
+  envelope: Option<Envelope>,
+}
+
+impl ContainerStreamReader {
+  /// Consumes just the header, and the recipient stanzas if there are any, from `reader`
+  pub async fn read_header<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<Self> {
+    let (header, header_bytes) = ContainerHeader::read_from(reader).await?;
+    let envelope = if header.is_envelope() {
+      Some(Envelope::read_from(reader, header.has_signature_section()).await?.0)
+    } else {
+      None
+    };
+
+    Ok(Self { header, header_bytes, envelope })
+  }
+
+  pub fn header(&self) -> &ContainerHeader {
+    &self.header
+  }
+
+  /// The encoded header, exactly as authenticated by the AEAD
+  pub fn header_bytes(&self) -> &[u8] {
+    &self.header_bytes
+  }
+
+  /// Recipient stanzas, only present in envelope containers
+  pub fn envelope(&self) -> Option<&Envelope> {
+    self.envelope.as_ref()
+  }
+
+  /// Decrypts the rest of `reader` into `writer`
+  ///
+  /// Only streamed aeads keep memory bounded, others read the whole payload first.
+  /// On error part of the plaintext may already have been written, discard it.
+  /// returns the number of plaintext bytes
+  pub async fn open<R, W>(&self, data_key: &[u8], reader: &mut R, writer: &mut W) -> anyhow::Result<u64>
+  where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+  {
+    match self.header.aead {
+      AeadAlgorithm::Aes256Gcm => {
+        let mut payload = Vec::new();
+        reader.read_to_end(&mut payload).await?;
+        let plaintext = Zeroizing::new(open_payload(&self.header, &self.header_bytes, &payload, data_key)?);
+        writer.write_all(&plaintext).await?;
+        writer.flush().await?;
+        Ok(plaintext.len() as u64)
+      }
+      AeadAlgorithm::Aes256GcmStream => {
+        stream::open_stream(aes_key(data_key)?, &self.header_bytes, reader, writer).await
+      }
+    }
+  }
+
+  /// Writes this container with new recipient stanzas, the rest of `reader` is copied as is
+  ///
+  /// `data_key` must be the key the current stanzas were written with.
+  /// returns the number of payload bytes copied
+  pub async fn rewrap<R, W>(&self, data_key: &[u8], envelope: &Envelope, reader: &mut R, writer: &mut W) -> anyhow::Result<u64>
+  where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+  {
+    let current = self.envelope.as_ref()
+      .ok_or_else(|| anyhow!("Container has no recipient stanzas to change"))?;
+    current.verify(&self.header_bytes, data_key)?;
+    if envelope.has_signature_section() != self.header.has_signature_section() {
+      return Err(anyhow!("Recipient stanzas don't match the container version"));
+    }
+
+    writer.write_all(&self.header_bytes).await?;
+    writer.write_all(&envelope.encode(&self.header_bytes, data_key)?).await?;
+    let copied = tokio::io::copy(reader, writer).await?;
+    writer.flush().await?;
+    Ok(copied)
+  }
+}
+
+fn open_payload(header: &ContainerHeader, header_bytes: &[u8], payload: &[u8], data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+  match header.aead {
+    AeadAlgorithm::Aes256Gcm => {
+      let key = aes_key(data_key)?;
+      if payload.len() < NONCE_LEN_BYTES {
+        return Err(anyhow!("Container payload is truncated"));
+      }
+      let cipher = Aes256Gcm::new(key);
+      let (nonce_bytes, ciphertext_bytes) = payload.split_at(NONCE_LEN_BYTES);
+
+      cipher.decrypt(nonce_bytes.into(), Payload { msg: ciphertext_bytes, aad: header_bytes })
+        .map_err(|e| anyhow!("Unable to decrypt data: {e}"))
+    }
+    AeadAlgorithm::Aes256GcmStream => {
+      stream::open(aes_key(data_key)?, header_bytes, payload)
+    }
+  }
+}
+
+fn aes_key(data_key: &[u8]) -> anyhow::Result<&Key<Aes256Gcm>> {
+  if data_key.len() != AES_256_LEN_BYTES {
+    return Err(anyhow!("Data key must be {AES_256_LEN_BYTES} bytes, got {}", data_key.len()));
+  }
+  Ok(data_key.into())
+}
+
+/// Minimal cursor over a byte slice that errors instead of panicking on short input
+pub(super) struct Parser<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Parser<'a> {
+  pub(super) fn new(bytes: &'a [u8]) -> Self {
+    Self { bytes, pos: 0 }
+  }
+
+  /// number of bytes consumed so far
+  pub(super) fn pos(&self) -> usize {
+    self.pos
+  }
+
+  pub(super) fn rest(&self) -> &'a [u8] {
+    &self.bytes[self.pos..]
+  }
+
+  pub(super) fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+    let end = self.pos.checked_add(len)
+      .filter(|end| *end <= self.bytes.len())
+      .ok_or_else(|| anyhow!("Input is truncated"))?;
+    let res = &self.bytes[self.pos..end];
+    self.pos = end;
+    Ok(res)
+  }
+
+  pub(super) fn u8(&mut self) -> anyhow::Result<u8> {
+    Ok(self.take(1)?[0])
+  }
+
+  pub(super) fn u16(&mut self) -> anyhow::Result<u16> {
+    let mut buf = [0u8; 2];
+    buf.copy_from_slice(self.take(2)?);
+    Ok(u16::from_be_bytes(buf))
+  }
+
+  pub(super) fn u32(&mut self) -> anyhow::Result<u32> {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(self.take(4)?);
+    Ok(u32::from_be_bytes(buf))
+  }
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_header() -> ContainerHeader {
+      let mut header = ContainerHeader::new(KeyWrapMethod::BuiltinKek, key_id(b"some key"));
+      header.kdf = Some(KdfParams {
+        algorithm: KdfAlgorithm::Argon2id,
+        m_cost: 19456,
+        t_cost: 2,
+        p_cost: 1,
+        salt: vec![7; 16],
+      });
+      header
+    }
+
+    #[test]
+    fn test_header_roundtrip() -> anyhow::Result<()> {
+      let header = sample_header();
+      let bytes = header.encode()?;
+
+      let (act, len) = ContainerHeader::decode(&bytes)?;
+      assert_eq!(header, act);
+      assert_eq!(bytes.len(), len);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_container_roundtrip() -> anyhow::Result<()> {
+      let key = Aes256Gcm::generate_key(OsRng);
+      let header = ContainerHeader::new(KeyWrapMethod::Raw, key_id(&key));
+
+      let sealed = ContainerWriter::new(header.clone()).seal(&key, b"hello world")?;
+      let reader = ContainerReader::parse(&sealed)?;
+      assert_eq!(&header, reader.header());
+      reader.header().check_key_id(&key)?;
+
+      assert_eq!(b"hello world".to_vec(), reader.open(&key)?);
+      Ok(())
+    }
+
+    #[test]
+    fn test_rejects_bad_magic_and_version() -> anyhow::Result<()> {
+      let mut bytes = sample_header().encode()?;
+      bytes[4] = SIGNED_ENVELOPE_FORMAT_VERSION + 1;
+      assert!(ContainerHeader::decode(&bytes).is_err(), "unknown version should be rejected");
+
+      bytes[0] = b'X';
+      assert!(ContainerHeader::decode(&bytes).is_err(), "bad magic should be rejected");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_rejects_truncated_header() -> anyhow::Result<()> {
+      let bytes = sample_header().encode()?;
+      for len in 0..bytes.len() {
+        assert!(ContainerHeader::decode(&bytes[..len]).is_err(), "header truncated to {len} bytes should be rejected");
+      }
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_header_is_authenticated() -> anyhow::Result<()> {
+      let key = Aes256Gcm::generate_key(OsRng);
+      let header = ContainerHeader::new(KeyWrapMethod::Raw, key_id(&key));
+      let mut sealed = ContainerWriter::new(header).seal(&key, b"hello world")?;
+
+      // flip the key wrap method, still a valid header but no longer the one that was sealed
+      sealed[6] = KeyWrapMethod::BuiltinKek as u8;
+      let reader = ContainerReader::parse(&sealed)?;
+      assert!(reader.open(&key).is_err(), "edited header must fail authentication");
+
+      Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_container_roundtrip() -> anyhow::Result<()> {
+      let key = Aes256Gcm::generate_key(OsRng);
+      let plaintext = vec![42u8; 3 * stream::SEGMENT_LEN_BYTES as usize / 2];
+
+      for aead in [AeadAlgorithm::Aes256Gcm, AeadAlgorithm::Aes256GcmStream] {
+        let mut header = sample_header();
+        header.aead = aead;
+
+        let mut sealed = Vec::new();
+        ContainerWriter::new(header.clone()).seal_stream(&key, &mut plaintext.as_slice(), &mut sealed).await?;
+        assert_eq!(plaintext, ContainerReader::parse(&sealed)?.open(&key)?, "{aead:?} opens in memory");
+
+        let mut input = sealed.as_slice();
+        let reader = ContainerStreamReader::read_header(&mut input).await?;
+        assert_eq!(&header, reader.header());
+        let mut act = Vec::new();
+        reader.open(&key, &mut input, &mut act).await?;
+        assert_eq!(plaintext, act, "{aead:?} opens streamed");
+      }
+
+      Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_header_leaves_payload() -> anyhow::Result<()> {
+      let mut bytes = sample_header().encode()?;
+      let header_len = bytes.len();
+      bytes.extend(b"payload");
+
+      let mut input = bytes.as_slice();
+      let (header, header_bytes) = ContainerHeader::read_from(&mut input).await?;
+      assert_eq!(sample_header(), header);
+      assert_eq!(&bytes[..header_len], header_bytes.as_slice());
+      assert_eq!(b"payload", input);
+
+      let mut truncated = &bytes[..header_len - 1];
+      assert!(ContainerHeader::read_from(&mut truncated).await.is_err());
+
+      Ok(())
+    }
+  }
+
+// #endregion ----------------
@@ -0,0 +1,279 @@
+use std::{fmt, str::FromStr};
+
+use aes_gcm::aead::OsRng;
+use anyhow::anyhow;
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use zeroize::Zeroizing;
+
+use super::{container::Parser, key_id, keystore::Keystore};
+
+/// ed25519 public keys and secrets are 32 bytes
+pub const ED25519_KEY_LEN_BYTES: usize = 32;
+/// ed25519 signatures are 64 bytes
+pub const ED25519_SIGNATURE_LEN_BYTES: usize = 64;
+/// Every signature file starts with these bytes
+pub const SIGNATURE_MAGIC: [u8; 4] = *b"ENCS";
+/// Current signature format version
+pub const SIGNATURE_VERSION: u8 = 1;
+/// sha256 digests of signed content are 32 bytes
+pub const DIGEST_LEN_BYTES: usize = 32;
+/// text form of a public key is this prefix followed by the key in hex
+const SIGNER_PREFIX: &str = "ed25519:";
+/// prepended to the digest before signing, so our signatures can't be replayed in another protocol
+const SIGNATURE_CONTEXT: &[u8] = b"encryption-app signature v1";
+
+/// sha256 of signed content
+pub type ContentDigest = [u8; DIGEST_LEN_BYTES];
+
+/// Public half of an ed25519 keypair, checks signatures made by its identity
+///
+/// Kept compressed, a decompressed key is several times larger and this travels in GUI messages.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Ed25519Signer([u8; ED25519_KEY_LEN_BYTES]);
+
+/// Secret half of an ed25519 keypair, kept in the keystore
+#[derive(Clone)]
+pub struct Ed25519Identity(SigningKey);
+
+/// A signature over the digest of some content, along with who made it
+///
+/// Layout:
+///
+/// ```text
+/// magic        4 bytes  "ENCS"
+/// version      1 byte
+/// signer       32 bytes ed25519 public key
+/// signature    64 bytes ed25519 signature over context || sha256(content)
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+  signer: Ed25519Signer,
+  signature: ed25519_dalek::Signature,
+}
+
+/// What checking a signature against the keystore found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+  /// signature verifies and the signer is one of ours or trusted
+  Trusted(Ed25519Signer),
+  /// signature verifies but nobody vouched for the signer
+  UnknownSigner(Ed25519Signer),
+  /// content or signature were changed after signing
+  Invalid,
+}
+
+impl Ed25519Signer {
+  pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+    let bytes: [u8; ED25519_KEY_LEN_BYTES] = bytes.try_into()
+      .map_err(|_| anyhow!("Signing key must be {ED25519_KEY_LEN_BYTES} bytes, got {}", bytes.len()))?;
+    VerifyingKey::from_bytes(&bytes)
+      .map_err(|e| anyhow!("Not an ed25519 public key: {e}"))?;
+    Ok(Self(bytes))
+  }
+
+  pub fn as_bytes(&self) -> &[u8; ED25519_KEY_LEN_BYTES] {
+    &self.0
+  }
+
+  fn verifying_key(&self) -> anyhow::Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&self.0)
+      .map_err(|e| anyhow!("Not an ed25519 public key: {e}"))
+  }
+
+  /// Fingerprint of the public key, see `key_id`
+  pub fn id(&self) -> Vec<u8> {
+    key_id(self.as_bytes())
+  }
+}
+
+impl fmt::Display for Ed25519Signer {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{SIGNER_PREFIX}{}", hex::encode(self.as_bytes()))
+  }
+}
+
+impl fmt::Debug for Ed25519Signer {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Ed25519Signer({self})")
+  }
+}
+
+impl FromStr for Ed25519Signer {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> anyhow::Result<Self> {
+    let hex_key = s.trim().strip_prefix(SIGNER_PREFIX)
+      .ok_or_else(|| anyhow!("Signing key must start with {SIGNER_PREFIX}"))?;
+    let bytes = hex::decode(hex_key)
+      .map_err(|e| anyhow!("Signing key is not valid hex: {e}"))?;
+    Self::from_bytes(&bytes)
+  }
+}
+
+impl Ed25519Identity {
+  pub fn generate() -> Self {
+    Self(SigningKey::generate(&mut OsRng))
+  }
+
+  pub(super) fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+    let bytes: Zeroizing<[u8; ED25519_KEY_LEN_BYTES]> = Zeroizing::new(bytes.try_into()
+      .map_err(|_| anyhow!("Secret key must be {ED25519_KEY_LEN_BYTES} bytes, got {}", bytes.len()))?);
+    Ok(Self(SigningKey::from_bytes(&bytes)))
+  }
+
+  pub(super) fn to_bytes(&self) -> Zeroizing<[u8; ED25519_KEY_LEN_BYTES]> {
+    Zeroizing::new(self.0.to_bytes())
+  }
+
+  pub fn signer(&self) -> Ed25519Signer {
+    Ed25519Signer(self.0.verifying_key().to_bytes())
+  }
+}
+
+impl fmt::Debug for Ed25519Identity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Ed25519Identity({})", self.signer())
+  }
+}
+
+impl Signature {
+  /// Signs content with the digest `digest`
+  pub fn sign(identity: &Ed25519Identity, digest: &ContentDigest) -> Self {
+    use ed25519_dalek::Signer;
+
+    let signature = identity.0.sign(&signed_message(digest));
+    Self { signer: identity.signer(), signature }
+  }
+
+  pub fn signer(&self) -> &Ed25519Signer {
+    &self.signer
+  }
+
+  /// Errors unless this signature was made over content with the digest `digest`
+  pub fn verify(&self, digest: &ContentDigest) -> anyhow::Result<()> {
+    self.signer.verifying_key()?
+      .verify_strict(&signed_message(digest), &self.signature)
+      .map_err(|_| anyhow!("Signature by {} does not match the content", self.signer))
+  }
+
+  /// Verifies and tells whether the signer is known to `keystore`
+  pub fn check(&self, digest: &ContentDigest, keystore: Option<&Keystore>) -> SignatureStatus {
+    if self.verify(digest).is_err() {
+      SignatureStatus::Invalid
+    } else if keystore.is_some_and(|keystore| keystore.is_known_signer(&self.signer)) {
+      SignatureStatus::Trusted(self.signer)
+    } else {
+      SignatureStatus::UnknownSigner(self.signer)
+    }
+  }
+
+  pub fn encode(&self) -> Vec<u8> {
+    let mut res = Vec::with_capacity(SIGNATURE_MAGIC.len() + 1 + ED25519_KEY_LEN_BYTES + ED25519_SIGNATURE_LEN_BYTES);
+    res.extend(SIGNATURE_MAGIC);
+    res.push(SIGNATURE_VERSION);
+    res.extend(self.signer.as_bytes());
+    res.extend(self.signature.to_bytes());
+    res
+  }
+
+  pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+    let mut parser = Parser::new(bytes);
+    if parser.take(SIGNATURE_MAGIC.len())? != SIGNATURE_MAGIC {
+      return Err(anyhow!("Not a signature (bad magic)"));
+    }
+    let version = parser.u8()?;
+    if version != SIGNATURE_VERSION {
+      return Err(anyhow!("Unsupported signature version: {version}"));
+    }
+    let signer = Ed25519Signer::from_bytes(parser.take(ED25519_KEY_LEN_BYTES)?)?;
+    let signature = ed25519_dalek::Signature::from_slice(parser.take(ED25519_SIGNATURE_LEN_BYTES)?)
+      .map_err(|e| anyhow!("Malformed signature: {e}"))?;
+    if !parser.rest().is_empty() {
+      return Err(anyhow!("Trailing bytes after signature"));
+    }
+
+    Ok(Self { signer, signature })
+  }
+}
+
+/// sha256 over everything `reader` yields, what signatures are made over
+pub async fn digest_reader<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<ContentDigest> {
+  let mut hasher = Sha256::new();
+  let mut buf = vec![0u8; 64 * 1024];
+  loop {
+    let len = reader.read(&mut buf).await?;
+    if len == 0 {
+      break;
+    }
+    hasher.update(&buf[..len]);
+  }
+  Ok(hasher.finalize().into())
+}
+
+/// sha256 of content held in memory
+#[allow(dead_code)]
+pub fn digest(content: &[u8]) -> ContentDigest {
+  Sha256::digest(content).into()
+}
+
+fn signed_message(digest: &ContentDigest) -> Vec<u8> {
+  let mut message = Vec::with_capacity(SIGNATURE_CONTEXT.len() + DIGEST_LEN_BYTES);
+  message.extend(SIGNATURE_CONTEXT);
+  message.extend(digest);
+  message
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() -> anyhow::Result<()> {
+      let identity = Ed25519Identity::generate();
+      let signature = Signature::sign(&identity, &digest(b"hello world"));
+
+      signature.verify(&digest(b"hello world"))?;
+      assert!(signature.verify(&digest(b"hello world!")).is_err());
+      assert_eq!(identity.signer(), *signature.signer());
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_signature_encode_roundtrip() -> anyhow::Result<()> {
+      let signature = Signature::sign(&Ed25519Identity::generate(), &digest(b"hello world"));
+      let bytes = signature.encode();
+      assert_eq!(signature, Signature::decode(&bytes)?);
+
+      let mut swapped = bytes.clone();
+      swapped[5..5 + ED25519_KEY_LEN_BYTES].copy_from_slice(Ed25519Identity::generate().signer().as_bytes());
+      assert!(Signature::decode(&swapped)?.verify(&digest(b"hello world")).is_err(), "signer is part of the check");
+      assert!(Signature::decode(&bytes[..bytes.len() - 1]).is_err());
+
+      Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_digest_reader_matches_digest() -> anyhow::Result<()> {
+      let content: Vec<u8> = (0..200_000u32).map(|x| x as u8).collect();
+      assert_eq!(digest(&content), digest_reader(&mut content.as_slice()).await?);
+      Ok(())
+    }
+
+    #[test]
+    fn test_signer_text_roundtrip() -> anyhow::Result<()> {
+      let signer = Ed25519Identity::generate().signer();
+      let text = signer.to_string();
+      assert!(text.starts_with(SIGNER_PREFIX));
+      assert_eq!(signer, text.parse()?);
+
+      assert!("ed25519:abcd".parse::<Ed25519Signer>().is_err());
+      Ok(())
+    }
+  }
+
+// #endregion ----------------
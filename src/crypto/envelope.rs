@@ -0,0 +1,539 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::io::AsyncRead;
+use zeroize::Zeroizing;
+
+use super::{
+  container::{read_kdf, read_more, KdfAlgorithm, KdfParams, KeyWrapMethod, Parser},
+  ed25519::Signature,
+  key_id,
+  keystore::{Keystore, MasterKey},
+  passphrase::{self, KdfCost, Passphrase},
+  unwrap_data_key, unwrap_data_key_with, unwrap_data_key_x25519, wrap_data_key, wrap_data_key_with, wrap_data_key_x25519,
+  vault::VaultKey,
+  x25519::X25519Recipient,
+  AES_256_LEN_BYTES,
+};
+
+/// hmac-sha256 tag over the header and the stanzas
+const MAC_LEN_BYTES: usize = 32;
+/// hkdf info for the key that authenticates the stanzas
+const MAC_KDF_INFO: &[u8] = b"encryption-app envelope mac v1";
+/// hkdf info for the key that hides the signature from everyone but the recipients
+const SIGNATURE_KDF_INFO: &[u8] = b"encryption-app envelope signature v1";
+
+/// Someone who should be able to open a container
+#[derive(Clone)]
+pub enum Recipient {
+  MasterKey(MasterKey),
+  X25519(X25519Recipient),
+  /// passphrase and the argon2id cost to derive its key with
+  Passphrase(Passphrase, KdfCost),
+  /// every file in a project vault, opened once a quorum unlocked the vault
+  VaultKey(VaultKey),
+}
+
+/// One wrapped copy of the data key
+///
+/// `key_id` tells which credential opens the stanza: the master key id, the public key id,
+/// or for passphrases the id of the wrapped key itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stanza {
+  pub method: KeyWrapMethod,
+  pub key_id: Vec<u8>,
+  pub kdf: Option<KdfParams>,
+  pub wrapped_key: Vec<u8>,
+}
+
+/// Recipient stanzas stored after the header of envelope containers
+///
+/// Layout (integers are big endian):
+///
+/// ```text
+/// count        1 byte
+/// stanzas      count times: method u8 | key id len u8 | key id | kdf (as in the header) | wrapped len u16 | wrapped key
+/// signature    only for version 3: len u16, 0 when unsigned | nonce || encrypted `Signature` || tag
+/// mac          32 bytes, hmac-sha256 over header || everything above
+/// ```
+///
+/// The stanzas are not associated data of the payload, so recipients can be added or
+/// removed without touching it. Instead they are authenticated with a key derived from
+/// the data key: whoever can open the container can change who else may open it,
+/// nobody else can.
+///
+/// The signature is over the plaintext (sign-then-encrypt) and encrypted with a key
+/// derived from the data key, so only recipients learn who signed. As with any
+/// sign-then-encrypt, a recipient can pass the signed plaintext on under a new envelope.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Envelope {
+  stanzas: Vec<Stanza>,
+  /// false for version 2 containers, they have no signature section
+  signature_section: bool,
+  /// encrypted `Signature` of the plaintext
+  signature: Option<Vec<u8>>,
+  /// tag read from a container, checked once a data key has been unwrapped
+  mac: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for Recipient {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::MasterKey(master_key) => write!(f, "Recipient({master_key:?})"),
+      Self::X25519(recipient) => write!(f, "Recipient({recipient})"),
+      Self::Passphrase(..) => f.write_str("Recipient(Passphrase(..))"),
+      Self::VaultKey(vault_key) => write!(f, "Recipient({vault_key:?})"),
+    }
+  }
+}
+
+impl Stanza {
+  /// Wraps `data_key` for `recipient`, slow by design for passphrases
+  pub fn wrap(recipient: &Recipient, data_key: &[u8]) -> anyhow::Result<Self> {
+    match recipient {
+      Recipient::MasterKey(master_key) => {
+        let (wrapped_key, _, _, _) = wrap_data_key(master_key, data_key)?;
+        Ok(Self { method: KeyWrapMethod::MasterKey, key_id: master_key.id().to_vec(), kdf: None, wrapped_key })
+      }
+      Recipient::X25519(recipient) => {
+        let wrapped_key = wrap_data_key_x25519(recipient, data_key)?;
+        Ok(Self { method: KeyWrapMethod::X25519, key_id: recipient.id(), kdf: None, wrapped_key })
+      }
+      Recipient::Passphrase(passphrase, cost) => {
+        if passphrase.is_empty() {
+          return Err(anyhow!("Passphrase must not be empty"));
+        }
+        let kdf = passphrase::new_kdf_params(*cost);
+        let kek = passphrase::derive_key(passphrase, &kdf)?;
+        let (wrapped_key, _, _, _) = wrap_data_key_with(&kek, data_key, &[])?;
+        Ok(Self { method: KeyWrapMethod::Passphrase, key_id: key_id(&wrapped_key), kdf: Some(kdf), wrapped_key })
+      }
+      Recipient::VaultKey(vault_key) => {
+        Ok(Self { method: KeyWrapMethod::VaultKey, key_id: vault_key.id(), kdf: None, wrapped_key: vault_key.wrap(data_key)? })
+      }
+    }
+  }
+
+  /// Unwraps with a key from `keystore`, None when the keystore has no key for this stanza
+  fn unwrap_with_keystore(&self, keystore: &Keystore) -> Option<anyhow::Result<Vec<u8>>> {
+    match self.method {
+      KeyWrapMethod::MasterKey => keystore.master_key(&self.key_id)
+        .map(|master_key| unwrap_data_key(master_key, &self.wrapped_key)),
+      KeyWrapMethod::X25519 => keystore.x25519_identity(&self.key_id)
+        .map(|identity| unwrap_data_key_x25519(identity, &self.wrapped_key)),
+      _ => None,
+    }
+  }
+
+  fn unwrap_with_passphrase(&self, passphrase: &Passphrase) -> Option<anyhow::Result<Vec<u8>>> {
+    match (self.method, &self.kdf) {
+      (KeyWrapMethod::Passphrase, Some(kdf)) => Some(passphrase::derive_key(passphrase, kdf)
+        .and_then(|kek| unwrap_data_key_with(&kek, &self.wrapped_key, &[]))),
+      _ => None,
+    }
+  }
+
+  fn encode_into(&self, res: &mut Vec<u8>) -> anyhow::Result<()> {
+    let key_id_len = u8::try_from(self.key_id.len())
+      .map_err(|_| anyhow!("Key id too long: {} bytes", self.key_id.len()))?;
+    let wrapped_len = u16::try_from(self.wrapped_key.len())
+      .map_err(|_| anyhow!("Wrapped key too long: {} bytes", self.wrapped_key.len()))?;
+    res.push(self.method as u8);
+    res.push(key_id_len);
+    res.extend(&self.key_id);
+    match &self.kdf {
+      None => res.push(0),
+      Some(kdf) => kdf.encode_into(res)?,
+    }
+    res.extend(wrapped_len.to_be_bytes());
+    res.extend(&self.wrapped_key);
+    Ok(())
+  }
+
+  fn decode_from(parser: &mut Parser<'_>) -> anyhow::Result<Self> {
+    let method = KeyWrapMethod::try_from(parser.u8()?)?;
+    let key_id_len = parser.u8()? as usize;
+    let key_id = parser.take(key_id_len)?.to_vec();
+    let kdf = match parser.u8()? {
+      0 => None,
+      x => Some(KdfParams::decode_from(KdfAlgorithm::try_from(x)?, parser)?),
+    };
+    let wrapped_len = parser.u16()? as usize;
+    let wrapped_key = parser.take(wrapped_len)?.to_vec();
+
+    Ok(Self { method, key_id, kdf, wrapped_key })
+  }
+}
+
+impl Envelope {
+  /// Wraps `data_key` once for every recipient
+  pub fn wrap(recipients: &[Recipient], data_key: &[u8]) -> anyhow::Result<Self> {
+    if recipients.is_empty() {
+      return Err(anyhow!("Need at least one recipient"));
+    }
+    let stanzas = recipients.iter()
+      .map(|recipient| Stanza::wrap(recipient, data_key))
+      .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(Self { stanzas, signature_section: true, signature: None, mac: None })
+  }
+
+  pub fn stanzas(&self) -> &[Stanza] {
+    &self.stanzas
+  }
+
+  pub(super) fn has_signature_section(&self) -> bool {
+    self.signature_section
+  }
+
+  /// Stores `signature` of the plaintext, encrypted so only recipients can read it
+  pub fn sign(&mut self, signature: &Signature, data_key: &[u8]) -> anyhow::Result<()> {
+    if !self.signature_section {
+      return Err(anyhow!("This container version can't hold a signature"));
+    }
+    let key = derive_key(data_key, SIGNATURE_KDF_INFO)?;
+    let (sealed, _, _, _) = wrap_data_key_with(&key, &signature.encode(), &[])?;
+    self.signature = Some(sealed);
+    self.mac = None;
+    Ok(())
+  }
+
+  /// Decrypts the signature of the plaintext, None when the sender did not sign
+  ///
+  /// Check the signature against the decrypted content with `Signature::check`.
+  pub fn signature(&self, data_key: &[u8]) -> anyhow::Result<Option<Signature>> {
+    let Some(sealed) = &self.signature else {
+      return Ok(None);
+    };
+    let key = derive_key(data_key, SIGNATURE_KDF_INFO)?;
+    let encoded = unwrap_data_key_with(&key, sealed, &[])
+      .map_err(|_| anyhow!("Unable to decrypt the signature"))?;
+    Signature::decode(&encoded).map(Some)
+  }
+
+  /// Adds a stanza for `recipient`, replacing an older one for the same key
+  pub fn add(&mut self, recipient: &Recipient, data_key: &[u8]) -> anyhow::Result<()> {
+    let stanza = Stanza::wrap(recipient, data_key)?;
+    self.stanzas.retain(|x| x.method != stanza.method || x.key_id != stanza.key_id);
+    self.stanzas.push(stanza);
+    self.mac = None;
+    Ok(())
+  }
+
+  /// Removes the stanzas opened by the key with id `key_id`, errors rather than leave nobody able to open the file
+  pub fn remove(&mut self, key_id: &[u8]) -> anyhow::Result<()> {
+    let remaining = self.stanzas.iter().filter(|x| x.key_id != key_id).count();
+    if remaining == self.stanzas.len() {
+      return Err(anyhow!("No recipient with key id {}", hex::encode(key_id)));
+    }
+    if remaining == 0 {
+      return Err(anyhow!("Can't remove the last recipient"));
+    }
+    self.stanzas.retain(|x| x.key_id != key_id);
+    self.mac = None;
+    Ok(())
+  }
+
+  /// True when only a passphrase could open this envelope, given what `keystore` holds
+  pub fn needs_passphrase(&self, keystore: Option<&Keystore>) -> bool {
+    let keystore_match = keystore.is_some_and(|keystore| self.stanzas.iter().any(|x| x.unwrap_with_keystore(keystore).is_some()));
+    !keystore_match && self.stanzas.iter().any(|x| x.method == KeyWrapMethod::Passphrase)
+  }
+
+  /// Id of the vault key that opens this envelope, for files in a project vault
+  pub fn vault_key_id(&self) -> Option<&[u8]> {
+    self.stanzas.iter().find(|x| x.method == KeyWrapMethod::VaultKey).map(|x| x.key_id.as_slice())
+  }
+
+  /// Unwraps with the key of an unlocked vault and checks the stanzas weren't tampered with
+  pub fn unwrap_with_vault_key(&self, header_bytes: &[u8], vault_key: &VaultKey) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let id = vault_key.id();
+    let stanza = self.stanzas.iter()
+      .find(|x| x.method == KeyWrapMethod::VaultKey && x.key_id == id)
+      .ok_or_else(|| anyhow!("File is not in the vault of key {}", hex::encode(&id)))?;
+    let data_key = Zeroizing::new(vault_key.unwrap(&stanza.wrapped_key)
+      .map_err(|_| anyhow!("Unable to unwrap the data key with the vault key"))?);
+    self.verify(header_bytes, &data_key)?;
+    Ok(data_key)
+  }
+
+  /// Tries every stanza there is a credential for and checks the stanzas weren't tampered with
+  pub fn unwrap(&self, header_bytes: &[u8], keystore: Option<&Keystore>, passphrase: Option<&Passphrase>) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let attempts = self.stanzas.iter().filter_map(|stanza| {
+      let from_keystore = keystore.and_then(|keystore| stanza.unwrap_with_keystore(keystore));
+      from_keystore.or_else(|| passphrase.and_then(|passphrase| stanza.unwrap_with_passphrase(passphrase)))
+    });
+
+    let mut tried = 0;
+    for attempt in attempts {
+      tried += 1;
+      if let Ok(data_key) = attempt {
+        let data_key = Zeroizing::new(data_key);
+        self.verify(header_bytes, &data_key)?;
+        return Ok(data_key);
+      }
+    }
+
+    if tried == 0 {
+      Err(anyhow!("None of the {} recipients of this file is in the keystore", self.stanzas.len()))
+    } else if passphrase.is_some() {
+      Err(anyhow!("Wrong passphrase"))
+    } else {
+      Err(anyhow!("Unable to unwrap the data key for any recipient"))
+    }
+  }
+
+  /// Encodes the stanzas followed by their mac
+  pub(super) fn encode(&self, header_bytes: &[u8], data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut res = self.encode_body()?;
+    let mac = compute_mac(header_bytes, &res, data_key)?;
+    res.extend(mac);
+    Ok(res)
+  }
+
+  /// Parses stanzas and mac from the front of `bytes`, returns the envelope and its encoded length
+  pub(super) fn decode(bytes: &[u8], signature_section: bool) -> anyhow::Result<(Self, usize)> {
+    let mut parser = Parser::new(bytes);
+    let count = parser.u8()?;
+    if count == 0 {
+      return Err(anyhow!("Container has no recipients"));
+    }
+    let stanzas = (0..count)
+      .map(|_| Stanza::decode_from(&mut parser))
+      .collect::<anyhow::Result<Vec<_>>>()?;
+    let signature = match signature_section {
+      false => None,
+      true => match parser.u16()? as usize {
+        0 => None,
+        len => Some(parser.take(len)?.to_vec()),
+      },
+    };
+    let mac = parser.take(MAC_LEN_BYTES)?.to_vec();
+
+    Ok((Self { stanzas, signature_section, signature, mac: Some(mac) }, parser.pos()))
+  }
+
+  /// Reads the encoded envelope from the front of `reader`, nothing more
+  pub(super) async fn read_from<R: AsyncRead + Unpin>(reader: &mut R, signature_section: bool) -> anyhow::Result<(Self, Vec<u8>)> {
+    let mut bytes = Vec::new();
+    read_more(reader, &mut bytes, 1).await?;
+    for _ in 0..bytes[0] {
+      // method, key id len, key id
+      read_more(reader, &mut bytes, 2).await?;
+      let key_id_len = bytes[bytes.len() - 1] as usize;
+      read_more(reader, &mut bytes, key_id_len).await?;
+      read_kdf(reader, &mut bytes).await?;
+      read_more(reader, &mut bytes, 2).await?;
+      let wrapped_len = u16::from_be_bytes([bytes[bytes.len() - 2], bytes[bytes.len() - 1]]) as usize;
+      read_more(reader, &mut bytes, wrapped_len).await?;
+    }
+    if signature_section {
+      read_more(reader, &mut bytes, 2).await?;
+      let signature_len = u16::from_be_bytes([bytes[bytes.len() - 2], bytes[bytes.len() - 1]]) as usize;
+      read_more(reader, &mut bytes, signature_len).await?;
+    }
+    read_more(reader, &mut bytes, MAC_LEN_BYTES).await?;
+
+    let (envelope, len) = Self::decode(&bytes, signature_section)?;
+    if len != bytes.len() {
+      return Err(anyhow!("Malformed recipient stanzas"));
+    }
+    Ok((envelope, bytes))
+  }
+
+  /// Everything the mac is computed over, except the header
+  fn encode_body(&self) -> anyhow::Result<Vec<u8>> {
+    let count = u8::try_from(self.stanzas.len())
+      .map_err(|_| anyhow!("Too many recipients: {}", self.stanzas.len()))?;
+    if count == 0 {
+      return Err(anyhow!("Need at least one recipient"));
+    }
+    let mut res = vec![count];
+    for stanza in &self.stanzas {
+      stanza.encode_into(&mut res)?;
+    }
+    match (&self.signature, self.signature_section) {
+      (_, false) => {}
+      (None, true) => res.extend(0u16.to_be_bytes()),
+      (Some(signature), true) => {
+        let signature_len = u16::try_from(signature.len())
+          .map_err(|_| anyhow!("Signature too long: {} bytes", signature.len()))?;
+        res.extend(signature_len.to_be_bytes());
+        res.extend(signature);
+      }
+    }
+    Ok(res)
+  }
+
+  pub(super) fn verify(&self, header_bytes: &[u8], data_key: &[u8]) -> anyhow::Result<()> {
+    let mac = self.mac.as_ref()
+      .ok_or_else(|| anyhow!("Recipient stanzas have not been written yet"))?;
+    let body = self.encode_body()?;
+
+    mac_for(header_bytes, &body, data_key)?
+      .verify_slice(mac)
+      .map_err(|_| anyhow!("Recipient stanzas have been tampered with"))
+  }
+}
+
+fn compute_mac(header_bytes: &[u8], body: &[u8], data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+  Ok(mac_for(header_bytes, body, data_key)?.finalize().into_bytes().to_vec())
+}
+
+fn mac_for(header_bytes: &[u8], body: &[u8], data_key: &[u8]) -> anyhow::Result<Hmac<Sha256>> {
+  let mac_key = derive_key(data_key, MAC_KDF_INFO)?;
+
+  let mut mac = Hmac::<Sha256>::new_from_slice(mac_key.as_slice())
+    .map_err(|e| anyhow!("Unable to create stanza mac: {e}"))?;
+  mac.update(header_bytes);
+  mac.update(body);
+  Ok(mac)
+}
+
+/// Key for one use of the data key within the envelope, `info` tells the uses apart
+fn derive_key(data_key: &[u8], info: &[u8]) -> anyhow::Result<Zeroizing<[u8; AES_256_LEN_BYTES]>> {
+  let mut key = Zeroizing::new([0u8; AES_256_LEN_BYTES]);
+  Hkdf::<Sha256>::new(None, data_key)
+    .expand(info, key.as_mut_slice())
+    .map_err(|e| anyhow!("Unable to derive envelope key: {e}"))?;
+  Ok(key)
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{generate_data_key, passphrase::TEST_KDF_COST};
+
+    const HEADER: &[u8] = b"test header";
+
+    fn keystore() -> anyhow::Result<Keystore> {
+      Keystore::create(&Passphrase::new("envelope".to_string()), TEST_KDF_COST)
+    }
+
+    #[test]
+    fn test_every_recipient_can_unwrap() -> anyhow::Result<()> {
+      let data_key = generate_data_key();
+      let ks_master = keystore()?;
+      let ks_x25519 = keystore()?;
+      let passphrase = Passphrase::new("shared secret".to_string());
+      let recipients = [
+        Recipient::MasterKey(ks_master.default_master_key().clone()),
+        Recipient::X25519(ks_x25519.default_x25519_identity().expect("identity").recipient()),
+        Recipient::Passphrase(passphrase.clone(), TEST_KDF_COST),
+      ];
+
+      let envelope = Envelope::wrap(&recipients, &data_key)?;
+      let bytes = envelope.encode(HEADER, &data_key)?;
+      let (envelope, len) = Envelope::decode(&bytes, true)?;
+      assert_eq!(bytes.len(), len);
+      assert_eq!(3, envelope.stanzas().len());
+
+      assert_eq!(data_key, *envelope.unwrap(HEADER, Some(&ks_master), None)?);
+      assert_eq!(data_key, *envelope.unwrap(HEADER, Some(&ks_x25519), None)?);
+      assert_eq!(data_key, *envelope.unwrap(HEADER, None, Some(&passphrase))?);
+
+      let stranger = keystore()?;
+      assert!(envelope.needs_passphrase(Some(&stranger)));
+      assert!(!envelope.needs_passphrase(Some(&ks_master)));
+      assert!(envelope.unwrap(HEADER, Some(&stranger), None).is_err());
+      let wrong = Passphrase::new("wrong".to_string());
+      assert!(envelope.unwrap(HEADER, Some(&stranger), Some(&wrong)).is_err_and(|e| e.to_string() == "Wrong passphrase"));
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_detects_tampering() -> anyhow::Result<()> {
+      let data_key = generate_data_key();
+      let ks = keystore()?;
+      let other = keystore()?;
+      let recipients = [
+        Recipient::MasterKey(ks.default_master_key().clone()),
+        Recipient::MasterKey(other.default_master_key().clone()),
+      ];
+      let bytes = Envelope::wrap(&recipients, &data_key)?.encode(HEADER, &data_key)?;
+
+      let (envelope, _) = Envelope::decode(&bytes, true)?;
+      assert!(envelope.unwrap(b"other header", Some(&ks), None).is_err(), "envelope is bound to its header");
+
+      // drop the second stanza but keep the mac
+      let mut stripped = envelope.clone();
+      stripped.stanzas.truncate(1);
+      assert!(stripped.unwrap(HEADER, Some(&ks), None).is_err_and(|e| e.to_string().contains("tampered")));
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_add_and_remove() -> anyhow::Result<()> {
+      let data_key = generate_data_key();
+      let ks = keystore()?;
+      let colleague = keystore()?;
+      let mut envelope = Envelope::wrap(&[Recipient::MasterKey(ks.default_master_key().clone())], &data_key)?;
+
+      let identity = colleague.default_x25519_identity().expect("identity");
+      envelope.add(&Recipient::X25519(identity.recipient()), &data_key)?;
+      envelope.add(&Recipient::X25519(identity.recipient()), &data_key)?;
+      assert_eq!(2, envelope.stanzas().len(), "adding the same key again replaces its stanza");
+
+      let (reread, _) = Envelope::decode(&envelope.encode(HEADER, &data_key)?, true)?;
+      assert_eq!(data_key, *reread.unwrap(HEADER, Some(&colleague), None)?);
+
+      envelope.remove(&identity.recipient().id())?;
+      assert!(envelope.remove(&identity.recipient().id()).is_err());
+      assert!(envelope.remove(ks.default_master_key().id()).is_err(), "last recipient stays");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_signature_survives_rewrap() -> anyhow::Result<()> {
+      let data_key = generate_data_key();
+      let ks = keystore()?;
+      let identity = ks.default_ed25519_identity().expect("identity");
+      let digest = crate::crypto::ed25519::digest(b"hello world");
+      let mut envelope = Envelope::wrap(&[Recipient::MasterKey(ks.default_master_key().clone())], &data_key)?;
+      assert_eq!(None, envelope.signature(&data_key)?);
+      envelope.sign(&Signature::sign(identity, &digest), &data_key)?;
+
+      let colleague = keystore()?.default_x25519_identity().expect("identity").recipient();
+      envelope.add(&Recipient::X25519(colleague), &data_key)?;
+      let bytes = envelope.encode(HEADER, &data_key)?;
+      let (reread, len) = Envelope::decode(&bytes, true)?;
+      assert_eq!(bytes.len(), len);
+      reread.verify(HEADER, &data_key)?;
+
+      let signature = reread.signature(&data_key)?.expect("signed");
+      assert_eq!(identity.signer(), *signature.signer());
+      signature.verify(&digest)?;
+      assert!(reread.signature(&generate_data_key()).is_err(), "only recipients can read the signature");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_version_2_has_no_signature_section() -> anyhow::Result<()> {
+      let data_key = generate_data_key();
+      let ks = keystore()?;
+      let mut envelope = Envelope::wrap(&[Recipient::MasterKey(ks.default_master_key().clone())], &data_key)?;
+      envelope.signature_section = false;
+
+      let bytes = envelope.encode(HEADER, &data_key)?;
+      let (reread, len) = Envelope::decode(&bytes, false)?;
+      assert_eq!(bytes.len(), len);
+      assert_eq!(data_key, *reread.unwrap(HEADER, Some(&ks), None)?);
+
+      let mut reread = reread;
+      let identity = ks.default_ed25519_identity().expect("identity");
+      assert!(reread.sign(&Signature::sign(identity, &[0u8; 32]), &data_key).is_err());
+
+      Ok(())
+    }
+  }
+
+// #endregion ----------------
@@ -0,0 +1,335 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use anyhow::anyhow;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Most shares a secret can be split into, every share needs its own non zero x in GF(256)
+pub const MAX_SHARES: u16 = 255;
+
+/// One share of a byte string, `y[i]` is the i-th byte's polynomial evaluated at `x`
+#[derive(Clone, PartialEq, Eq)]
+pub struct ByteShare {
+  pub x: u8,
+  pub y: Zeroizing<Vec<u8>>,
+}
+
+impl Zeroize for ByteShare {
+  fn zeroize(&mut self) {
+    self.x.zeroize();
+    self.y.zeroize();
+  }
+}
+
+/// Multiplication in GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1,
+/// without tables or branches on the operands so timing does not depend on secrets
+fn mul(mut a: u8, mut b: u8) -> u8 {
+  let mut res = 0u8;
+  for _ in 0..8 {
+    res ^= a & 0u8.wrapping_sub(b & 1);
+    let carry = 0u8.wrapping_sub(a >> 7);
+    a = (a << 1) ^ (carry & 0x1b);
+    b >>= 1;
+  }
+  res
+}
+
+/// Multiplicative inverse, a^254 since a^255 = 1 for every non zero a
+fn inv(a: u8) -> u8 {
+  let mut res = 1u8;
+  let mut base = a;
+  let mut exp = 254u8;
+  while exp > 0 {
+    if exp & 1 == 1 {
+      res = mul(res, base);
+    }
+    base = mul(base, base);
+    exp >>= 1;
+  }
+  res
+}
+
+/// Splits `secret` byte by byte into `n_shares` shares, any `k_thres` of them give it back
+pub fn split(secret: &[u8], n_shares: u16, k_thres: u16) -> anyhow::Result<Vec<ByteShare>> {
+  if secret.is_empty() {
+    return Err(anyhow!("Nothing to split, the secret is empty"));
+  }
+  if k_thres < 2 || k_thres > n_shares {
+    return Err(anyhow!("threshold (k) must be between 2 and the number of shares (n): {k_thres} of {n_shares}"));
+  }
+  if n_shares > MAX_SHARES {
+    return Err(anyhow!("At most {MAX_SHARES} shares, asked for {n_shares}"));
+  }
+
+  // coefficients of x^1 .. x^(k-1) for every byte, the constant term is the secret byte
+  let mut coefficients = Zeroizing::new(vec![0u8; secret.len() * (k_thres as usize - 1)]);
+  OsRng.fill_bytes(&mut coefficients);
+
+  let shares = (1..=n_shares as u8).map(|x| {
+    let y = secret.iter()
+      .zip(coefficients.chunks(k_thres as usize - 1))
+      .map(|(&byte, poly)| {
+        // horner, highest degree first down to the secret byte
+        let acc = poly.iter().rev().fold(0u8, |acc, &c| mul(acc, x) ^ c);
+        mul(acc, x) ^ byte
+      })
+      .collect();
+    ByteShare { x, y: Zeroizing::new(y) }
+  }).collect();
+
+  Ok(shares)
+}
+
+/// Puts a secret back together from at least threshold shares, more shares don't hurt
+///
+/// Shares from different splits or fewer than the threshold give a wrong secret, not an error.
+pub fn combine(shares: &[ByteShare]) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+  check_shares(shares)?;
+  let xs: Vec<u8> = shares.iter().map(|x| x.x).collect();
+  let basis = lagrange_basis(&xs, 0);
+
+  let mut secret = Zeroizing::new(vec![0u8; shares[0].y.len()]);
+  for (share, &l) in shares.iter().zip(&basis) {
+    for (byte, &y) in secret.iter_mut().zip(share.y.iter()) {
+      *byte ^= mul(l, y);
+    }
+  }
+
+  Ok(secret)
+}
+
+/// Like `combine`, but shares beyond the threshold are checked against each other,
+/// returns the secret and the x of every share that is not on the same polynomials as the rest
+///
+/// With `m` shares up to `(m - k_thres) / 2` bad ones are found and left out (Berlekamp-Welch).
+/// Agreeing shares take the constant time path, the decoding only runs once they disagree.
+pub fn combine_robust(shares: &[ByteShare], k_thres: u16) -> anyhow::Result<(Zeroizing<Vec<u8>>, Vec<u8>)> {
+  check_shares(shares)?;
+  let k = k_thres as usize;
+  if k == 0 || shares.len() < k {
+    return Err(anyhow!("Need {k} shares to recover the secret, only have {}", shares.len()));
+  }
+  if shares.len() == k || consistent(shares, k) {
+    return Ok((combine(shares)?, vec![]));
+  }
+
+  let max_bad = (shares.len() - k) / 2;
+  if max_bad == 0 {
+    return Err(anyhow!("Shares disagree, one of them is bad. Add another share to find out which"));
+  }
+  let xs: Vec<u8> = shares.iter().map(|x| x.x).collect();
+  let mut secret = Zeroizing::new(Vec::with_capacity(shares[0].y.len()));
+  let mut bad = vec![false; shares.len()];
+  let mut ys = Zeroizing::new(vec![0u8; shares.len()]);
+  for i in 0..shares[0].y.len() {
+    for (y, share) in ys.iter_mut().zip(shares) {
+      *y = share.y[i];
+    }
+    let poly = berlekamp_welch(&xs, &ys, k, max_bad)
+      .ok_or_else(|| anyhow!("Too many bad shares to tell them apart, {max_bad} at most with {} shares", shares.len()))?;
+    for (j, (x, y)) in xs.iter().zip(ys.iter()).enumerate() {
+      bad[j] |= eval(&poly, *x) != *y;
+    }
+    secret.push(poly[0]);
+  }
+
+  let bad: Vec<u8> = xs.iter().zip(&bad).filter(|(_, b)| **b).map(|(x, _)| *x).collect();
+  if bad.len() > max_bad {
+    return Err(anyhow!("Too many bad shares to tell them apart, {max_bad} at most with {} shares", shares.len()));
+  }
+  Ok((secret, bad))
+}
+
+fn check_shares(shares: &[ByteShare]) -> anyhow::Result<()> {
+  let first = shares.first().ok_or_else(|| anyhow!("No shares to combine"))?;
+  for (i, share) in shares.iter().enumerate() {
+    if share.x == 0 {
+      return Err(anyhow!("Share index 0 would be the secret itself"));
+    }
+    if share.y.len() != first.y.len() {
+      return Err(anyhow!("Share #{} is {} bytes, share #{} is {}", share.x, share.y.len(), first.x, first.y.len()));
+    }
+    if shares[..i].iter().any(|x| x.x == share.x) {
+      return Err(anyhow!("Share #{} is there twice", share.x));
+    }
+  }
+  Ok(())
+}
+
+/// Lagrange basis polynomials of `xs` evaluated at `at`, subtraction is xor
+fn lagrange_basis(xs: &[u8], at: u8) -> Vec<u8> {
+  xs.iter().map(|&xi| {
+    xs.iter()
+      .filter(|&&xj| xj != xi)
+      .fold(1u8, |acc, &xj| mul(acc, mul(xj ^ at, inv(xj ^ xi))))
+  }).collect()
+}
+
+/// Whether the shares past the first `k` lie on the polynomials the first `k` define
+fn consistent(shares: &[ByteShare], k: usize) -> bool {
+  let xs: Vec<u8> = shares[..k].iter().map(|x| x.x).collect();
+  let mut diff = 0u8;
+  for other in &shares[k..] {
+    let basis = lagrange_basis(&xs, other.x);
+    for (i, &y) in other.y.iter().enumerate() {
+      let expected = shares[..k].iter().zip(&basis).fold(0u8, |acc, (share, &l)| acc ^ mul(l, share.y[i]));
+      diff |= expected ^ y;
+    }
+  }
+  diff == 0
+}
+
+/// `poly` with the lowest degree first, at `x`
+fn eval(poly: &[u8], x: u8) -> u8 {
+  poly.iter().rev().fold(0u8, |acc, &c| mul(acc, x) ^ c)
+}
+
+/// The polynomial of degree below `k` through all but at most `max_bad` of the points, lowest degree first
+///
+/// Solves `Q(x_i) = y_i E(x_i)` for Q of degree below `k + max_bad` and monic E of degree `max_bad`,
+/// E vanishes on the bad points and Q / E is the polynomial.
+fn berlekamp_welch(xs: &[u8], ys: &[u8], k: usize, max_bad: usize) -> Option<Vec<u8>> {
+  let q_len = k + max_bad;
+  let unknowns = q_len + max_bad;
+  let mut rows: Zeroizing<Vec<Vec<u8>>> = Zeroizing::new(xs.iter().zip(ys).map(|(&x, &y)| {
+    let powers: Vec<u8> = (0..=q_len).scan(1u8, |acc, _| {
+      let p = *acc;
+      *acc = mul(*acc, x);
+      Some(p)
+    }).collect();
+    let mut row = powers[..q_len].to_vec();
+    row.extend(powers[..max_bad].iter().map(|p| mul(y, *p)));
+    row.push(mul(y, powers[max_bad]));
+    row
+  }).collect());
+
+  // gauss-jordan, free unknowns stay 0
+  let mut pivots = vec![];
+  let mut r = 0;
+  for col in 0..unknowns {
+    let Some(pivot) = (r..rows.len()).find(|&i| rows[i][col] != 0) else {
+      continue;
+    };
+    rows.swap(r, pivot);
+    let scale = inv(rows[r][col]);
+    for x in rows[r].iter_mut() {
+      *x = mul(*x, scale);
+    }
+    let pivot_row = rows[r].clone();
+    for (i, row) in rows.iter_mut().enumerate() {
+      if i != r && row[col] != 0 {
+        let f = row[col];
+        for (x, p) in row.iter_mut().zip(&pivot_row) {
+          *x ^= mul(f, *p);
+        }
+      }
+    }
+    pivots.push(col);
+    r += 1;
+  }
+  if rows[r..].iter().any(|row| row[unknowns] != 0) {
+    return None;
+  }
+  let mut solution = vec![0u8; unknowns];
+  for (i, &col) in pivots.iter().enumerate() {
+    solution[col] = rows[i][unknowns];
+  }
+
+  // Q / E, E is monic so no inverses are needed
+  let mut q = solution[..q_len].to_vec();
+  let mut e = solution[q_len..].to_vec();
+  e.push(1);
+  let mut quotient = vec![0u8; k];
+  for i in (0..k).rev() {
+    let c = q[i + max_bad];
+    quotient[i] = c;
+    for (j, &ej) in e.iter().enumerate() {
+      q[i + j] ^= mul(c, ej);
+    }
+  }
+  q.iter().all(|x| *x == 0).then_some(quotient)
+}
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_inverse() {
+      assert_eq!(0x01, mul(0x53, 0xca), "fips-197 example");
+      assert_eq!(0xc1, mul(0x57, 0x83), "fips-197 example");
+      for a in 1..=255u8 {
+        assert_eq!(1, mul(a, inv(a)), "inverse of {a}");
+      }
+    }
+
+    #[test]
+    fn test_split_combine_any_length() -> anyhow::Result<()> {
+      for len in [1, 2, 32, 60, 1000] {
+        let secret: Vec<u8> = (0..len).map(|x| (x * 7 + 3) as u8).collect();
+        let shares = split(&secret, 5, 3)?;
+        assert_eq!(5, shares.len());
+
+        assert_eq!(secret, *combine(&shares[..3])?);
+        assert_eq!(secret, *combine(&[shares[4].clone(), shares[0].clone(), shares[2].clone()])?);
+        assert_eq!(secret, *combine(&shares)?, "more than threshold");
+        if len >= 32 {
+          assert_ne!(secret, *combine(&shares[..2])?, "fewer than threshold");
+        }
+      }
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_combine_robust() -> anyhow::Result<()> {
+      let secret: Vec<u8> = (0..60).map(|x| (x * 11 + 5) as u8).collect();
+      let shares = split(&secret, 7, 3)?;
+
+      let (recovered, bad) = combine_robust(&shares, 3)?;
+      assert_eq!(secret, *recovered);
+      assert!(bad.is_empty());
+
+      // 7 shares of a 3 of n split find up to 2 bad ones
+      let mut tampered = shares.clone();
+      tampered[1].y[0] ^= 0x40;
+      tampered[4].y.iter_mut().for_each(|x| *x = x.wrapping_add(1));
+      assert_ne!(secret, *combine(&tampered)?, "plain combine is fooled");
+      let (recovered, bad) = combine_robust(&tampered, 3)?;
+      assert_eq!(secret, *recovered);
+      assert_eq!(vec![2, 5], bad);
+
+      // a bad share among the first k is found just the same
+      let mut first_bad = shares[..5].to_vec();
+      first_bad[0].y[3] ^= 9;
+      let (recovered, bad) = combine_robust(&first_bad, 3)?;
+      assert_eq!(secret, *recovered);
+      assert_eq!(vec![1], bad);
+
+      tampered[6].y[10] ^= 1;
+      assert!(combine_robust(&tampered, 3).unwrap_err().to_string().contains("Too many bad shares"));
+      let err = combine_robust(&[shares[0].clone(), shares[2].clone(), shares[3].clone(), tampered[4].clone()], 3).unwrap_err();
+      assert!(err.to_string().contains("Add another share"), "one spare share only detects");
+      assert!(combine_robust(&shares[..2], 3).is_err(), "fewer than threshold");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_split_limits() -> anyhow::Result<()> {
+      assert!(split(b"", 3, 2).is_err());
+      assert!(split(b"x", 3, 1).is_err());
+      assert!(split(b"x", 3, 4).is_err());
+      assert!(split(b"x", 256, 2).is_err());
+
+      let shares = split(b"all of them", 255, 255)?;
+      assert_eq!(b"all of them".to_vec(), *combine(&shares)?);
+
+      let mut twice = split(b"x", 3, 2)?;
+      twice[1].x = twice[0].x;
+      assert!(combine(&twice).is_err());
+
+      Ok(())
+    }
+  }
+
+// #endregion ----------------
@@ -0,0 +1,452 @@
+use std::fmt;
+
+use aes_gcm::{
+  aead::{rand_core::RngCore, Aead, KeyInit, OsRng, Payload},
+  AeadCore, Aes256Gcm, Key,
+};
+use anyhow::anyhow;
+use zeroize::Zeroizing;
+
+use super::{
+  container::{KdfAlgorithm, KdfParams, Parser},
+  ed25519::{Ed25519Identity, Ed25519Signer, ED25519_KEY_LEN_BYTES},
+  passphrase::{self, KdfCost, Passphrase},
+  share::{FileId, FILE_ID_LEN_BYTES},
+  x25519::{X25519Identity, X25519_KEY_LEN_BYTES},
+  AES_256_LEN_BYTES, NONCE_LEN_BYTES,
+};
+
+/// Every keystore file starts with these bytes
+pub const KEYSTORE_MAGIC: [u8; 4] = *b"ENCK";
+/// Current keystore format version
+pub const KEYSTORE_VERSION: u8 = 1;
+/// master key ids are random, not derived from the key
+pub const MASTER_KEY_ID_LEN_BYTES: usize = 16;
+
+/// A key encryption key owned by a user or team, referred to by its id
+#[derive(Clone)]
+pub struct MasterKey {
+  id: [u8; MASTER_KEY_ID_LEN_BYTES],
+  key: Zeroizing<[u8; AES_256_LEN_BYTES]>,
+}
+
+impl MasterKey {
+  pub fn generate() -> Self {
+    let mut id = [0u8; MASTER_KEY_ID_LEN_BYTES];
+    OsRng.fill_bytes(&mut id);
+    let mut key = Zeroizing::new([0u8; AES_256_LEN_BYTES]);
+    OsRng.fill_bytes(key.as_mut_slice());
+
+    Self { id, key }
+  }
+
+  pub fn id(&self) -> &[u8] {
+    &self.id
+  }
+
+  pub(super) fn key(&self) -> &[u8; AES_256_LEN_BYTES] {
+    &self.key
+  }
+}
+
+impl fmt::Debug for MasterKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "MasterKey({})", hex::encode(self.id))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum EntryKind {
+  MasterKey = 1,
+  /// x25519 secret key, the id is the id of its public key
+  X25519Identity = 2,
+  /// ed25519 secret key, the id is the id of its public key
+  Ed25519Identity = 3,
+  /// ed25519 public key of someone else whose signatures we trust, the "secret" is the public key
+  KnownSigner = 4,
+  /// latest epoch the shares of a split file were refreshed to, the id is the file id, the "secret" a u32
+  ShareEpoch = 5,
+}
+
+impl TryFrom<u8> for EntryKind {
+  type Error = anyhow::Error;
+
+  fn try_from(value: u8) -> anyhow::Result<Self> {
+    match value {
+      1 => Ok(Self::MasterKey),
+      2 => Ok(Self::X25519Identity),
+      3 => Ok(Self::Ed25519Identity),
+      4 => Ok(Self::KnownSigner),
+      5 => Ok(Self::ShareEpoch),
+      x => Err(anyhow!("Unknown keystore entry kind: {x}")),
+    }
+  }
+}
+
+/// Local store of secret keys, encrypted at rest with a passphrase
+///
+/// File layout (integers are big endian):
+///
+/// ```text
+/// magic        4 bytes  "ENCK"
+/// version      1 byte
+/// kdf params   algorithm u8, m_cost u32, t_cost u32, p_cost u32, salt len u8, salt
+/// nonce        12 bytes
+/// body         aes-256-gcm encrypted entries, everything before the nonce is associated data
+/// ```
+///
+/// The body is a u16 entry count followed by entries of
+/// `kind u8 | id len u8 | id | secret len u16 | secret`.
+/// The first master key is the one used for new files, the first x25519 identity
+/// is the one handed out as our public key, the first ed25519 identity signs files.
+#[derive(Clone)]
+pub struct Keystore {
+  kdf: KdfParams,
+  /// derived from the passphrase on unlock so the store can be saved again without asking
+  kek: Zeroizing<[u8; AES_256_LEN_BYTES]>,
+  master_keys: Vec<MasterKey>,
+  x25519_identities: Vec<X25519Identity>,
+  ed25519_identities: Vec<Ed25519Identity>,
+  /// signers trusted on top of our own identities
+  known_signers: Vec<Ed25519Signer>,
+  /// files whose shares were refreshed, shares of older epochs no longer open them
+  share_epochs: Vec<(FileId, u32)>,
+}
+
+impl fmt::Debug for Keystore {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Keystore")
+      .field("master_keys", &self.master_keys)
+      .field("x25519_identities", &self.x25519_identities)
+      .field("ed25519_identities", &self.ed25519_identities)
+      .field("known_signers", &self.known_signers)
+      .field("share_epochs", &self.share_epochs.len())
+      .finish_non_exhaustive()
+  }
+}
+
+impl Keystore {
+  /// New keystore holding a freshly generated master key, x25519 identity and ed25519 identity
+  pub fn create(passphrase: &Passphrase, cost: KdfCost) -> anyhow::Result<Self> {
+    if passphrase.is_empty() {
+      return Err(anyhow!("Passphrase must not be empty"));
+    }
+    let kdf = passphrase::new_kdf_params(cost);
+    let kek = passphrase::derive_key(passphrase, &kdf)?;
+
+    Ok(Self {
+      kdf,
+      kek,
+      master_keys: vec![MasterKey::generate()],
+      x25519_identities: vec![X25519Identity::generate()],
+      ed25519_identities: vec![Ed25519Identity::generate()],
+      known_signers: Vec::new(),
+      share_epochs: Vec::new(),
+    })
+  }
+
+  /// Decrypts a keystore file
+  pub fn unlock(bytes: &[u8], passphrase: &Passphrase) -> anyhow::Result<Self> {
+    let mut parser = Parser::new(bytes);
+    if parser.take(KEYSTORE_MAGIC.len())? != KEYSTORE_MAGIC {
+      return Err(anyhow!("Not a keystore file (bad magic)"));
+    }
+    let version = parser.u8()?;
+    if version != KEYSTORE_VERSION {
+      return Err(anyhow!("Unsupported keystore version: {version}"));
+    }
+    let kdf = KdfParams::decode_from(KdfAlgorithm::try_from(parser.u8()?)?, &mut parser)?;
+    let aad = &bytes[..parser.pos()];
+    let nonce = parser.take(NONCE_LEN_BYTES)?;
+
+    let kek = passphrase::derive_key(passphrase, &kdf)?;
+    let key: &Key<Aes256Gcm> = (&*kek).into();
+    let cipher = Aes256Gcm::new(key);
+    let body = Zeroizing::new(cipher.decrypt(nonce.into(), Payload { msg: parser.rest(), aad })
+      .map_err(|_| anyhow!("Wrong passphrase or corrupted keystore"))?);
+
+    let mut keystore = Self {
+      kdf,
+      kek,
+      master_keys: Vec::new(),
+      x25519_identities: Vec::new(),
+      ed25519_identities: Vec::new(),
+      known_signers: Vec::new(),
+      share_epochs: Vec::new(),
+    };
+    keystore.decode_body(&body)?;
+    if keystore.master_keys.is_empty() {
+      return Err(anyhow!("Keystore holds no master key"));
+    }
+
+    Ok(keystore)
+  }
+
+  /// Encrypts the keystore for writing to disk
+  pub fn seal(&self) -> anyhow::Result<Vec<u8>> {
+    let mut res = Vec::new();
+    res.extend(KEYSTORE_MAGIC);
+    res.push(KEYSTORE_VERSION);
+    self.kdf.encode_into(&mut res)?;
+
+    let body = self.encode_body()?;
+    let key: &Key<Aes256Gcm> = (&*self.kek).into();
+    let cipher = Aes256Gcm::new(key);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, Payload { msg: &body, aad: &res })
+      .map_err(|e| anyhow!("Unable to encrypt keystore: {e}"))?;
+
+    res.extend(nonce);
+    res.extend(ciphertext);
+    Ok(res)
+  }
+
+  /// Master key used to wrap data keys of new files
+  pub fn default_master_key(&self) -> &MasterKey {
+    &self.master_keys[0]
+  }
+
+  pub fn master_key(&self, id: &[u8]) -> Option<&MasterKey> {
+    self.master_keys.iter().find(|x| x.id() == id)
+  }
+
+  /// Identity whose public key we hand out, absent in keystores created before public keys existed
+  pub fn default_x25519_identity(&self) -> Option<&X25519Identity> {
+    self.x25519_identities.first()
+  }
+
+  /// Identity whose public key has the id `id`
+  pub fn x25519_identity(&self, id: &[u8]) -> Option<&X25519Identity> {
+    self.x25519_identities.iter().find(|x| x.recipient().id() == id)
+  }
+
+  /// Identity we sign files with, absent in keystores created before signing existed
+  pub fn default_ed25519_identity(&self) -> Option<&Ed25519Identity> {
+    self.ed25519_identities.first()
+  }
+
+  /// True for our own signing keys and those added with `add_known_signer`
+  pub fn is_known_signer(&self, signer: &Ed25519Signer) -> bool {
+    self.ed25519_identities.iter().any(|x| x.signer() == *signer) || self.known_signers.contains(signer)
+  }
+
+  /// Trusts signatures made by `signer` from now on
+  pub fn add_known_signer(&mut self, signer: Ed25519Signer) {
+    if !self.is_known_signer(&signer) {
+      self.known_signers.push(signer);
+    }
+  }
+
+  /// Oldest epoch whose shares still open the file `file_id`, 0 unless its shares were refreshed
+  pub fn share_epoch(&self, file_id: &FileId) -> u32 {
+    self.share_epochs.iter()
+      .find(|(id, _)| id == file_id)
+      .map_or(0, |(_, epoch)| *epoch)
+  }
+
+  /// Rejects shares of `file_id` from before `epoch` from now on, never goes back to an older epoch
+  pub fn set_share_epoch(&mut self, file_id: FileId, epoch: u32) {
+    match self.share_epochs.iter_mut().find(|(id, _)| *id == file_id) {
+      Some((_, current)) => *current = epoch.max(*current),
+      None => self.share_epochs.push((file_id, epoch)),
+    }
+  }
+
+  /// Adds a master key, e.g. one shared by a team, so its files can be opened
+  #[allow(dead_code)]
+  pub fn add_master_key(&mut self, master_key: MasterKey) {
+    if self.master_key(master_key.id()).is_none() {
+      self.master_keys.push(master_key);
+    }
+  }
+
+  fn encode_body(&self) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let count = self.master_keys.len() + self.x25519_identities.len() + self.ed25519_identities.len() + self.known_signers.len() + self.share_epochs.len();
+    let count = u16::try_from(count)
+      .map_err(|_| anyhow!("Too many keystore entries"))?;
+    let mut body = Zeroizing::new(Vec::new());
+    body.extend(count.to_be_bytes());
+    for master_key in &self.master_keys {
+      body.push(EntryKind::MasterKey as u8);
+      body.push(MASTER_KEY_ID_LEN_BYTES as u8);
+      body.extend(master_key.id);
+      body.extend((AES_256_LEN_BYTES as u16).to_be_bytes());
+      body.extend(master_key.key.iter());
+    }
+    for identity in &self.x25519_identities {
+      let id = identity.recipient().id();
+      body.push(EntryKind::X25519Identity as u8);
+      body.push(id.len() as u8);
+      body.extend(id);
+      body.extend((X25519_KEY_LEN_BYTES as u16).to_be_bytes());
+      body.extend(identity.to_bytes().iter());
+    }
+    for identity in &self.ed25519_identities {
+      let id = identity.signer().id();
+      body.push(EntryKind::Ed25519Identity as u8);
+      body.push(id.len() as u8);
+      body.extend(id);
+      body.extend((ED25519_KEY_LEN_BYTES as u16).to_be_bytes());
+      body.extend(identity.to_bytes().iter());
+    }
+    for signer in &self.known_signers {
+      let id = signer.id();
+      body.push(EntryKind::KnownSigner as u8);
+      body.push(id.len() as u8);
+      body.extend(id);
+      body.extend((ED25519_KEY_LEN_BYTES as u16).to_be_bytes());
+      body.extend(signer.as_bytes());
+    }
+    for (file_id, epoch) in &self.share_epochs {
+      body.push(EntryKind::ShareEpoch as u8);
+      body.push(FILE_ID_LEN_BYTES as u8);
+      body.extend(file_id);
+      body.extend((size_of::<u32>() as u16).to_be_bytes());
+      body.extend(epoch.to_be_bytes());
+    }
+    Ok(body)
+  }
+
+  /// Adds the entries of a decrypted body to this keystore
+  fn decode_body(&mut self, body: &[u8]) -> anyhow::Result<()> {
+    let mut parser = Parser::new(body);
+    let count = parser.u16()?;
+
+    for _ in 0..count {
+      let kind = EntryKind::try_from(parser.u8()?)?;
+      let id_len = parser.u8()? as usize;
+      let id = parser.take(id_len)?;
+      let secret_len = parser.u16()? as usize;
+      let secret = parser.take(secret_len)?;
+
+      match kind {
+        EntryKind::MasterKey => {
+          let id = id.try_into()
+            .map_err(|_| anyhow!("Bad master key id length: {id_len}"))?;
+          let mut key = Zeroizing::new([0u8; AES_256_LEN_BYTES]);
+          if secret.len() != AES_256_LEN_BYTES {
+            return Err(anyhow!("Bad master key length: {secret_len}"));
+          }
+          key.copy_from_slice(secret);
+          self.master_keys.push(MasterKey { id, key });
+        }
+        EntryKind::X25519Identity => {
+          let identity = X25519Identity::from_bytes(secret)?;
+          if identity.recipient().id() != id {
+            return Err(anyhow!("X25519 identity does not match its id"));
+          }
+          self.x25519_identities.push(identity);
+        }
+        EntryKind::Ed25519Identity => {
+          let identity = Ed25519Identity::from_bytes(secret)?;
+          if identity.signer().id() != id {
+            return Err(anyhow!("Ed25519 identity does not match its id"));
+          }
+          self.ed25519_identities.push(identity);
+        }
+        EntryKind::KnownSigner => {
+          let signer = Ed25519Signer::from_bytes(secret)?;
+          if signer.id() != id {
+            return Err(anyhow!("Known signer does not match its id"));
+          }
+          self.known_signers.push(signer);
+        }
+        EntryKind::ShareEpoch => {
+          let file_id = id.try_into()
+            .map_err(|_| anyhow!("Bad file id length: {id_len}"))?;
+          let epoch = secret.try_into()
+            .map_err(|_| anyhow!("Bad share epoch length: {secret_len}"))?;
+          self.share_epochs.push((file_id, u32::from_be_bytes(epoch)));
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::passphrase::TEST_KDF_COST;
+
+    #[test]
+    fn test_keystore_roundtrip() -> anyhow::Result<()> {
+      let passphrase = Passphrase::new("keystore pass".to_string());
+      let mut keystore = Keystore::create(&passphrase, TEST_KDF_COST)?;
+      keystore.add_master_key(MasterKey::generate());
+
+      let sealed = keystore.seal()?;
+      let act = Keystore::unlock(&sealed, &passphrase)?;
+
+      assert_eq!(2, act.master_keys.len());
+      assert_eq!(keystore.default_master_key().id(), act.default_master_key().id());
+      let identity = act.default_x25519_identity().expect("new keystores have an identity");
+      assert_eq!(keystore.default_x25519_identity().map(|x| x.recipient()), Some(identity.recipient()));
+      assert!(act.x25519_identity(&identity.recipient().id()).is_some());
+      for (exp, act) in keystore.master_keys.iter().zip(act.master_keys.iter()) {
+        assert_eq!(exp.key(), act.key());
+      }
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_keystore_keeps_signers() -> anyhow::Result<()> {
+      let passphrase = Passphrase::new("keystore pass".to_string());
+      let mut keystore = Keystore::create(&passphrase, TEST_KDF_COST)?;
+      let own = keystore.default_ed25519_identity().expect("new keystores sign").signer();
+      let colleague = Ed25519Identity::generate().signer();
+      assert!(keystore.is_known_signer(&own));
+      assert!(!keystore.is_known_signer(&colleague));
+      keystore.add_known_signer(colleague);
+
+      let act = Keystore::unlock(&keystore.seal()?, &passphrase)?;
+      assert_eq!(Some(own), act.default_ed25519_identity().map(|x| x.signer()));
+      assert!(act.is_known_signer(&colleague));
+      assert!(!act.is_known_signer(&Ed25519Identity::generate().signer()));
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_keystore_keeps_share_epochs() -> anyhow::Result<()> {
+      let passphrase = Passphrase::new("keystore pass".to_string());
+      let mut keystore = Keystore::create(&passphrase, TEST_KDF_COST)?;
+      assert_eq!(0, keystore.share_epoch(&[1u8; FILE_ID_LEN_BYTES]));
+      keystore.set_share_epoch([1u8; FILE_ID_LEN_BYTES], 2);
+      keystore.set_share_epoch([1u8; FILE_ID_LEN_BYTES], 1);
+
+      let act = Keystore::unlock(&keystore.seal()?, &passphrase)?;
+      assert_eq!(2, act.share_epoch(&[1u8; FILE_ID_LEN_BYTES]), "epochs never go back");
+      assert_eq!(0, act.share_epoch(&[2u8; FILE_ID_LEN_BYTES]));
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_keystore_wrong_passphrase()-> anyhow::Result<()> {
+      let keystore = Keystore::create(&Passphrase::new("right".to_string()), TEST_KDF_COST)?;
+      let sealed = keystore.seal()?;
+
+      let res = Keystore::unlock(&sealed, &Passphrase::new("wrong".to_string()));
+      assert!(res.is_err());
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_master_keys_are_unique() {
+      let k0 = MasterKey::generate();
+      let k1 = MasterKey::generate();
+
+      assert_ne!(k0.id(), k1.id());
+      assert_ne!(k0.key(), k1.key());
+      assert!(!format!("{k0:?}").contains(&hex::encode(k0.key())), "debug output must not contain the key");
+    }
+  }
+
+// #endregion ----------------
@@ -0,0 +1,184 @@
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use zeroize::{Zeroize, Zeroizing};
+
+/// 1024 words, 4 to 8 letters, no two share their first 4 letters
+const WORDLIST: &str = include_str!("wordlist.txt");
+/// Bits carried by one word
+const RADIX_BITS: u32 = 10;
+/// Words of the rs1024 checksum ending every mnemonic
+const CHECKSUM_WORDS: usize = 3;
+/// Mixed into the checksum, so mnemonics of other tools never pass it
+const CUSTOMIZATION: &[u8] = b"encryption-app share";
+
+fn words() -> &'static [&'static str] {
+  static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+  WORDS.get_or_init(|| WORDLIST.lines().collect())
+}
+
+/// Index of `word` in the word list, the first 4 letters are enough
+fn word_index(word: &str) -> Option<u16> {
+  let word = word.to_ascii_lowercase();
+  let words = words();
+  match words.binary_search(&word.as_str()) {
+    Ok(i) => Some(i as u16),
+    Err(_) if word.len() == 4 => words.iter().position(|x| x.starts_with(&word)).map(|i| i as u16),
+    Err(_) => None,
+  }
+}
+
+/// Reed-Solomon code over GF(1024) as in SLIP-39, detects any 3 wrong words
+fn rs1024_polymod(values: impl IntoIterator<Item = u32>) -> u32 {
+  const GEN: [u32; 10] = [
+    0xe0e040, 0x1c1c080, 0x3838100, 0x7070200, 0xe0e0009,
+    0x1c0c2412, 0x38086c24, 0x3090fc48, 0x21b1f890, 0x3f3f120,
+  ];
+  let mut chk = 1u32;
+  for v in values {
+    let b = chk >> 20;
+    chk = ((chk & 0xfffff) << 10) ^ v;
+    for (i, g) in GEN.iter().enumerate() {
+      if (b >> i) & 1 == 1 {
+        chk ^= g;
+      }
+    }
+  }
+  chk
+}
+
+fn checksum_input(data: &[u16]) -> impl Iterator<Item = u32> + '_ {
+  CUSTOMIZATION.iter().map(|x| *x as u32).chain(data.iter().map(|x| *x as u32))
+}
+
+/// Words for `bytes`, SLIP-39 style
+///
+/// The first word is the number of zero bits padding the front of the data to whole words,
+/// then come the data words and 3 checksum words.
+pub fn encode(bytes: &[u8]) -> Zeroizing<String> {
+  let bits = bytes.len() as u32 * 8;
+  let padding = (RADIX_BITS - bits % RADIX_BITS) % RADIX_BITS;
+
+  let mut data = Zeroizing::new(vec![padding as u16]);
+  let mut acc = 0u32;
+  let mut acc_bits = padding;
+  for byte in bytes {
+    acc = (acc << 8) | *byte as u32;
+    acc_bits += 8;
+    if acc_bits >= RADIX_BITS {
+      acc_bits -= RADIX_BITS;
+      data.push((acc >> acc_bits) as u16 & 0x3ff);
+    }
+  }
+  acc.zeroize();
+
+  let chk = rs1024_polymod(checksum_input(&data).chain([0; CHECKSUM_WORDS])) ^ 1;
+  data.extend((0..CHECKSUM_WORDS).rev().map(|i| (chk >> (RADIX_BITS * i as u32)) as u16 & 0x3ff));
+
+  let words = words();
+  Zeroizing::new(data.iter().map(|x| words[*x as usize]).collect::<Vec<_>>().join(" "))
+}
+
+/// Bytes of a mnemonic written by `encode`, any whitespace between the words and any case
+pub fn decode(s: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+  let data = Zeroizing::new(s.split_whitespace()
+    .enumerate()
+    .map(|(i, word)| word_index(word).ok_or_else(|| anyhow!("Word {} is not in the word list", i + 1)))
+    .collect::<anyhow::Result<Vec<_>>>()?);
+  if data.len() < CHECKSUM_WORDS + 2 {
+    return Err(anyhow!("Too few words, got {}", data.len()));
+  }
+  if rs1024_polymod(checksum_input(&data)) != 1 {
+    return Err(anyhow!("Mnemonic checksum mismatch, a word is wrong, missing or out of order"));
+  }
+
+  let data = &data[..data.len() - CHECKSUM_WORDS];
+  let padding = data[0] as u32;
+  let bits = (data.len() as u32 - 1) * RADIX_BITS;
+  if padding >= RADIX_BITS || !(bits - padding).is_multiple_of(8) {
+    return Err(anyhow!("Mnemonic has a bad padding word"));
+  }
+
+  let mut bytes = Zeroizing::new(Vec::with_capacity(((bits - padding) / 8) as usize));
+  let mut acc = 0u32;
+  let mut acc_bits = 0;
+  let mut skip = padding;
+  for word in &data[1..] {
+    acc = (acc << RADIX_BITS) | *word as u32;
+    acc_bits += RADIX_BITS;
+    // the padding fits in the first word
+    if skip > 0 {
+      if acc >> (acc_bits - skip) != 0 {
+        return Err(anyhow!("Mnemonic padding is not zero"));
+      }
+      acc_bits -= skip;
+      skip = 0;
+    }
+    while acc_bits >= 8 {
+      acc_bits -= 8;
+      bytes.push((acc >> acc_bits) as u8);
+    }
+    acc &= (1 << acc_bits) - 1;
+  }
+  acc.zeroize();
+
+  Ok(bytes)
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wordlist() {
+      let words = words();
+      assert_eq!(1 << RADIX_BITS, words.len());
+      assert!(words.windows(2).all(|x| x[0] < x[1]), "sorted, no duplicates");
+      assert!(words.windows(2).all(|x| x[0][..4] != x[1][..4]), "unique 4 letter prefixes");
+      assert!(words.iter().all(|x| (4..=8).contains(&x.len())));
+      assert!(words.iter().all(|x| !x.chars().all(|c| c.is_ascii_hexdigit())), "pasted words never look like hex");
+    }
+
+    #[test]
+    fn test_mnemonic_roundtrip() -> anyhow::Result<()> {
+      for len in [1, 4, 5, 16, 18, 100, 144] {
+        let bytes: Vec<u8> = (0..len).map(|x| (x * 37 + 11) as u8).collect();
+        let mnemonic = encode(&bytes);
+        assert_eq!(bytes, *decode(&mnemonic)?, "{len} bytes");
+        assert_eq!(bytes, *decode(&mnemonic.to_uppercase().replace(' ', "\n  "))?);
+      }
+      let zeros = [0u8; 9];
+      assert_eq!(zeros.to_vec(), *decode(&encode(&zeros))?, "leading zero bytes are not padding");
+
+      let short: String = encode(b"hello").split(' ').map(|x| &x[..4]).collect::<Vec<_>>().join(" ");
+      assert_eq!(b"hello".to_vec(), *decode(&short)?, "4 letter prefixes");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_mnemonic_catches_mistakes() {
+      let mnemonic = encode(b"a share on paper");
+      let words: Vec<&str> = mnemonic.split(' ').collect();
+
+      for i in 0..words.len() {
+        for replacement in ["academic", "zero", "lunar"] {
+          if words[i] == replacement {
+            continue;
+          }
+          let mut typo = words.clone();
+          typo[i] = replacement;
+          assert!(decode(&typo.join(" ")).is_err(), "word {i} replaced by {replacement}");
+        }
+      }
+      let mut swapped = words.clone();
+      swapped.swap(2, 3);
+      assert!(decode(&swapped.join(" ")).is_err(), "swapped words");
+      assert!(decode(&words[1..].join(" ")).is_err(), "missing word");
+      assert!(decode(&format!("{} notaword", mnemonic.as_str())).unwrap_err().to_string().contains("Word"));
+    }
+  }
+
+// #endregion ----------------
@@ -0,0 +1,1309 @@
+use std::iter::zip;
+
+use aes_gcm::{
+  aead::{Aead, KeyInit, OsRng, Payload},
+  AeadCore, Aes256Gcm, Key,
+};
+use anyhow::anyhow;
+use crypto_bigint::{NonZero, RandomMod, U128, U64};
+// use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::error;
+use zeroize::{Zeroize, Zeroizing};
+
+mod container;
+mod ed25519;
+mod envelope;
+mod gf256;
+mod keystore;
+mod mnemonic;
+mod passphrase;
+mod share;
+mod stream;
+mod vault;
+mod vss;
+mod x25519;
+
+pub use container::{key_id, AeadAlgorithm, ContainerHeader, ContainerReader, ContainerStreamReader, ContainerWriter, KeyWrapMethod};
+pub use ed25519::{digest_reader, ContentDigest, Ed25519Identity, Ed25519Signer, Signature, SignatureStatus};
+pub use envelope::{Envelope, Recipient, Stanza};
+pub use gf256::MAX_SHARES;
+pub use keystore::{Keystore, MasterKey};
+pub use passphrase::{calibrated_cost, KdfCost, Passphrase};
+pub use share::{combine_key, file_id_reader, refresh_shares, split_key, split_key_groups, split_secret, FileId, PasswordVault, Share, ShareBundle, ShareId, BUNDLE_MAGIC, PASSWORD_VAULT_MAGIC, SHARE_MAGIC};
+pub use vault::{create_vault, create_vault_groups, refresh_vault_shares, unlock_vault, VaultHeader, VaultKey};
+pub use x25519::{X25519Identity, X25519Recipient};
+
+
+/// Compiled-in key wrapping key from before keystores existed.
+/// Only used to open old files, new files are wrapped with a keystore master key.
+const LEGACY_KEY_WRAPPER_KEY: [u8; 32] = [44, 122, 25, 25, 157, 162, 122, 10, 189, 72, 169, 15, 91, 54, 194, 213, 145, 15, 10, 165, 181, 142, 49, 122, 201, 27, 157, 154, 45, 12, 75, 86];
+/// aes 256 bit key length in bytes
+const AES_256_LEN_BYTES: usize = 32;
+/// aes_gcm generates 96bit (12 byte) nonce by default
+const NONCE_LEN_BYTES: usize = 12;
+/// aes_gcm uses a 128bit (16 byte) authentication tag (MAC)
+const TAG_LEN_BYTES: usize = 16;
+/// padding needed for encrypted aes data keys
+#[allow(dead_code)]
+const PADDING_FOR_SHAMIR_60: &str = "00000000";
+
+
+
+// #[derive(Error, Debug)]
+// pub enum SSSError {
+//     #[error("Threshold ({k_thres:?}) must be less than number of shares ({n_shares:?})")]
+//     ThresholdGreaterThanShares {
+//       k_thres: u16,
+//       n_shares: u16
+//     },
+
+//     #[error("{msg:?}")]
+//     Unexpected {
+//       msg: String
+//     },
+
+//     #[error("unknown SSS error")]
+//     Unknown,
+// }
+
+// #[derive(Debug, Clone, Copy)]
+// pub struct ShamirShare {
+//   points: [Point; 4],
+// }
+
+/// Returns four arrays for the key (32 byte) and one for the encrypted data
+/// Returns four arrays  - array 0 is the first 8 bytes of the key, array 1 is the second 8 bytes
+/// Each array is the points (shares) for a portion of the AES-256 key
+#[allow(dead_code)]
+#[allow(clippy::type_complexity)]
+pub fn shamir_encrypt_embed_nonce(data: &[u8], n_shares: u16, k_thres: u16) -> anyhow::Result<(Vec<Point>, Vec<Point>, Vec<Point>, Vec<Point>, Vec<u8>)> {
+  // Get 32 byte (256 bit) aes key
+  let aes_key = generate_data_key();
+  let header = ContainerHeader::new(KeyWrapMethod::Shamir, key_id(&aes_key));
+  let enc_data = ContainerWriter::new(header).seal(&aes_key, data)?;
+  // Turn 32 bytes into 64 character hex string. Each byte is represented by two hex characters
+  let ks = hex::encode(&aes_key);
+
+  // Split up 64 hex characters (32 bytes) into 16 hex character (8 byte) blocks for shamir splitting
+  let k0 = U64::from_be_hex(&ks[0..16]);
+  let k1 = U64::from_be_hex(&ks[16..32]);
+  let k2 = U64::from_be_hex(&ks[32..48]);
+  let k3 = U64::from_be_hex(&ks[48..64]);
+
+  let prime = non_zero_prime()
+    .map_err(|e| anyhow!("Unable to generate non-zero prime: {e}"))?;
+
+  let shares0 = make_shares(k0.into(), n_shares, k_thres, &prime)?;
+  let shares1 = make_shares(k1.into(), n_shares, k_thres, &prime)?;
+  let shares2 = make_shares(k2.into(), n_shares, k_thres, &prime)?;
+  let shares3 = make_shares(k3.into(), n_shares, k_thres, &prime)?;
+
+  Ok((shares0, shares1, shares2, shares3, enc_data))
+}
+
+#[allow(dead_code)]
+pub fn shamir_decrypt_embed_nonce(data: &[u8], _n_shares: u16, _k_thres: u16,
+  shares0: Vec<Point>, shares1: Vec<Point>, shares2: Vec<Point>, shares3: Vec<Point>)
+   -> anyhow::Result<Vec<u8>> {
+  let prime = non_zero_prime()
+    .map_err(|e| anyhow!("Unable to generate non-zero prime: {e}"))?;
+  
+  let k0 = recover_secret(&shares0, &prime)?;
+  let k1 = recover_secret(&shares1, &prime)?;
+  let k2 = recover_secret(&shares2, &prime)?;
+  let k3 = recover_secret(&shares3, &prime)?;
+
+  let mut ks: String = String::with_capacity(512);
+  ks.push_str(&k0.to_string());
+  ks.push_str(&k1.to_string());
+  ks.push_str(&k2.to_string());
+  ks.push_str(&k3.to_string());
+
+  let aes_key = hex::decode(&ks)
+    .map_err(|e| anyhow!("Unable to decode aes_key: {e}"))?;
+
+  let reader = ContainerReader::parse(data)?;
+  reader.header().check_key_id(&aes_key)?;
+  let data = reader.open(&aes_key)?;
+
+  Ok(data)
+}
+
+/// Encrypt content with aes data key and then break into shamir shares (multi-part key)
+/// Each array is the points (shares) for a portion of the AES-256 key
+#[allow(dead_code)]
+pub fn shamir_encrypt_embed_nonce_60_bytes(data: &[u8], n_shares: u16, k_thres: u16, master_key: &MasterKey) -> anyhow::Result<(Vec<MultiPartyKey8Points>, Vec<u8>)> {
+  // Get 32 byte (256 bit) aes key
+  let data_key = generate_data_key();
+  // 32 byte data key turns into 60 byte encrypted key
+  let (aes_key, _, _, _) = wrap_data_key(master_key, data_key.as_slice())?;
+  let header = ContainerHeader::new(KeyWrapMethod::ShamirMasterKey, master_key.id().to_vec());
+  let enc_data = ContainerWriter::new(header).seal(&data_key, data)?;
+  // Turn 60 bytes into 120 character hex string. Each byte is represented by two hex characters
+  let ks = hex::encode(&aes_key);
+
+  // Split up 120 hex characters (60 bytes) into 16 hex character (8 byte) blocks for shamir splitting
+  let k0 = U64::from_be_hex(&ks[0..16]);
+  let k1 = U64::from_be_hex(&ks[16..32]);
+  let k2 = U64::from_be_hex(&ks[32..48]);
+  let k3 = U64::from_be_hex(&ks[48..64]);
+  let k4 = U64::from_be_hex(&ks[64..80]);
+  let k5 = U64::from_be_hex(&ks[80..96]);
+  let k6 = U64::from_be_hex(&ks[96..112]);
+  // Since the last part is only 4 bytes, we have to pad it with 4 bytes of zeros;
+  let k7 = U64::from_be_hex(&format!("{}{PADDING_FOR_SHAMIR_60}", &ks[112..120]));
+
+  let prime = non_zero_prime()
+    .map_err(|e| anyhow!("Unable to generate non-zero prime: {e}"))?;
+
+  // Each part (16 hex char) of the overall key (120 hex char) is broken into n_shares len of Points
+  let shares0 = make_shares(k0.into(), n_shares, k_thres, &prime)
+    .inspect_err(|e| error!("error gen shares0: {e}"))?;
+  let shares1 = make_shares(k1.into(), n_shares, k_thres, &prime)
+    .inspect_err(|e| error!("error gen shares1: {e}"))?;
+  let shares2 = make_shares(k2.into(), n_shares, k_thres, &prime)
+    .inspect_err(|e| error!("error gen shares2: {e}"))?;
+  let shares3 = make_shares(k3.into(), n_shares, k_thres, &prime)
+    .inspect_err(|e| error!("error gen shares3: {e}"))?;
+  let shares4 = make_shares(k4.into(), n_shares, k_thres, &prime)
+    .inspect_err(|e| error!("error gen shares4: {e}"))?;
+  let shares5 = make_shares(k5.into(), n_shares, k_thres, &prime)
+    .inspect_err(|e| error!("error gen shares5: {e}"))?;
+  let shares6 = make_shares(k6.into(), n_shares, k_thres, &prime)
+    .inspect_err(|e| error!("error gen shares6: {e}"))?;
+  let shares7 = make_shares(k7.into(), n_shares, k_thres, &prime)
+    .inspect_err(|e| error!("error gen shares7: {e}"))?;
+
+  let n_shares = n_shares as usize;
+  let mut keys: Vec<MultiPartyKey8Points> = Vec::with_capacity(n_shares);
+  for (p0, (p1, (p2, (p3, (p4, (p5, (p6, p7))))))) in zip(shares0,
+      zip(shares1, zip(shares2, zip(shares3,
+      zip(shares4, zip(shares5, zip(shares6, shares7))))))) {
+    keys.push(MultiPartyKey8Points { p0, p1, p2, p3, p4, p5, p6, p7 })
+  }
+
+  Ok((keys, enc_data))
+}
+
+/// Combine shamir shares (multi-part key), decrypt aes data key and then content
+#[allow(dead_code)]
+pub fn shamir_decrypt_embed_nonce_60_bytes(data: &[u8], _n_shares: u16, _k_thres: u16, keys: Vec<MultiPartyKey8Points>, keystore: &Keystore) -> anyhow::Result<Vec<u8>> {
+  let aes_key = recover_key_60_bytes(keys)?;
+
+  let reader = ContainerReader::parse(data)?;
+  let aes_key = unwrap_container_key(reader.header(), aes_key.as_slice(), keystore)?;
+
+  let data = reader.open(&aes_key)?;
+
+  Ok(data)
+}
+
+/// Combine shamir shares (multi-part key) back into the 60 byte wrapped data key
+fn recover_key_60_bytes(keys: Vec<MultiPartyKey8Points>) -> anyhow::Result<Vec<u8>> {
+  let mut shares0 = Vec::new();
+  let mut shares1 = Vec::new();
+  let mut shares2 = Vec::new();
+  let mut shares3 = Vec::new();
+  let mut shares4 = Vec::new();
+  let mut shares5 = Vec::new();
+  let mut shares6 = Vec::new();
+  let mut shares7 = Vec::new();
+
+  for mpkey in keys {
+    shares0.push(mpkey.p0);
+    shares1.push(mpkey.p1);
+    shares2.push(mpkey.p2);
+    shares3.push(mpkey.p3);
+    shares4.push(mpkey.p4);
+    shares5.push(mpkey.p5);
+    shares6.push(mpkey.p6);
+    shares7.push(mpkey.p7);
+  }
+
+  let prime = non_zero_prime()
+    .map_err(|e| anyhow!("Unable to generate non-zero prime: {e}"))?;
+
+  let k0 = recover_secret(&shares0, &prime)?;
+  let k1 = recover_secret(&shares1, &prime)?;
+  let k2 = recover_secret(&shares2, &prime)?;
+  let k3 = recover_secret(&shares3, &prime)?;
+  let k4 = recover_secret(&shares4, &prime)?;
+  let k5 = recover_secret(&shares5, &prime)?;
+  let k6 = recover_secret(&shares6, &prime)?;
+  let k7 = recover_secret(&shares7, &prime)?;
+
+  let mut ks: String = String::with_capacity(512);
+  ks.push_str(&k0.to_string());
+  ks.push_str(&k1.to_string());
+  ks.push_str(&k2.to_string());
+  ks.push_str(&k3.to_string());
+  ks.push_str(&k4.to_string());
+  ks.push_str(&k5.to_string());
+  ks.push_str(&k6.to_string());
+  let k7_str = k7.to_string();
+  // we need to grab only the first 4 bytes of the last section as it has 4 bytes of padding
+  ks.push_str(&k7_str[0..(k7_str.len() - PADDING_FOR_SHAMIR_60.len())]);
+
+  hex::decode(&ks)
+    .map_err(|e| anyhow!("Unable to decode aes_key: {e}"))
+}
+
+// 12th mersenne prime - 2^127 - 1
+// pub static PRIME: U128 = U128::from_be_hex("7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF");
+const PRIME: U128 = U128::from_u128(2u128.pow(127) - 1);
+
+// Evaluates polynomial (coefficient tuple) at x, used to generate a shamir pool
+// in make_random_shares below.
+//
+// poly is in the form a + bx + cx^2 ...
+fn fun_of_x(poly: &[U128], x: &U128, prime: &NonZero<U128>) -> U128 {
+  let mut accum = U128::ZERO;
+  let mut x_power = U128::ONE;
+
+  for coeff in poly {
+    accum = accum.add_mod(&coeff.mul_mod(&x_power, prime), prime);
+    x_power = x_power.mul_mod(x, prime);
+  }
+
+  accum
+}
+
+
+// secret
+// n_shares - break secret into number of shares
+// k_thres - threshold number of shares to recombine secret
+fn make_shares(secret: u64, n_shares: u16, k_thres: u16, prime: &NonZero<U128>) -> anyhow::Result<Vec<Point>> {
+  if k_thres >= n_shares { return Err(anyhow!("threshold (k) greater than shares (n): {k_thres} > {n_shares}")); }
+
+  let secret = U128::from_u64(secret);
+
+  // poly is set up as a0 + a1*x + a2*x^2
+  let mut poly = vec![secret];
+
+  for _ in 1..k_thres {
+    let mut t = U128::ZERO;
+
+    while t == U128::ZERO {
+      t = U128::random_mod(&mut OsRng, prime);
+    }
+    poly.push(t);
+  }
+
+  let mut points = Vec::new();
+  for i in 1..=n_shares {
+    let x = i;
+    let y = fun_of_x(&poly, &U128::from_u16(i), prime);
+    points.push(Point { x, y });
+  }
+
+  Ok(points)
+}
+
+fn non_zero_prime() -> anyhow::Result<NonZero<U128>> {
+  NonZero::new(PRIME).into_option()
+    .ok_or_else(|| anyhow!("Unable to create non-zero prime"))
+}
+
+fn recover_secret(shares: &[Point], prime: &NonZero<U128>) -> anyhow::Result<U64> {
+  let mut ans = U128::ZERO;
+
+  let mut xs: Vec<U128> = Vec::new();
+  let mut ys: Vec<U128> = Vec::new();
+
+  for point in shares.iter() {
+    let x = point.x;
+    let y = point.y;
+    xs.push(U128::from_u16(x));
+    ys.push(U128::from(y));
+  }
+
+  for i in 0..xs.len() {
+    let l_i = lagrange_basis(&xs, i, prime)?;
+    let y_i = ys.get(i)
+      .ok_or_else(|| anyhow!("Could not get y_i[{}]", i))?;
+
+    ans = ans.add_mod(&y_i.mul_mod(&l_i, prime), prime);
+  }
+
+  let ans: U64 = ans.resize();
+  Ok(ans)
+}
+
+fn lagrange_basis(xs: &[U128], i: usize, prime: &NonZero<U128>) -> anyhow::Result<U128> {
+  let mut numer = U128::ONE;
+  let mut denom = U128::ONE;
+
+
+  let x_i = xs.get(i)
+    .ok_or_else(|| anyhow!("Unable to get x_i value"))?;
+
+  for j in 0..xs.len() {
+    if i == j { continue; }
+
+    let x_j = xs.get(j)
+      .ok_or_else(|| anyhow!("Unable to get x_j value"))?;
+
+    numer = numer.mul_mod(&x_j.neg_mod(prime), prime);
+    denom = denom.mul_mod(&x_i.sub_mod(x_j, prime), prime);
+  }
+
+  let inv = Into::<Option<_>>::into(denom.inv_mod(prime))
+    .ok_or_else(|| anyhow!("Unable to find modulo multiplicative inverse"))?;
+
+  Ok(numer.mul_mod(&inv, prime))
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Point {
+  pub x: u16,
+  pub y: U128
+}
+
+impl Point {
+  pub const BIT_SIZE_IN_BYTES: usize = 18;
+
+  pub fn encode(&self) -> Vec<u8> {
+    let mut res: Vec<u8> = Vec::with_capacity(Self::BIT_SIZE_IN_BYTES);
+    let mut sx = self.x.to_be_bytes().to_vec();
+    let mut sy = self.y.to_be_bytes().to_vec();
+    res.append(&mut sx);
+    res.append(&mut sy);
+    res
+  }
+
+  pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+    let mut sx = bytes.to_vec();
+    let sy = sx.split_off(2);
+    let ax: [u8; 2] = Self::vec_to_array(sx);
+    let x = u16::from_be_bytes(ax);
+    let y = U128::from_be_slice(sy.as_slice());
+
+    Ok(Self { x, y })
+  }
+
+  #[allow(dead_code)]
+  pub fn encode_to_string(&self) -> String {
+    let mut res = String::with_capacity(260);
+    let sx = hex::encode(self.x.to_be_bytes());
+    let sy = hex::encode(self.y.to_be_bytes());
+    res.push_str(&sx);
+    res.push_str(&sy);
+    res
+  }
+
+  pub fn decode_from_string(s: String) -> anyhow::Result<Self> {
+    let (sx, sy) = s.split_at(4);
+    let sx = hex::decode(sx)
+      .map_err(|e| anyhow!("Can't parse point.x: {e}"))?;
+    let sy = hex::decode(sy)
+      .map_err(|e| anyhow!("Can't parse point.y: {e}"))?;
+    let ax: [u8; 2] = Self::vec_to_array(sx);
+    let x = u16::from_be_bytes(ax);
+    let y = U128::from_be_slice(sy.as_slice());
+
+    Ok(Self { x, y })
+  }
+
+  /// Words for paper backups, a mistyped word is caught by the checksum, see `mnemonic::encode`
+  #[allow(dead_code)]
+  pub fn encode_to_mnemonic(&self) -> Zeroizing<String> {
+    mnemonic::encode(&Zeroizing::new(self.encode()))
+  }
+
+  #[allow(dead_code)]
+  pub fn decode_from_mnemonic(s: &str) -> anyhow::Result<Self> {
+    let bytes = mnemonic::decode(s)?;
+    if bytes.len() != Self::BIT_SIZE_IN_BYTES {
+      return Err(anyhow!("A point is {} bytes, the words hold {}", Self::BIT_SIZE_IN_BYTES, bytes.len()));
+    }
+    Self::decode(&bytes)
+  }
+
+  fn vec_to_array<T, const N: usize>(v: Vec<T>) -> [T; N]
+  where
+      T: Copy, // Or Clone, depending on whether you need to copy or clone elements
+  {
+      v.try_into()
+          .unwrap_or_else(|v: Vec<T>| {
+              panic!("Expected a Vec of length {} but it was {}", N, v.len());
+          })
+  }
+}
+
+impl Zeroize for Point {
+  fn zeroize(&mut self) {
+    self.x.zeroize();
+    self.y.zeroize();
+  }
+}
+
+/// One party's share of a 60 byte wrapped key, a point on each of the 8 polynomials
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultiPartyKey8Points {
+  pub p0: Point,
+  pub p1: Point,
+  pub p2: Point,
+  pub p3: Point,
+  pub p4: Point,
+  pub p5: Point,
+  pub p6: Point,
+  pub p7: Point,
+}
+
+#[allow(dead_code)]
+impl MultiPartyKey8Points {
+  /// Share index, the x every point was evaluated at
+  pub fn index(&self) -> u16 {
+    self.p0.x
+  }
+
+  fn points(&self) -> [&Point; 8] {
+    [&self.p0, &self.p1, &self.p2, &self.p3, &self.p4, &self.p5, &self.p6, &self.p7]
+  }
+
+  /// Hex of the 8 points, as `Point::encode_to_string` writes them
+  pub fn encode_to_string(&self) -> String {
+    self.points().iter().map(|x| x.encode_to_string()).collect()
+  }
+
+  /// Parses pasted hex, whitespace between the points is ignored
+  pub fn decode_from_string(s: &str) -> anyhow::Result<Self> {
+    let s = Zeroizing::new(s.split_whitespace().collect::<String>());
+    let point_len = Point::BIT_SIZE_IN_BYTES * 2;
+    if s.len() != point_len * 8 || !s.is_ascii() {
+      return Err(anyhow!("A share is {} hex characters, got {}", point_len * 8, s.len()));
+    }
+    let mut points = Vec::with_capacity(8);
+    for i in 0..8 {
+      points.push(Point::decode_from_string(s[i * point_len..(i + 1) * point_len].to_string())?);
+    }
+    Self::from_points(&points)
+  }
+
+  /// Words of the 8 encoded points, see `mnemonic::encode`
+  pub fn encode_to_mnemonic(&self) -> Zeroizing<String> {
+    mnemonic::encode(&Zeroizing::new(self.encode(Point::BIT_SIZE_IN_BYTES)))
+  }
+
+  pub fn decode_from_mnemonic(s: &str) -> anyhow::Result<Self> {
+    Self::decode(&mnemonic::decode(s)?, Point::BIT_SIZE_IN_BYTES)
+  }
+
+  fn from_points(points: &[Point]) -> anyhow::Result<Self> {
+    let [p0, p1, p2, p3, p4, p5, p6, p7] = points.try_into()
+      .map_err(|_| anyhow!("A share has 8 points, got {}", points.len()))?;
+    let share = Self { p0, p1, p2, p3, p4, p5, p6, p7 };
+    if share.index() == 0 || share.points().iter().any(|x| x.x != share.index()) {
+      return Err(anyhow!("Share points disagree on the share index"));
+    }
+    Ok(share)
+  }
+
+  // chunk size is expect to be sizeof(Point)
+  pub fn encode(&self, chunk_size: usize) -> Vec<u8> {
+    let mut res: Vec<u8> = Vec::with_capacity(chunk_size * 8);
+    let mut s0 = self.p0.encode();
+    let mut s1 = self.p1.encode();
+    let mut s2 = self.p2.encode();
+    let mut s3 = self.p3.encode();
+    let mut s4 = self.p4.encode();
+    let mut s5 = self.p5.encode();
+    let mut s6 = self.p6.encode();
+    let mut s7 = self.p7.encode();
+    res.append(&mut s0);
+    res.append(&mut s1);
+    res.append(&mut s2);
+    res.append(&mut s3);
+    res.append(&mut s4);
+    res.append(&mut s5);
+    res.append(&mut s6);
+    res.append(&mut s7);
+    res
+  }
+
+  // chunk size is expect to be sizeof(Point)
+  pub fn decode(bytes: &[u8], chunk_size: usize) -> anyhow::Result<Self> {
+    if chunk_size != Point::BIT_SIZE_IN_BYTES || bytes.len() != chunk_size * 8 {
+      return Err(anyhow!("A share is {} bytes, got {}", Point::BIT_SIZE_IN_BYTES * 8, bytes.len()));
+    }
+    // fn get_chunk(parts: &mut std::slice::Chunks<'_, u8>, idx: usize) -> Result<Point> {
+    //   Point::decode(&parts.nth(idx)
+    //     .map(|x| x.to_vec())
+    //     .ok_or_else(|| AppError::General(format!("Unable to get part[{idx}] of stream")))?)
+    // }
+    // let mut parts = bytes.chunks(chunk_size);
+    // let p0 = get_chunk(&mut parts, 0)?;
+    // let p1 = get_chunk(&mut parts, 1)?;
+    // let p2 = get_chunk(&mut parts, 2)?;
+    // let p3 = get_chunk(&mut parts, 3)?;
+    // let p4 = get_chunk(&mut parts, 4)?;
+    // let p5 = get_chunk(&mut parts, 5)?;
+    // let p6 = get_chunk(&mut parts, 6)?;
+    // let p7 = get_chunk(&mut parts, 7)?;
+
+    fn parse_chunk(opt_x: Option<&[u8]>) -> anyhow::Result<Point> {
+      if let Some(x) = opt_x {
+        Point::decode(x)
+      } else {
+        Err(anyhow!("Unable to parse_chunk()"))
+      }
+    }
+
+    let mut parts = bytes.chunks(chunk_size);
+    let p0 = parse_chunk(parts.next())?;
+    let p1 = parse_chunk(parts.next())?;
+    let p2 = parse_chunk(parts.next())?;
+    let p3 = parse_chunk(parts.next())?;
+    let p4 = parse_chunk(parts.next())?;
+    let p5 = parse_chunk(parts.next())?;
+    let p6 = parse_chunk(parts.next())?;
+    let p7 = parse_chunk(parts.next())?;
+
+    Self::from_points(&[p0, p1, p2, p3, p4, p5, p6, p7])
+  }
+}
+
+impl Zeroize for MultiPartyKey8Points {
+  fn zeroize(&mut self) {
+    for point in [&mut self.p0, &mut self.p1, &mut self.p2, &mut self.p3, &mut self.p4, &mut self.p5, &mut self.p6, &mut self.p7] {
+      point.zeroize();
+    }
+  }
+}
+
+
+
+/// fresh random 32 byte (256 bit) aes data key
+fn generate_data_key() -> Vec<u8> {
+  Aes256Gcm::generate_key(OsRng).to_vec()
+}
+
+// returns (aes key, encrypted data)
+// encrypted data is a container: header || nonce || ciphertext || tag
+#[allow(dead_code)]
+pub fn symmetric_encrypt_embed_nonce(data: &[u8]) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+  let key = generate_data_key();
+  let header = ContainerHeader::new(KeyWrapMethod::Raw, key_id(&key));
+  let container = ContainerWriter::new(header).seal(&key, data)?;
+
+  Ok((key, container))
+}
+
+#[allow(dead_code)]
+pub fn symmetric_decrypt_using_embedded_nonce(key: &[u8], container: &[u8]) -> anyhow::Result<Vec<u8>> {
+  let reader = ContainerReader::parse(container)?;
+  reader.header().check_key_id(key)?;
+
+  let data = reader.open(key)?;
+
+  Ok(data)
+}
+
+/// A fresh data key together with its wrapped form and the header describing how it was wrapped
+///
+/// New containers are written with the streamed aead so they can be sealed with bounded memory.
+pub struct SealingKey {
+  header: ContainerHeader,
+  data_key: Zeroizing<Vec<u8>>,
+  /// empty for envelopes
+  wrapped_key: Vec<u8>,
+  envelope: Option<Envelope>,
+}
+
+impl SealingKey {
+  /// data key wrapped by a keystore master key
+  pub fn enc_data_key(master_key: &MasterKey) -> anyhow::Result<Self> {
+    let data_key = Zeroizing::new(generate_data_key());
+
+    let (wrapped_key, _, _, _) = wrap_data_key(master_key, data_key.as_slice())?;
+    let mut header = ContainerHeader::new(KeyWrapMethod::MasterKey, master_key.id().to_vec());
+    header.aead = AeadAlgorithm::Aes256GcmStream;
+
+    Ok(Self { header, data_key, wrapped_key, envelope: None })
+  }
+
+  /// data key wrapped by a keystore master key, the wrapped key is split into shares instead of stored next to the container
+  pub fn shamir_master_key(master_key: &MasterKey) -> anyhow::Result<Self> {
+    let data_key = Zeroizing::new(generate_data_key());
+
+    let (wrapped_key, _, _, _) = wrap_data_key(master_key, data_key.as_slice())?;
+    let mut header = ContainerHeader::new(KeyWrapMethod::ShamirMasterKey, master_key.id().to_vec());
+    header.aead = AeadAlgorithm::Aes256GcmStream;
+
+    Ok(Self { header, data_key, wrapped_key, envelope: None })
+  }
+
+  /// data key wrapped by a key derived from `passphrase` (argon2id), slow by design
+  /// kdf salt and cost are stored in the container header
+  pub fn passphrase(passphrase: &Passphrase, cost: KdfCost) -> anyhow::Result<Self> {
+    if passphrase.is_empty() {
+      return Err(anyhow!("Passphrase must not be empty"));
+    }
+    let data_key = Zeroizing::new(generate_data_key());
+
+    let kdf = passphrase::new_kdf_params(cost);
+    let kek = passphrase::derive_key(passphrase, &kdf)?;
+    let (wrapped_key, _, _, _) = wrap_data_key_with(&kek, data_key.as_slice(), &[])?;
+
+    let mut header = ContainerHeader::new(KeyWrapMethod::Passphrase, key_id(&wrapped_key));
+    header.aead = AeadAlgorithm::Aes256GcmStream;
+    header.kdf = Some(kdf);
+
+    Ok(Self { header, data_key, wrapped_key, envelope: None })
+  }
+
+  /// data key wrapped to the public key of `recipient`, only their identity can unwrap it
+  #[allow(dead_code)]
+  pub fn x25519(recipient: &X25519Recipient) -> anyhow::Result<Self> {
+    let data_key = Zeroizing::new(generate_data_key());
+
+    let wrapped_key = wrap_data_key_x25519(recipient, data_key.as_slice())?;
+    let mut header = ContainerHeader::new(KeyWrapMethod::X25519, recipient.id());
+    header.aead = AeadAlgorithm::Aes256GcmStream;
+
+    Ok(Self { header, data_key, wrapped_key, envelope: None })
+  }
+
+  /// data key wrapped once per recipient, stored in the container so no key file is needed
+  /// slow by design when a recipient is a passphrase
+  pub fn envelope(recipients: &[Recipient]) -> anyhow::Result<Self> {
+    let data_key = Zeroizing::new(generate_data_key());
+    let envelope = Envelope::wrap(recipients, data_key.as_slice())?;
+
+    Ok(Self { header: ContainerHeader::envelope(), data_key, wrapped_key: Vec::new(), envelope: Some(envelope) })
+  }
+
+  /// Signs content with the digest `digest` and puts the signature inside the container
+  /// only envelopes have room for a signature
+  pub fn sign(&mut self, identity: &Ed25519Identity, digest: &ContentDigest) -> anyhow::Result<()> {
+    let envelope = self.envelope.as_mut()
+      .ok_or_else(|| anyhow!("Only containers with recipient stanzas can be signed"))?;
+    envelope.sign(&Signature::sign(identity, digest), &self.data_key)
+  }
+
+  /// The wrapped data key, to be stored next to the container
+  /// None for envelopes, they carry their wrapped keys inside
+  pub fn wrapped_key(&self) -> Option<&[u8]> {
+    (!self.header.is_envelope()).then_some(self.wrapped_key.as_slice())
+  }
+
+  fn writer(&self) -> ContainerWriter {
+    let writer = ContainerWriter::new(self.header.clone());
+    match &self.envelope {
+      Some(envelope) => writer.envelope(envelope.clone()),
+      None => writer,
+    }
+  }
+
+  /// Encrypts `data` held in memory, returns the container
+  pub fn seal(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    self.writer().seal(&self.data_key, data)
+  }
+
+  /// Encrypts everything `reader` yields into a container written to `writer`
+  /// returns the number of plaintext bytes
+  pub async fn seal_stream<R, W>(&self, reader: &mut R, writer: &mut W) -> anyhow::Result<u64>
+  where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+  {
+    self.writer().seal_stream(&self.data_key, reader, writer).await
+  }
+}
+
+/// symmetric encryption with data key encrypted by a keystore master key
+/// returns (wrapped aes key, encrypted data)
+#[allow(dead_code)]
+pub fn symmetric_encrypt_embed_nonce_enc_data_key(data: &[u8], master_key: &MasterKey) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+  let sealing_key = SealingKey::enc_data_key(master_key)?;
+  let container = sealing_key.seal(data)?;
+
+  Ok((sealing_key.wrapped_key, container))
+}
+
+/// symmetric decryption with data key encrypted as well
+/// the master key named in the container header is looked up in `keystore`,
+/// files from before keystores existed are opened with the legacy built-in key
+/// returns decrypted data
+#[allow(dead_code)]
+pub fn symmetric_decrypt_using_embedded_nonce_enc_data_key(wrapped_key: &[u8], container: &[u8], keystore: &Keystore) -> anyhow::Result<Vec<u8>> {
+  let reader = ContainerReader::parse(container)?;
+
+  let data_key = unwrap_container_key(reader.header(), wrapped_key, keystore)?;
+
+  let plaintext = reader.open(data_key.as_slice())?;
+
+  Ok(plaintext)
+}
+
+/// Unwraps the data key of a container protected by a keystore key: a master key,
+/// an x25519 identity or the legacy built-in key
+pub fn unwrap_container_key(header: &ContainerHeader, wrapped_key: &[u8], keystore: &Keystore) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+  let data_key = match header.key_wrap {
+    KeyWrapMethod::BuiltinKek | KeyWrapMethod::ShamirBuiltinKek => {
+      header.check_key_id(wrapped_key)?;
+      legacy_unwrap_data_key(wrapped_key)
+    }
+    KeyWrapMethod::MasterKey | KeyWrapMethod::ShamirMasterKey => {
+      let master_key = keystore.master_key(&header.key_id)
+        .ok_or_else(|| anyhow!("Master key {} is not in the keystore", hex::encode(&header.key_id)))?;
+      unwrap_data_key(master_key, wrapped_key)
+    }
+    KeyWrapMethod::X25519 => {
+      let identity = keystore.x25519_identity(&header.key_id)
+        .ok_or_else(|| anyhow!("Container is for public key id {}, which is not in the keystore", hex::encode(&header.key_id)))?;
+      unwrap_data_key_x25519(identity, wrapped_key)
+    }
+    x => Err(anyhow!("Container key is not wrapped with a keystore key ({x:?})")),
+  }?;
+
+  Ok(Zeroizing::new(data_key))
+}
+
+/// Unwraps the data key of a container protected by a passphrase, slow by design
+pub fn unwrap_container_key_passphrase(header: &ContainerHeader, wrapped_key: &[u8], passphrase: &Passphrase) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+  if header.key_wrap != KeyWrapMethod::Passphrase {
+    return Err(anyhow!("Container key is not protected by a passphrase ({:?})", header.key_wrap));
+  }
+  header.check_key_id(wrapped_key)?;
+  let kdf = header.kdf.as_ref()
+    .ok_or_else(|| anyhow!("Container is missing its kdf params"))?;
+
+  let kek = passphrase::derive_key(passphrase, kdf)?;
+  let data_key = unwrap_data_key_with(&kek, wrapped_key, &[])
+    .map_err(|_| anyhow!("Wrong passphrase"))?;
+
+  Ok(Zeroizing::new(data_key))
+}
+
+/// symmetric encryption with data key wrapped by a key derived from `passphrase` (argon2id)
+/// kdf salt and cost are stored in the container header
+/// returns (wrapped aes key, encrypted data)
+#[allow(dead_code)]
+pub fn symmetric_encrypt_embed_nonce_passphrase(data: &[u8], passphrase: &Passphrase, cost: KdfCost) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+  let sealing_key = SealingKey::passphrase(passphrase, cost)?;
+  let container = sealing_key.seal(data)?;
+
+  Ok((sealing_key.wrapped_key, container))
+}
+
+/// symmetric decryption with data key wrapped by a passphrase derived key
+/// returns decrypted data
+#[allow(dead_code)]
+pub fn symmetric_decrypt_using_embedded_nonce_passphrase(wrapped_key: &[u8], container: &[u8], passphrase: &Passphrase) -> anyhow::Result<Vec<u8>> {
+  let reader = ContainerReader::parse(container)?;
+
+  let data_key = unwrap_container_key_passphrase(reader.header(), wrapped_key, passphrase)?;
+
+  let plaintext = reader.open(data_key.as_slice())?;
+
+  Ok(plaintext)
+}
+
+/// Wrap a data key by encrypting it
+///
+/// Math:
+/// Original key is 256 bits or 32 bytes, which is chunked into four 8 byte groups equivalent to four 64 bit groups
+/// Nonce is 96 bits or 12 bytes
+/// Tag (or MAC Message Authencation Code) is 128 bits or 16 bytes
+/// Cipher text is original key length + tag length + nonce length
+/// Cipher text len = 32 bytes + 16 + 12 = 60 bytes
+///
+/// If we want to chunk an encrypted data key we'd want to break it into multiple 8 byte sections
+/// Originally we'd break the 32 bytes into four 8 byte sections
+/// Now we'd break 60 bytes into seven 8 bytes sections and one 4 byte section
+///
+/// The master key id is bound to the wrapped key as associated data
+fn wrap_data_key(master_key: &MasterKey, data_key: &[u8]) -> anyhow::Result<(Vec<u8>, usize, usize, usize)> {
+  wrap_data_key_with(master_key.key(), data_key, master_key.id())
+}
+
+fn unwrap_data_key(master_key: &MasterKey, data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+  unwrap_data_key_with(master_key.key(), data_key, master_key.id())
+}
+
+/// Wrap a data key to an x25519 public key, see `x25519::wrap_data_key` for the layout
+fn wrap_data_key_x25519(recipient: &X25519Recipient, data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+  x25519::wrap_data_key(recipient, data_key)
+}
+
+/// Unwrap a data key wrapped to the public key of `identity`
+fn unwrap_data_key_x25519(identity: &X25519Identity, data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+  x25519::unwrap_data_key(identity, data_key)
+}
+
+/// Unwrap a data key from before keystores existed
+fn legacy_unwrap_data_key(data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+  unwrap_data_key_with(&LEGACY_KEY_WRAPPER_KEY, data_key, &[])
+}
+
+/// Wrap a data key with the given 32 byte key wrapping key, same layout as `wrap_data_key`
+fn wrap_data_key_with(kek: &[u8; AES_256_LEN_BYTES], data_key: &[u8], aad: &[u8]) -> anyhow::Result<(Vec<u8>, usize, usize, usize)> {
+  let key: &Key<Aes256Gcm> = kek.into();
+  let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+  let encrypter = Aes256Gcm::new(key);
+  let cipher = encrypter.encrypt(&nonce, Payload { msg: data_key, aad })
+    .map_err(|e| anyhow!("Unable to wrap data key: {e}"))?;
+
+  let nonce = nonce.to_vec();
+  let mut cipher_text = Vec::with_capacity(nonce.len() + cipher.len());
+  let nonce_len = nonce.len();
+  let cipher_len = cipher.len();
+  cipher_text.extend(nonce);
+  cipher_text.extend(cipher);
+
+  Ok((cipher_text, key.len(), nonce_len, cipher_len))
+}
+
+fn unwrap_data_key_with(kek: &[u8; AES_256_LEN_BYTES], data_key: &[u8], aad: &[u8]) -> anyhow::Result<Vec<u8>> {
+  if data_key.len() < NONCE_LEN_BYTES {
+    return Err(anyhow!("Wrapped data key is truncated"));
+  }
+  let key: &Key<Aes256Gcm> = kek.into();
+  let (nonce_bytes, ciphertext_bytes) = data_key.split_at(NONCE_LEN_BYTES);
+
+  let decrypter = Aes256Gcm::new(key);
+  let data = decrypter.decrypt(nonce_bytes.into(), Payload { msg: ciphertext_bytes, aad })
+    .map_err(|e| anyhow!("Unable to unwrap data key: {e}"))?;
+
+  Ok(data)
+}
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_n;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn header_len(container: &[u8]) -> anyhow::Result<usize> {
+      Ok(ContainerReader::parse(container)?.header_bytes().len())
+    }
+
+    fn test_keystore() -> anyhow::Result<Keystore> {
+      Keystore::create(&Passphrase::new("test keystore".to_string()), passphrase::TEST_KDF_COST)
+    }
+
+    #[test]
+    fn test_roundtrip_symmetric() -> anyhow::Result<()> {
+      let orig = b"hello world";
+
+      let (aes_key, enc_bytes) = symmetric_encrypt_embed_nonce(orig)?;
+    
+      let act = symmetric_decrypt_using_embedded_nonce(&aes_key, &enc_bytes)?;
+
+      assert_eq!(orig.to_vec(), act);
+      Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip_symmetric_enc_data_key() -> anyhow::Result<()> {
+      let orig = b"hello world";
+
+      let keystore = test_keystore()?;
+
+      let (aes_key, enc_bytes) = symmetric_encrypt_embed_nonce_enc_data_key(orig, keystore.default_master_key())?;
+      assert_eq!(keystore.default_master_key().id(), ContainerReader::parse(&enc_bytes)?.header().key_id.as_slice());
+    
+      let act = symmetric_decrypt_using_embedded_nonce_enc_data_key(&aes_key, &enc_bytes, &keystore)?;
+
+      assert_eq!(orig.to_vec(), act);
+      Ok(())
+    }
+
+    #[test]
+    fn test_enc_data_key_rejects_other_key() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (_key0, enc_bytes0) = symmetric_encrypt_embed_nonce_enc_data_key(b"hello world", keystore.default_master_key())?;
+      let (key1, _enc_bytes1) = symmetric_encrypt_embed_nonce_enc_data_key(b"hello world", keystore.default_master_key())?;
+
+      let res = symmetric_decrypt_using_embedded_nonce_enc_data_key(&key1, &enc_bytes0, &keystore);
+      assert!(res.is_err(), "key from another file must be rejected");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_enc_data_key_needs_master_key() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let other = test_keystore()?;
+      let (key, enc_bytes) = symmetric_encrypt_embed_nonce_enc_data_key(b"hello world", keystore.default_master_key())?;
+
+      let res = symmetric_decrypt_using_embedded_nonce_enc_data_key(&key, &enc_bytes, &other);
+      assert!(res.is_err_and(|e| e.to_string().contains("is not in the keystore")));
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip_x25519_recipient() -> anyhow::Result<()> {
+      let orig = b"hello colleague";
+      let keystore = test_keystore()?;
+      let recipient = keystore.default_x25519_identity().expect("identity").recipient();
+
+      let sealing_key = SealingKey::x25519(&recipient)?;
+      let enc_bytes = sealing_key.seal(orig)?;
+      let reader = ContainerReader::parse(&enc_bytes)?;
+      assert_eq!(KeyWrapMethod::X25519, reader.header().key_wrap);
+
+      let data_key = unwrap_container_key(reader.header(), sealing_key.wrapped_key().expect("wrapped key"), &keystore)?;
+      assert_eq!(orig.to_vec(), reader.open(&data_key)?);
+
+      let res = unwrap_container_key(reader.header(), sealing_key.wrapped_key().expect("wrapped key"), &test_keystore()?);
+      assert!(res.is_err_and(|e| e.to_string().contains("not in the keystore")));
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_legacy_builtin_kek_still_opens() -> anyhow::Result<()> {
+      let orig = b"hello world";
+      let data_key = generate_data_key();
+      let (wrapped_key, _, _, _) = wrap_data_key_with(&LEGACY_KEY_WRAPPER_KEY, data_key.as_slice(), &[])?;
+      let header = ContainerHeader::new(KeyWrapMethod::BuiltinKek, key_id(&wrapped_key));
+      let enc_bytes = ContainerWriter::new(header).seal(&data_key, orig)?;
+
+      let act = symmetric_decrypt_using_embedded_nonce_enc_data_key(&wrapped_key, &enc_bytes, &test_keystore()?)?;
+      assert_eq!(orig.to_vec(), act);
+
+      Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_stream_enc_data_key() -> anyhow::Result<()> {
+      let orig: Vec<u8> = repeat_n(0x15, 100_000).collect();
+      let keystore = test_keystore()?;
+
+      let sealing_key = SealingKey::enc_data_key(keystore.default_master_key())?;
+      let mut enc_bytes = Vec::new();
+      sealing_key.seal_stream(&mut orig.as_slice(), &mut enc_bytes).await?;
+
+      let mut input = enc_bytes.as_slice();
+      let container = ContainerStreamReader::read_header(&mut input).await?;
+      assert_eq!(AeadAlgorithm::Aes256GcmStream, container.header().aead);
+      let data_key = unwrap_container_key(container.header(), sealing_key.wrapped_key().expect("wrapped key"), &keystore)?;
+      let mut act = Vec::new();
+      container.open(&data_key, &mut input, &mut act).await?;
+
+      assert_eq!(orig, act);
+      Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_stream_passphrase_stanza() -> anyhow::Result<()> {
+      let orig: Vec<u8> = repeat_n(0x3c, 100_000).collect();
+      let passphrase = Passphrase::new("correct horse".to_string());
+      let keystore = test_keystore()?;
+
+      let sealing_key = SealingKey::envelope(&[Recipient::Passphrase(passphrase.clone(), passphrase::TEST_KDF_COST)])?;
+      assert!(sealing_key.wrapped_key().is_none(), "no key file next to the container");
+      let mut enc_bytes = Vec::new();
+      sealing_key.seal_stream(&mut orig.as_slice(), &mut enc_bytes).await?;
+
+      let mut input = enc_bytes.as_slice();
+      let container = ContainerStreamReader::read_header(&mut input).await?;
+      let envelope = container.envelope().expect("envelope");
+      assert!(envelope.needs_passphrase(Some(&keystore)));
+      let wrong = Passphrase::new("wrong".to_string());
+      assert!(envelope.unwrap(container.header_bytes(), Some(&keystore), Some(&wrong)).is_err());
+      let data_key = envelope.unwrap(container.header_bytes(), None, Some(&passphrase))?;
+      let mut act = Vec::new();
+      container.open(&data_key, &mut input, &mut act).await?;
+
+      assert_eq!(orig, act);
+      Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_envelope_rewrap_keeps_payload() -> anyhow::Result<()> {
+      let orig: Vec<u8> = repeat_n(0x2a, 100_000).collect();
+      let owner = test_keystore()?;
+      let colleague = test_keystore()?;
+
+      let sealing_key = SealingKey::envelope(&[Recipient::MasterKey(owner.default_master_key().clone())])?;
+      assert!(sealing_key.wrapped_key().is_none(), "envelopes carry their own wrapped keys");
+      let mut enc_bytes = Vec::new();
+      sealing_key.seal_stream(&mut orig.as_slice(), &mut enc_bytes).await?;
+
+      let mut input = enc_bytes.as_slice();
+      let container = ContainerStreamReader::read_header(&mut input).await?;
+      let mut envelope = container.envelope().expect("envelope").clone();
+      let data_key = envelope.unwrap(container.header_bytes(), Some(&owner), None)?;
+      assert!(envelope.unwrap(container.header_bytes(), Some(&colleague), None).is_err());
+
+      let colleague_key = colleague.default_x25519_identity().expect("identity").recipient();
+      envelope.add(&Recipient::X25519(colleague_key), &data_key)?;
+      envelope.remove(owner.default_master_key().id())?;
+      let mut rewrapped = Vec::new();
+      container.rewrap(&data_key, &envelope, &mut input, &mut rewrapped).await?;
+
+      let mut input = rewrapped.as_slice();
+      let container = ContainerStreamReader::read_header(&mut input).await?;
+      let envelope = container.envelope().expect("envelope");
+      assert!(envelope.unwrap(container.header_bytes(), Some(&owner), None).is_err(), "removed recipient is locked out");
+      let data_key = envelope.unwrap(container.header_bytes(), Some(&colleague), None)?;
+      let mut act = Vec::new();
+      container.open(&data_key, &mut input, &mut act).await?;
+
+      assert_eq!(orig, act);
+      Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sign_then_encrypt() -> anyhow::Result<()> {
+      let orig: Vec<u8> = repeat_n(0x51, 100_000).collect();
+      let sender = test_keystore()?;
+      let receiver = test_keystore()?;
+      let receiver_key = receiver.default_x25519_identity().expect("identity").recipient();
+
+      let mut sealing_key = SealingKey::envelope(&[Recipient::X25519(receiver_key)])?;
+      let identity = sender.default_ed25519_identity().expect("identity");
+      sealing_key.sign(identity, &digest_reader(&mut orig.as_slice()).await?)?;
+      let mut enc_bytes = Vec::new();
+      sealing_key.seal_stream(&mut orig.as_slice(), &mut enc_bytes).await?;
+
+      let mut input = enc_bytes.as_slice();
+      let container = ContainerStreamReader::read_header(&mut input).await?;
+      let envelope = container.envelope().expect("envelope");
+      let data_key = envelope.unwrap(container.header_bytes(), Some(&receiver), None)?;
+      let signature = envelope.signature(&data_key)?.expect("signed");
+      let mut act = Vec::new();
+      container.open(&data_key, &mut input, &mut act).await?;
+
+      let digest = digest_reader(&mut act.as_slice()).await?;
+      assert_eq!(SignatureStatus::UnknownSigner(identity.signer()), signature.check(&digest, Some(&receiver)));
+      assert_eq!(SignatureStatus::Trusted(identity.signer()), signature.check(&digest, Some(&sender)));
+      assert_eq!(SignatureStatus::Invalid, signature.check(&[0u8; 32], Some(&sender)));
+
+      assert!(SealingKey::enc_data_key(sender.default_master_key())?.sign(identity, &digest).is_err(), "key files have no room for a signature");
+      Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip_symmetric_passphrase() -> anyhow::Result<()> {
+      let orig = b"hello world";
+      let passphrase = Passphrase::new("correct horse battery staple".to_string());
+
+      let (wrapped_key, enc_bytes) = symmetric_encrypt_embed_nonce_passphrase(orig, &passphrase, passphrase::TEST_KDF_COST)?;
+      let header = ContainerReader::parse(&enc_bytes)?.header().clone();
+      assert_eq!(KeyWrapMethod::Passphrase, header.key_wrap);
+      assert!(header.kdf.is_some(), "kdf params must be stored in the header");
+
+      let act = symmetric_decrypt_using_embedded_nonce_passphrase(&wrapped_key, &enc_bytes, &passphrase)?;
+      assert_eq!(orig.to_vec(), act);
+
+      let wrong = Passphrase::new("wrong".to_string());
+      let res = symmetric_decrypt_using_embedded_nonce_passphrase(&wrapped_key, &enc_bytes, &wrong);
+      assert!(res.is_err_and(|e| e.to_string() == "Wrong passphrase"));
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_shamir_roundtrip() -> anyhow::Result<()> {
+      let data0 = Vec::from(b"hello world");
+      let n_shares = 4;
+      let k_thres = 2;
+
+      let (sh0, sh1, sh2, sh3, enc_data) = shamir_encrypt_embed_nonce(data0.as_slice(), n_shares, k_thres)?;
+
+      let data1 = shamir_decrypt_embed_nonce(&enc_data, n_shares, k_thres, sh0, sh1, sh2, sh3)?;
+
+      assert_eq!(data0, data1);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_point_str_roundtrip() -> anyhow::Result<()> {
+      let pt = Point {
+        x: 9,
+        y: U128::from_u16(87)
+      };
+
+      let orig = pt.encode_to_string();
+      assert_eq!("000900000000000000000000000000000057", orig.as_str());
+
+      let act = Point::decode_from_string(orig)?;
+      assert_eq!(pt, act);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_point_roundtrip() -> anyhow::Result<()> {
+      let pt = Point {
+        x: 9,
+        y: U128::from_u16(87)
+      };
+
+      let orig = pt.encode();
+      assert_eq!("000900000000000000000000000000000057", hex::encode(&orig));
+
+      let act = Point::decode(&orig)?;
+      assert_eq!(pt, act);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_wrap_key_roundtrip() -> anyhow::Result<()> {
+      let key = Aes256Gcm::generate_key(OsRng).to_vec();
+      let master_key = MasterKey::generate();
+      let (wrapped_key, key_len, nonce_len, cipher_len) = wrap_data_key(&master_key, key.as_slice())?;
+      let unwrapped_key = unwrap_data_key(&master_key, wrapped_key.as_slice())?;
+
+      assert_eq!(AES_256_LEN_BYTES, key.len());
+      assert_eq!(AES_256_LEN_BYTES, key_len);
+      assert_eq!(NONCE_LEN_BYTES, nonce_len);
+      assert_eq!(AES_256_LEN_BYTES + TAG_LEN_BYTES, cipher_len, "cipher not matching size");
+      assert_eq!(key.len() + TAG_LEN_BYTES + NONCE_LEN_BYTES, wrapped_key.len(), "encrypting adds 28 bytes overhead");
+
+      assert_ne!(key, wrapped_key);
+      assert_eq!(key, unwrapped_key);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_data0_length() -> anyhow::Result<()> {
+      let data = b"hello world";
+      let (_key, enc_bytes) = symmetric_encrypt_embed_nonce(data)?;
+
+      assert_eq!(header_len(&enc_bytes)? + data.len() + TAG_LEN_BYTES + NONCE_LEN_BYTES, enc_bytes.len(), "pre and post byte length mismatch");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_data1_length() -> anyhow::Result<()> {
+      let data: Vec<u8> = repeat_n(0x15, 64).collect();
+      let (_key, enc_bytes) = symmetric_encrypt_embed_nonce(data.as_slice())?;
+
+      assert_eq!(header_len(&enc_bytes)? + data.len() + TAG_LEN_BYTES + NONCE_LEN_BYTES, enc_bytes.len(), "pre and post byte length mismatch");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_nonce_is_not_already_embedded() -> anyhow::Result<()> {
+      let data: Vec<u8> = repeat_n(0x15, 64).collect();
+      let (_key, enc_bytes) = symmetric_encrypt_embed_nonce(data.as_slice())?;
+
+      let payload = &enc_bytes.as_slice()[header_len(&enc_bytes)?..];
+      assert_ne!(payload[0..NONCE_LEN_BYTES], payload[NONCE_LEN_BYTES..(2 * NONCE_LEN_BYTES)], "is the nonce already embedded?");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_shamir_60_roundtrip_test_0() -> anyhow::Result<()> {
+      let secret = b"hello world how are you doing?";
+      let n_shares = 4;
+      let k_thres = 3;
+
+      let keystore = test_keystore()?;
+      let (mp_keys, enc_data) = shamir_encrypt_embed_nonce_60_bytes(secret, n_shares, k_thres, keystore.default_master_key())?;
+      assert_eq!(header_len(&enc_data)? + secret.len() + TAG_LEN_BYTES + NONCE_LEN_BYTES, enc_data.len());
+
+      let mut decrypt_keys = mp_keys.clone();
+      let _ = decrypt_keys.remove((n_shares - 1) as usize);
+
+      let clear = shamir_decrypt_embed_nonce_60_bytes(enc_data.as_slice(), n_shares, k_thres, decrypt_keys, &keystore)?;
+      assert_eq!(secret.to_vec(), clear);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_shamir_60_roundtrip_test_1() -> anyhow::Result<()> {
+      let secret = b"hello world how are you doing?";
+      let n_shares = 4;
+      let k_thres = 3;
+
+      let keystore = test_keystore()?;
+      let (mp_keys, enc_data) = shamir_encrypt_embed_nonce_60_bytes(secret, n_shares, k_thres, keystore.default_master_key())?;
+      assert_eq!(header_len(&enc_data)? + secret.len() + TAG_LEN_BYTES + NONCE_LEN_BYTES, enc_data.len());
+
+      let mut decrypt_keys = mp_keys.clone();
+      let one_key = decrypt_keys.remove((n_shares - 1) as usize);
+
+      let one_key_enc = one_key.encode(Point::BIT_SIZE_IN_BYTES);
+      assert_eq!(Point::BIT_SIZE_IN_BYTES * 8, one_key_enc.len());
+
+      let clear = shamir_decrypt_embed_nonce_60_bytes(enc_data.as_slice(), n_shares, k_thres, decrypt_keys, &keystore)?;
+      assert_eq!(secret.to_vec(), clear);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_shamir_60_roundtrip_test_encode_decode() -> anyhow::Result<()> {
+      let secret = b"hello world how are you doing?";
+      let n_shares = 4;
+      let k_thres = 3;
+
+      let keystore = test_keystore()?;
+      let (mp_keys, enc_data) = shamir_encrypt_embed_nonce_60_bytes(secret, n_shares, k_thres, keystore.default_master_key())?;
+      assert_eq!(header_len(&enc_data)? + secret.len() + TAG_LEN_BYTES + NONCE_LEN_BYTES, enc_data.len());
+
+      let mut decrypt_keys = mp_keys.clone();
+      let one_key = decrypt_keys.remove((n_shares - 1) as usize);
+
+      let one_key_enc = one_key.encode(Point::BIT_SIZE_IN_BYTES);
+      assert_eq!(Point::BIT_SIZE_IN_BYTES * 8, one_key_enc.len());
+      let one_key_dec = MultiPartyKey8Points::decode(&one_key_enc, Point::BIT_SIZE_IN_BYTES)?;
+      assert_eq!(one_key, one_key_dec);
+
+      let clear = shamir_decrypt_embed_nonce_60_bytes(enc_data.as_slice(), n_shares, k_thres, decrypt_keys, &keystore)?;
+      assert_eq!(secret.to_vec(), clear);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_share_str_roundtrip() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (mp_keys, _) = shamir_encrypt_embed_nonce_60_bytes(b"hello world", 3, 2, keystore.default_master_key())?;
+
+      let text = mp_keys[1].encode_to_string();
+      assert_eq!(Point::BIT_SIZE_IN_BYTES * 2 * 8, text.len());
+      let spaced: String = text.chars().enumerate()
+        .flat_map(|(i, c)| if i > 0 && i % 36 == 0 { vec!['\n', c] } else { vec![c] })
+        .collect();
+      let share = MultiPartyKey8Points::decode_from_string(&spaced)?;
+      assert_eq!(mp_keys[1], share);
+      assert_eq!(2, share.index());
+
+      assert!(MultiPartyKey8Points::decode_from_string(&text[..text.len() - 2]).is_err());
+      let mut mixed = mp_keys[0].encode(Point::BIT_SIZE_IN_BYTES);
+      mixed[..Point::BIT_SIZE_IN_BYTES].copy_from_slice(&mp_keys[2].p0.encode());
+      assert!(MultiPartyKey8Points::decode(&mixed, Point::BIT_SIZE_IN_BYTES).is_err(), "points of different shares");
+      assert!(MultiPartyKey8Points::decode(&mixed[1..], Point::BIT_SIZE_IN_BYTES).is_err());
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_share_mnemonic_roundtrip() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (mp_keys, _) = shamir_encrypt_embed_nonce_60_bytes(b"hello world", 3, 2, keystore.default_master_key())?;
+
+      let words = mp_keys[2].encode_to_mnemonic();
+      assert_eq!(mp_keys[2], MultiPartyKey8Points::decode_from_mnemonic(&words)?);
+      assert_eq!(mp_keys[2].p5, Point::decode_from_mnemonic(&mp_keys[2].p5.encode_to_mnemonic())?);
+      assert!(Point::decode_from_mnemonic(&words).is_err(), "8 points are not one");
+
+      let mut typo: Vec<&str> = words.split(' ').collect();
+      typo[7] = if typo[7] == "academic" { "acid" } else { "academic" };
+      assert!(MultiPartyKey8Points::decode_from_mnemonic(&typo.join(" ")).unwrap_err().to_string().contains("checksum"));
+
+      Ok(())
+    }
+  }
+
+// #endregion ----------------
@@ -0,0 +1,184 @@
+use std::{
+  fmt,
+  sync::OnceLock,
+  time::{Duration, Instant},
+};
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use anyhow::anyhow;
+use argon2::{Algorithm, Argon2, Params, Version};
+use zeroize::Zeroizing;
+
+use super::{container::{KdfAlgorithm, KdfParams}, AES_256_LEN_BYTES};
+
+/// how long a single key derivation should take on this machine
+pub const KDF_TARGET_DURATION: Duration = Duration::from_millis(500);
+/// argon2 salt length in bytes
+const SALT_LEN_BYTES: usize = 16;
+/// never go below the argon2 recommended memory cost (19 MiB)
+const MIN_M_COST: u32 = Params::DEFAULT_M_COST;
+/// refuse to derive with params from a file that would need more than 2 GiB of memory
+const MAX_M_COST: u32 = 2 * 1024 * 1024;
+const MIN_T_COST: u32 = 2;
+const MAX_T_COST: u32 = 64;
+
+/// A passphrase typed in by the user, wiped from memory when dropped
+#[derive(Clone, PartialEq, Eq)]
+pub struct Passphrase(Zeroizing<String>);
+
+impl Passphrase {
+  pub fn new(passphrase: String) -> Self {
+    Self(Zeroizing::new(passphrase))
+  }
+
+  pub fn as_bytes(&self) -> &[u8] {
+    self.0.as_bytes()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+impl fmt::Debug for Passphrase {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // never log the passphrase itself
+    f.write_str("Passphrase(..)")
+  }
+}
+
+/// Argon2id cost parameters: memory in KiB, iterations and lanes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfCost {
+  pub m_cost: u32,
+  pub t_cost: u32,
+  pub p_cost: u32,
+}
+
+impl Default for KdfCost {
+  fn default() -> Self {
+    Self { m_cost: MIN_M_COST, t_cost: MIN_T_COST, p_cost: Params::DEFAULT_P_COST }
+  }
+}
+
+/// Picks an iteration count so that one derivation takes about `target` on this machine
+///
+/// Memory cost stays at the recommended 19 MiB, a single pass is timed and the
+/// iteration count is scaled from that.
+pub fn calibrate(target: Duration) -> anyhow::Result<KdfCost> {
+  let probe = KdfCost { t_cost: 1, ..KdfCost::default() };
+  let params = KdfParams {
+    algorithm: KdfAlgorithm::Argon2id,
+    m_cost: probe.m_cost,
+    t_cost: probe.t_cost,
+    p_cost: probe.p_cost,
+    salt: vec![0; SALT_LEN_BYTES],
+  };
+
+  let start = Instant::now();
+  let _ = derive_key(&Passphrase::new("calibration".to_string()), &params)?;
+  let per_pass = start.elapsed().max(Duration::from_millis(1));
+
+  let t_cost = (target.as_secs_f64() / per_pass.as_secs_f64()).round() as u32;
+
+  Ok(KdfCost { t_cost: t_cost.clamp(MIN_T_COST, MAX_T_COST), ..probe })
+}
+
+/// Cost calibrated against `KDF_TARGET_DURATION`, measured once per session
+pub fn calibrated_cost() -> anyhow::Result<KdfCost> {
+  static CALIBRATED: OnceLock<KdfCost> = OnceLock::new();
+
+  if let Some(cost) = CALIBRATED.get() {
+    return Ok(*cost);
+  }
+  let cost = calibrate(KDF_TARGET_DURATION)?;
+  Ok(*CALIBRATED.get_or_init(|| cost))
+}
+
+/// Fresh Argon2id params with a random salt, ready to be stored in a container header
+pub fn new_kdf_params(cost: KdfCost) -> KdfParams {
+  let mut salt = vec![0u8; SALT_LEN_BYTES];
+  OsRng.fill_bytes(&mut salt);
+
+  KdfParams {
+    algorithm: KdfAlgorithm::Argon2id,
+    m_cost: cost.m_cost,
+    t_cost: cost.t_cost,
+    p_cost: cost.p_cost,
+    salt,
+  }
+}
+
+/// Derives a 32 byte key wrapping key from `passphrase`
+pub fn derive_key(passphrase: &Passphrase, params: &KdfParams) -> anyhow::Result<Zeroizing<[u8; AES_256_LEN_BYTES]>> {
+  match params.algorithm {
+    KdfAlgorithm::Argon2id => {
+      if params.m_cost > MAX_M_COST || params.t_cost > MAX_T_COST {
+        return Err(anyhow!("Kdf cost too high: m_cost {} KiB, t_cost {}", params.m_cost, params.t_cost));
+      }
+      let argon_params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(AES_256_LEN_BYTES))
+        .map_err(|e| anyhow!("Invalid argon2 params: {e}"))?;
+      let argon = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params);
+
+      let mut key = Zeroizing::new([0u8; AES_256_LEN_BYTES]);
+      argon.hash_password_into(passphrase.as_bytes(), &params.salt, key.as_mut_slice())
+        .map_err(|e| anyhow!("Unable to derive key from passphrase: {e}"))?;
+
+      Ok(key)
+    }
+  }
+}
+
+/// keeps tests fast, far below anything used for real files
+#[cfg(test)]
+pub(crate) const TEST_KDF_COST: KdfCost = KdfCost { m_cost: 64, t_cost: 1, p_cost: 1 };
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_is_deterministic() -> anyhow::Result<()> {
+      let params = new_kdf_params(TEST_KDF_COST);
+      let passphrase = Passphrase::new("correct horse".to_string());
+
+      let k0 = derive_key(&passphrase, &params)?;
+      let k1 = derive_key(&passphrase, &params)?;
+      assert_eq!(k0, k1);
+
+      let k2 = derive_key(&Passphrase::new("battery staple".to_string()), &params)?;
+      assert_ne!(k0, k2, "different passphrase must give a different key");
+
+      let k3 = derive_key(&passphrase, &new_kdf_params(TEST_KDF_COST))?;
+      assert_ne!(k0, k3, "different salt must give a different key");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_rejects_excessive_cost() {
+      let mut params = new_kdf_params(TEST_KDF_COST);
+      params.m_cost = u32::MAX;
+
+      assert!(derive_key(&Passphrase::new("x".to_string()), &params).is_err());
+    }
+
+    #[test]
+    fn test_calibrate_stays_in_bounds() -> anyhow::Result<()> {
+      let cost = calibrate(Duration::from_millis(1))?;
+      assert_eq!(MIN_T_COST, cost.t_cost);
+      assert_eq!(MIN_M_COST, cost.m_cost);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_debug_hides_passphrase() {
+      let passphrase = Passphrase::new("hunter2".to_string());
+      assert!(!format!("{passphrase:?}").contains("hunter2"));
+    }
+  }
+
+// #endregion ----------------
@@ -0,0 +1,1327 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use zeroize::{Zeroize, Zeroizing};
+
+use super::{
+  container::{self, Parser}, gf256::{self, ByteShare}, mnemonic, recover_key_60_bytes, unwrap_container_key, vss::{self, VerifiableShare},
+  ContainerHeader, ContainerReader, KdfCost, KeyWrapMethod, Keystore, MasterKey, MultiPartyKey8Points, Passphrase, Point,
+  Recipient, SealingKey, X25519Recipient,
+};
+
+/// Every share file starts with these bytes
+pub const SHARE_MAGIC: [u8; 4] = *b"ENSH";
+/// Files holding several shares of one holder start with these bytes instead
+pub const BUNDLE_MAGIC: [u8; 4] = *b"ENSB";
+/// Current bundle format version
+const BUNDLE_VERSION: u8 = 1;
+/// Passwords vaults start with these bytes
+pub const PASSWORD_VAULT_MAGIC: [u8; 4] = *b"ENPV";
+/// Current passwords vault format version
+const PASSWORD_VAULT_VERSION: u8 = 1;
+/// Share format version holding 8 prime field points of a 60 byte wrapped key
+pub const POINTS_SHARE_VERSION: u8 = 1;
+/// Share format version holding a GF(256) share of a secret of any length
+pub const BYTES_SHARE_VERSION: u8 = 2;
+/// Share format version holding a pedersen verifiable share along with the commitments to check it
+pub const VERIFIABLE_SHARE_VERSION: u8 = 3;
+/// Current share format version, a GF(256) or pedersen share tagged with its epoch
+pub const SHARE_VERSION: u8 = 4;
+/// Share format version of a member's share in a split across groups, a version 4 share tagged with its group
+pub const GROUP_SHARE_VERSION: u8 = 5;
+/// Scheme byte of version 4 shares
+const SCHEME_BYTES: u8 = 1;
+const SCHEME_PEDERSEN: u8 = 2;
+/// Length of the identifier tying shares to the ciphertext they were split for
+pub const FILE_ID_LEN_BYTES: usize = 16;
+/// Length of the truncated sha256 checksum ending a share
+pub(super) const CHECKSUM_LEN_BYTES: usize = 4;
+/// Length of the points of one version 1 share
+const POINTS_LEN_BYTES: usize = Point::BIT_SIZE_IN_BYTES * 8;
+/// Length of everything in front of the share value, shared by both versions
+const PREFIX_LEN_BYTES: usize = SHARE_MAGIC.len() + 1 + 2 + 2 + 2 + FILE_ID_LEN_BYTES;
+/// hashed ahead of the ciphertext, so file ids can't be confused with other digests
+const FILE_ID_CONTEXT: &[u8] = b"encryption-app share file id v1";
+
+/// Identifies the split a share belongs to, for files this is derived from the ciphertext
+pub type FileId = [u8; FILE_ID_LEN_BYTES];
+
+/// One party's share of a split secret, along with what is needed to put the secret back together
+///
+/// Layout:
+///
+/// ```text
+/// magic        4 bytes  "ENSH"
+/// version      1 byte
+/// threshold    u16      shares needed to recover the secret
+/// total        u16      shares the secret was split into
+/// index        u16      which share this is, 1 based
+/// file id      16 bytes see `file_id`
+/// version 1:
+///   points     144 bytes 8 encoded `Point`s of a 60 byte wrapped key
+/// version 2:
+///   len        u32
+///   value      len bytes, the GF(256) share, as long as the secret
+/// version 3:
+///   len        u32      secret length
+///   commitments  32 bytes for each of k coefficients of every 31 byte chunk of the secret
+///   values     64 bytes per chunk, f(index) and g(index) scalars, see `vss::Commitments`
+/// version 4:
+///   scheme     u8       1 for a version 2 body, 2 for a version 3 body
+///   epoch      u32      how often the secret was refreshed, see `Share::refresh`
+///   body       as in version 2 or 3
+/// version 5:
+///   scheme, epoch as in version 4
+///   group      u16      which group the share's holder is a member of, 1 based
+///   groups needed u16   groups needed to recover the secret
+///   groups     u16      groups the secret was split across
+///   body       as in version 2, threshold, total and index count members of the group
+/// checksum     4 bytes  sha256 of everything before, truncated
+/// ```
+///
+/// Shares of versions 1 to 3 are from epoch 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+  threshold: u16,
+  total: u16,
+  file_id: FileId,
+  /// bumped every time the secret is split again, shares of different epochs never mix
+  epoch: u32,
+  /// set when the secret was split across groups, see `Share::split_groups`
+  group: Option<Group>,
+  value: ShareValue,
+}
+
+/// Where a member's share sits in a two level split
+///
+/// The secret is split across `total` groups, `threshold` of them recover it, and every group's
+/// share is split again across the group's members.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Group {
+  /// 1 based
+  pub index: u16,
+  pub threshold: u16,
+  pub total: u16,
+}
+
+/// Names a share within its split, members of a group are numbered within the group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ShareId {
+  pub group: Option<u16>,
+  pub index: u16,
+}
+
+impl std::fmt::Display for ShareId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.group {
+      Some(group) => write!(f, "#{} of group {group}", self.index),
+      None => write!(f, "#{}", self.index),
+    }
+  }
+}
+
+impl std::str::FromStr for ShareId {
+  type Err = anyhow::Error;
+
+  /// Parses what `Display` writes, `#3` or `#3 of group 2`
+  fn from_str(s: &str) -> anyhow::Result<Self> {
+    let s = s.trim().strip_prefix('#').ok_or_else(|| anyhow!("Share number must start with #: {s}"))?;
+    let (index, group) = match s.split_once(" of group ") {
+      Some((index, group)) => (index, Some(group.trim().parse()?)),
+      None => (s, None),
+    };
+    Ok(Self { group, index: index.trim().parse()? })
+  }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum ShareValue {
+  /// prime field points, only read from old share files
+  Points(MultiPartyKey8Points),
+  Bytes(ByteShare),
+  Pedersen(VerifiableShare),
+}
+
+impl std::fmt::Debug for ShareValue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Points(_) => write!(f, "Points(..)"),
+      Self::Bytes(share) => write!(f, "Bytes({} bytes)", share.y.len()),
+      Self::Pedersen(share) => write!(f, "Pedersen({})", share.commitments.fingerprint()),
+    }
+  }
+}
+
+impl Share {
+  /// Splits `secret` into `n_shares` shares, any `k_thres` of them recover it
+  pub fn split(secret: &[u8], n_shares: u16, k_thres: u16, file_id: FileId) -> anyhow::Result<Vec<Share>> {
+    Ok(gf256::split(secret, n_shares, k_thres)?
+      .into_iter()
+      .map(|share| Share { threshold: k_thres, total: n_shares, file_id, epoch: 0, group: None, value: ShareValue::Bytes(share) })
+      .collect())
+  }
+
+  /// Like `split`, but every share carries commitments its holder can check it against, see `verify`
+  pub fn split_verifiable(secret: &[u8], n_shares: u16, k_thres: u16, file_id: FileId) -> anyhow::Result<Vec<Share>> {
+    Ok(vss::split(secret, n_shares, k_thres)?
+      .into_iter()
+      .map(|share| Share { threshold: k_thres, total: n_shares, file_id, epoch: 0, group: None, value: ShareValue::Pedersen(share) })
+      .collect())
+  }
+
+  /// Splits `secret` across groups of `(n_members, k_members)`, any `k_groups` groups recover it
+  /// once each brings `k_members` of its members
+  ///
+  /// The secret is split into one share per group, and every group's share again across its members.
+  /// A group of threshold 1 hands each member the group's share itself.
+  pub fn split_groups(secret: &[u8], groups: &[(u16, u16)], k_groups: u16, file_id: FileId) -> anyhow::Result<Vec<Share>> {
+    let n_groups = u16::try_from(groups.len()).ok().filter(|x| *x <= gf256::MAX_SHARES)
+      .ok_or_else(|| anyhow!("At most {} groups", gf256::MAX_SHARES))?;
+    if k_groups < 2 || k_groups > n_groups {
+      return Err(anyhow!("groups needed must be between 2 and the number of groups: {k_groups} of {n_groups}"));
+    }
+    let mut shares = Vec::new();
+    for (&(n_members, k_members), group_share) in groups.iter().zip(gf256::split(secret, n_groups, k_groups)?) {
+      let group = Group { index: group_share.x as u16, threshold: k_groups, total: n_groups };
+      let members = match k_members {
+        1 if n_members <= gf256::MAX_SHARES => (1..=n_members)
+          .map(|x| ByteShare { x: x as u8, y: group_share.y.clone() })
+          .collect(),
+        1 => return Err(anyhow!("At most {} members in group {}", gf256::MAX_SHARES, group.index)),
+        _ => gf256::split(&group_share.y, n_members, k_members)
+          .map_err(|e| anyhow!("Group {}: {e}", group.index))?,
+      };
+      shares.extend(members.into_iter().map(|share| Share {
+        threshold: k_members, total: n_members, file_id, epoch: 0, group: Some(group), value: ShareValue::Bytes(share),
+      }));
+    }
+    Ok(shares)
+  }
+
+  /// Splits the secret `shares` hold again under a new policy, for the next epoch
+  ///
+  /// The secret stays the same but the new shares don't combine with the old ones,
+  /// once the new epoch is recorded (see `Keystore::set_share_epoch`) old shares are rejected.
+  /// Shares split across groups are refused, a flat split would quietly drop the groups.
+  pub fn refresh(shares: &[Share], n_shares: u16, k_thres: u16, verifiable: bool) -> anyhow::Result<Vec<Share>> {
+    if shares.iter().any(|x| x.group.is_some()) {
+      return Err(anyhow!("Shares split across groups can't be refreshed yet, the new shares would lose the groups"));
+    }
+    let secret = Share::combine(shares)?;
+    let first = &shares[0];
+    let epoch = first.epoch.checked_add(1)
+      .ok_or_else(|| anyhow!("Shares can't be refreshed any more often"))?;
+    let shares = if verifiable {
+      Share::split_verifiable(&secret, n_shares, k_thres, first.file_id)?
+    } else {
+      Share::split(&secret, n_shares, k_thres, first.file_id)?
+    };
+    Ok(shares.into_iter().map(|x| Share { epoch, ..x }).collect())
+  }
+
+  /// Puts the secret back together, errors unless `shares` are enough distinct shares of one split
+  pub fn combine(shares: &[Share]) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    Ok(Share::combine_robust(shares)?.0)
+  }
+
+  /// Like `combine`, also returns the shares that disagree with the rest and were left out
+  ///
+  /// Verifiable shares are checked against their commitments, plain shares beyond the threshold against each other.
+  /// Shares of a split across groups recover the share of every group with enough members first,
+  /// groups without enough members are skipped.
+  pub fn combine_robust(shares: &[Share]) -> anyhow::Result<(Zeroizing<Vec<u8>>, Vec<ShareId>)> {
+    let first = shares.first().ok_or_else(|| anyhow!("No shares to recover the secret from"))?;
+    for (i, share) in shares.iter().enumerate() {
+      first.check_same_split(share)?;
+      if shares[..i].iter().any(|x| x.id() == share.id()) {
+        return Err(anyhow!("Share {} is there twice", share.id()));
+      }
+    }
+    let Some(group) = first.group else {
+      return Share::combine_members(shares);
+    };
+
+    let mut group_shares = Vec::new();
+    let mut bad = Vec::new();
+    for index in 1..=group.total {
+      let members: Vec<Share> = shares.iter().filter(|x| x.id().group == Some(index)).cloned().collect();
+      if members.first().is_none_or(|x| members.len() < x.threshold as usize) {
+        continue;
+      }
+      let (y, bad_members) = Share::combine_members(&members)?;
+      bad.extend(bad_members);
+      group_shares.push(ByteShare { x: index as u8, y });
+    }
+    if group_shares.len() < group.threshold as usize {
+      return Err(anyhow!("Need {} groups to recover the secret, only {} have enough of their members",
+        group.threshold, group_shares.len()));
+    }
+    let (secret, bad_groups) = gf256::combine_robust(&group_shares, group.threshold)?;
+    // a group's share is only known to be bad as a whole
+    bad.extend(shares.iter()
+      .filter(|x| x.id().group.is_some_and(|g| bad_groups.contains(&(g as u8))))
+      .map(Share::id));
+    Ok((secret, bad))
+  }
+
+  /// Combines shares of one flat split, or of the members of one group
+  fn combine_members(shares: &[Share]) -> anyhow::Result<(Zeroizing<Vec<u8>>, Vec<ShareId>)> {
+    let first = &shares[0];
+    let id = |index| ShareId { group: first.id().group, index };
+    if shares.len() < first.threshold as usize {
+      return Err(anyhow!("Need {} shares to recover the secret, only have {}", first.threshold, shares.len()));
+    }
+
+    match &first.value {
+      ShareValue::Bytes(_) => {
+        let byte_shares: Vec<ByteShare> = shares.iter()
+          .filter_map(|x| match &x.value {
+            ShareValue::Bytes(share) => Some(share.clone()),
+            _ => None,
+          })
+          .collect();
+        let (secret, bad) = gf256::combine_robust(&byte_shares, first.threshold)?;
+        Ok((secret, bad.into_iter().map(|x| id(x as u16)).collect()))
+      }
+      ShareValue::Pedersen(_) => {
+        let (good, bad): (Vec<VerifiableShare>, Vec<VerifiableShare>) = shares.iter()
+          .filter_map(|x| match &x.value {
+            ShareValue::Pedersen(share) => Some(share.clone()),
+            _ => None,
+          })
+          .partition(|x| x.verify().is_ok());
+        // too few good ones left, the first bad one says what is wrong with it
+        if good.len() < first.threshold as usize && let Some(share) = bad.first() {
+          share.verify()?;
+        }
+        Ok((vss::combine(&good)?, bad.iter().map(|x| id(x.x)).collect()))
+      }
+      ShareValue::Points(_) => {
+        let keys = shares.iter()
+          .filter_map(|x| match &x.value {
+            ShareValue::Points(key) => Some(*key),
+            _ => None,
+          })
+          .collect();
+        Ok((Zeroizing::new(recover_key_60_bytes(keys)?), vec![]))
+      }
+    }
+  }
+
+  pub fn index(&self) -> u16 {
+    match &self.value {
+      ShareValue::Points(key) => key.index(),
+      ShareValue::Bytes(share) => share.x as u16,
+      ShareValue::Pedersen(share) => share.x,
+    }
+  }
+
+  /// The index along with the group, for telling apart members of different groups
+  pub fn id(&self) -> ShareId {
+    ShareId { group: self.group.map(|x| x.index), index: self.index() }
+  }
+
+  /// The group the share's holder is a member of, for splits across groups
+  pub fn group(&self) -> Option<Group> {
+    self.group
+  }
+
+  /// Whether the share carries commitments to check it against
+  pub fn is_verifiable(&self) -> bool {
+    matches!(self.value, ShareValue::Pedersen(_))
+  }
+
+  /// Errors when the share does not match the commitments the dealer published, or has none
+  pub fn verify(&self) -> anyhow::Result<()> {
+    match &self.value {
+      ShareValue::Pedersen(share) => share.verify(),
+      _ => Err(anyhow!("Share #{} has no commitments to check it against", self.index())),
+    }
+  }
+
+  /// Fingerprint of the commitments, the same on every share of a verifiable split
+  pub fn commitments_fingerprint(&self) -> Option<String> {
+    match &self.value {
+      ShareValue::Pedersen(share) => Some(share.commitments.fingerprint()),
+      _ => None,
+    }
+  }
+
+  pub fn threshold(&self) -> u16 {
+    self.threshold
+  }
+
+  pub fn total(&self) -> u16 {
+    self.total
+  }
+
+  pub fn file_id(&self) -> &FileId {
+    &self.file_id
+  }
+
+  pub fn epoch(&self) -> u32 {
+    self.epoch
+  }
+
+  /// Errors unless `other` came from the same split as this share
+  pub fn check_same_split(&self, other: &Share) -> anyhow::Result<()> {
+    if self.file_id != other.file_id {
+      return Err(anyhow!("Share {} belongs to a different file than share {}", other.id(), self.id()));
+    }
+    if self.epoch != other.epoch {
+      return Err(anyhow!("Share {} is from epoch {}, share {} from epoch {}, shares from before a refresh don't mix with newer ones",
+        other.id(), other.epoch, self.id(), self.epoch));
+    }
+    if self.group.map(|x| (x.threshold, x.total)) != other.group.map(|x| (x.threshold, x.total)) {
+      return Err(anyhow!("Share {} and share {} were split across groups differently", other.id(), self.id()));
+    }
+    // members of different groups may need different numbers of their members
+    if self.id().group == other.id().group && (self.threshold != other.threshold || self.total != other.total) {
+      return Err(anyhow!("Share {} is from a {} of {} split, share {} from a {} of {} split",
+        other.id(), other.threshold, other.total, self.id(), self.threshold, self.total));
+    }
+    if std::mem::discriminant(&self.value) != std::mem::discriminant(&other.value) {
+      return Err(anyhow!("Share {} and share {} use different share formats", other.id(), self.id()));
+    }
+    if self.commitments_fingerprint() != other.commitments_fingerprint() {
+      return Err(anyhow!("Share {} was committed to differently than share {}, the dealer handed out inconsistent shares", other.id(), self.id()));
+    }
+    Ok(())
+  }
+
+  pub fn encode(&self) -> Zeroizing<Vec<u8>> {
+    let mut res = Zeroizing::new(Vec::new());
+    res.extend(SHARE_MAGIC);
+    let scheme = match &self.value {
+      ShareValue::Points(_) => None,
+      ShareValue::Bytes(_) => Some(SCHEME_BYTES),
+      ShareValue::Pedersen(_) => Some(SCHEME_PEDERSEN),
+    };
+    res.push(match (scheme, self.group) {
+      (None, _) => POINTS_SHARE_VERSION,
+      (Some(_), None) => SHARE_VERSION,
+      (Some(_), Some(_)) => GROUP_SHARE_VERSION,
+    });
+    res.extend(self.threshold.to_be_bytes());
+    res.extend(self.total.to_be_bytes());
+    res.extend(self.index().to_be_bytes());
+    res.extend(self.file_id);
+    if let Some(scheme) = scheme {
+      res.push(scheme);
+      res.extend(self.epoch.to_be_bytes());
+    }
+    if let Some(group) = self.group {
+      res.extend(group.index.to_be_bytes());
+      res.extend(group.threshold.to_be_bytes());
+      res.extend(group.total.to_be_bytes());
+    }
+    match &self.value {
+      ShareValue::Points(key) => res.extend(key.encode(Point::BIT_SIZE_IN_BYTES).as_slice()),
+      ShareValue::Bytes(share) => {
+        res.extend((share.y.len() as u32).to_be_bytes());
+        res.extend(share.y.as_slice());
+      }
+      ShareValue::Pedersen(share) => share.encode_into(&mut res),
+    }
+    let checksum = checksum(&res);
+    res.extend(checksum);
+    res
+  }
+
+  pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+    if bytes.len() == POINTS_LEN_BYTES && !bytes.starts_with(&SHARE_MAGIC) {
+      return Err(anyhow!("Share has no threshold or file id, it predates the share file format"));
+    }
+    let mut parser = Parser::new(bytes);
+    if parser.take(SHARE_MAGIC.len())? != SHARE_MAGIC {
+      return Err(anyhow!("Not a share (bad magic)"));
+    }
+    let version = parser.u8()?;
+    if !(POINTS_SHARE_VERSION..=GROUP_SHARE_VERSION).contains(&version) {
+      return Err(anyhow!("Unsupported share version: {version}"));
+    }
+    let (body, expected) = bytes.split_at(bytes.len().checked_sub(CHECKSUM_LEN_BYTES)
+      .filter(|x| *x >= PREFIX_LEN_BYTES)
+      .ok_or_else(|| anyhow!("Share is truncated"))?);
+    if checksum(body) != expected {
+      return Err(anyhow!("Share is corrupted (checksum mismatch)"));
+    }
+
+    let mut parser = Parser::new(&body[SHARE_MAGIC.len() + 1..]);
+    let threshold = parser.u16()?;
+    let total = parser.u16()?;
+    let index = parser.u16()?;
+    let file_id = parser.take(FILE_ID_LEN_BYTES)?.try_into()?;
+    let (scheme, epoch) = match version {
+      SHARE_VERSION | GROUP_SHARE_VERSION => (parser.u8()?, parser.u32()?),
+      BYTES_SHARE_VERSION => (SCHEME_BYTES, 0),
+      VERIFIABLE_SHARE_VERSION => (SCHEME_PEDERSEN, 0),
+      _ => (0, 0),
+    };
+    let group = if version == GROUP_SHARE_VERSION {
+      let group = Group { index: parser.u16()?, threshold: parser.u16()?, total: parser.u16()? };
+      if group.threshold < 2 || group.threshold > group.total || group.total > gf256::MAX_SHARES
+        || group.index == 0 || group.index > group.total || scheme != SCHEME_BYTES {
+        return Err(anyhow!("Share of group {} of a {} of {} split across groups makes no sense", group.index, group.threshold, group.total));
+      }
+      Some(group)
+    } else {
+      None
+    };
+    let value = match (version, scheme) {
+      (POINTS_SHARE_VERSION, _) => {
+        ShareValue::Points(MultiPartyKey8Points::decode(parser.take(POINTS_LEN_BYTES)?, Point::BIT_SIZE_IN_BYTES)?)
+      }
+      (_, SCHEME_BYTES) => {
+        let len = parser.u32()? as usize;
+        let y = Zeroizing::new(parser.take(len)?.to_vec());
+        let x = u8::try_from(index).map_err(|_| anyhow!("Share #{index} is past the last possible share"))?;
+        ShareValue::Bytes(ByteShare { x, y })
+      }
+      (_, SCHEME_PEDERSEN) => ShareValue::Pedersen(VerifiableShare::decode_from(index, threshold, &mut parser)?),
+      (_, scheme) => return Err(anyhow!("Unknown share scheme: {scheme}")),
+    };
+    if !parser.rest().is_empty() {
+      return Err(anyhow!("Trailing bytes after share"));
+    }
+    // a group may hand every member its share as is
+    let min_threshold = if group.is_some() { 1 } else { 2 };
+    if threshold < min_threshold || threshold > total || index == 0 || index > total {
+      return Err(anyhow!("Share #{index} of a {threshold} of {total} split makes no sense"));
+    }
+
+    let share = Self { threshold, total, file_id, epoch, group, value };
+    if share.index() != index {
+      return Err(anyhow!("Share #{index} holds the points of share #{}", share.index()));
+    }
+    Ok(share)
+  }
+
+  /// Hex of the encoded share, for pasting
+  pub fn encode_to_string(&self) -> String {
+    hex::encode(self.encode().as_slice())
+  }
+
+  /// Parses pasted hex, whitespace is ignored
+  #[allow(dead_code)]
+  pub fn decode_from_string(s: &str) -> anyhow::Result<Self> {
+    let s = Zeroizing::new(s.split_whitespace().collect::<String>());
+    let bytes = Zeroizing::new(hex::decode(s.as_bytes())
+      .map_err(|e| anyhow!("Share is not valid hex: {e}"))?);
+    Self::decode(&bytes)
+  }
+
+  /// Words for writing the share down, the mnemonic checksum catches mistyped words
+  pub fn encode_to_mnemonic(&self) -> Zeroizing<String> {
+    mnemonic::encode(&self.encode())
+  }
+
+  #[allow(dead_code)]
+  pub fn decode_from_mnemonic(s: &str) -> anyhow::Result<Self> {
+    Self::decode(&mnemonic::decode(s)?)
+  }
+
+  /// Parses a share typed or pasted as hex or as words
+  #[allow(dead_code)]
+  pub fn decode_from_text(s: &str) -> anyhow::Result<Self> {
+    Self::decode(&decode_text(s)?)
+  }
+}
+
+/// Bytes of a share or bundle typed or pasted as hex or as words, pasted words never look like hex
+fn decode_text(s: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+  if s.chars().all(|c| c.is_ascii_hexdigit() || c.is_whitespace()) {
+    let s = Zeroizing::new(s.split_whitespace().collect::<String>());
+    Ok(Zeroizing::new(hex::decode(s.as_bytes()).map_err(|e| anyhow!("Share is not valid hex: {e}"))?))
+  } else {
+    mnemonic::decode(s)
+  }
+}
+
+impl Zeroize for Share {
+  fn zeroize(&mut self) {
+    match &mut self.value {
+      ShareValue::Points(key) => key.zeroize(),
+      ShareValue::Bytes(share) => share.zeroize(),
+      ShareValue::Pedersen(share) => share.zeroize(),
+    }
+  }
+}
+
+/// Shares handed to one holder together, every share is one vote toward the threshold
+///
+/// A holder trusted like two others simply gets two shares of the split. Layout:
+///
+/// ```text
+/// magic        4 bytes  "ENSB"
+/// version      1 byte
+/// count        u8       shares in the bundle, at least 2
+/// shares       count times: len u16, then a share as in `Share`
+/// checksum     4 bytes  sha256 of everything before, truncated
+/// ```
+///
+/// A bundle of one share is written as that share alone, single vote holders keep plain share files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareBundle {
+  shares: Vec<Share>,
+}
+
+impl ShareBundle {
+  /// Errors unless `shares` are distinct shares of one split
+  pub fn new(shares: Vec<Share>) -> anyhow::Result<Self> {
+    let first = shares.first().ok_or_else(|| anyhow!("A share bundle needs at least one share"))?;
+    if shares.len() > u8::MAX as usize {
+      return Err(anyhow!("At most {} shares fit in a bundle", u8::MAX));
+    }
+    for (i, share) in shares.iter().enumerate() {
+      first.check_same_split(share)?;
+      if shares[..i].iter().any(|x| x.id() == share.id()) {
+        return Err(anyhow!("Share {} is in the bundle twice", share.id()));
+      }
+    }
+    Ok(Self { shares })
+  }
+
+  pub fn shares(&self) -> &[Share] {
+    &self.shares
+  }
+
+  pub fn into_shares(self) -> Vec<Share> {
+    self.shares
+  }
+
+  /// How many of the threshold's votes the holder of this bundle brings
+  pub fn votes(&self) -> usize {
+    self.shares.len()
+  }
+
+  pub fn first(&self) -> &Share {
+    &self.shares[0]
+  }
+
+  /// Errors when a share is too long to be counted in a bundle, a single share is written as is
+  pub fn encode(&self) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    if let [share] = self.shares.as_slice() {
+      return Ok(share.encode());
+    }
+    let mut res = Zeroizing::new(Vec::new());
+    res.extend(BUNDLE_MAGIC);
+    res.push(BUNDLE_VERSION);
+    res.push(self.shares.len() as u8);
+    for share in &self.shares {
+      let encoded = share.encode();
+      let len = u16::try_from(encoded.len())
+        .map_err(|_| anyhow!("Share {} is {} bytes, too long for a bundle", share.id(), encoded.len()))?;
+      res.extend(len.to_be_bytes());
+      res.extend(encoded.as_slice());
+    }
+    let checksum = checksum(&res);
+    res.extend(checksum);
+    Ok(res)
+  }
+
+  /// Parses a bundle, or a single share file as a bundle of one
+  pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+    if !bytes.starts_with(&BUNDLE_MAGIC) {
+      return Self::new(vec![Share::decode(bytes)?]);
+    }
+    let (body, expected) = bytes.split_at(bytes.len().checked_sub(CHECKSUM_LEN_BYTES)
+      .filter(|x| *x > BUNDLE_MAGIC.len() + 2)
+      .ok_or_else(|| anyhow!("Share bundle is truncated"))?);
+    if checksum(body) != expected {
+      return Err(anyhow!("Share bundle is corrupted (checksum mismatch)"));
+    }
+
+    let mut parser = Parser::new(&body[BUNDLE_MAGIC.len()..]);
+    let version = parser.u8()?;
+    if version != BUNDLE_VERSION {
+      return Err(anyhow!("Unsupported share bundle version: {version}"));
+    }
+    let count = parser.u8()?;
+    let shares = (0..count)
+      .map(|_| {
+        let len = parser.u16()? as usize;
+        Share::decode(parser.take(len)?)
+      })
+      .collect::<anyhow::Result<Vec<_>>>()?;
+    if !parser.rest().is_empty() {
+      return Err(anyhow!("Trailing bytes after share bundle"));
+    }
+    Self::new(shares)
+  }
+
+  /// Words for writing the bundle down, see `Share::encode_to_mnemonic`
+  pub fn encode_to_mnemonic(&self) -> anyhow::Result<Zeroizing<String>> {
+    Ok(mnemonic::encode(&self.encode()?))
+  }
+
+  /// Parses a bundle or a share typed or pasted as hex or as words
+  pub fn decode_from_text(s: &str) -> anyhow::Result<Self> {
+    Self::decode(&decode_text(s)?)
+  }
+
+  /// Encrypts the bundle to its custodian's public key, so it can travel over untrusted channels
+  ///
+  /// The result is an ordinary envelope container, the custodian opens it like any file encrypted to them.
+  pub fn seal(&self, recipient: &X25519Recipient) -> anyhow::Result<Vec<u8>> {
+    SealingKey::envelope(&[Recipient::X25519(*recipient)])?.seal(&self.encode()?)
+  }
+
+  /// Encrypts the bundle with its custodian's own passphrase, argon2id derives the key wrapping key
+  ///
+  /// Like `seal` the result is an envelope container, slow by design.
+  pub fn protect(&self, passphrase: &Passphrase, cost: KdfCost) -> anyhow::Result<Vec<u8>> {
+    SealingKey::envelope(&[Recipient::Passphrase(passphrase.clone(), cost)])?.seal(&self.encode()?)
+  }
+
+  /// Whether `bytes` are a sealed or protected bundle rather than a share or bundle file
+  pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&container::MAGIC)
+  }
+
+  /// Whether a sealed bundle opens with a passphrase, see `protect`
+  pub fn needs_passphrase(bytes: &[u8]) -> bool {
+    ContainerReader::parse(bytes).is_ok_and(|x| x.envelope().is_some_and(|x| x.needs_passphrase(None)))
+  }
+
+  /// Key ids of the public keys a sealed bundle was sealed to, see `X25519Recipient::id`
+  pub fn sealed_to(bytes: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+    let reader = ContainerReader::parse(bytes)?;
+    let envelope = reader.envelope().ok_or_else(|| anyhow!("Not a sealed share"))?;
+    Ok(envelope.stanzas().iter().map(|x| x.key_id.clone()).collect())
+  }
+
+  /// Opens a bundle sealed with `seal`, the custodian's identity must be in `keystore`
+  pub fn unseal(bytes: &[u8], keystore: &Keystore) -> anyhow::Result<Self> {
+    Self::open_sealed(bytes, Some(keystore), None)
+  }
+
+  /// Opens a bundle protected with `protect`, slow by design
+  pub fn unseal_with_passphrase(bytes: &[u8], passphrase: &Passphrase) -> anyhow::Result<Self> {
+    Self::open_sealed(bytes, None, Some(passphrase))
+  }
+
+  fn open_sealed(bytes: &[u8], keystore: Option<&Keystore>, passphrase: Option<&Passphrase>) -> anyhow::Result<Self> {
+    let reader = ContainerReader::parse(bytes)?;
+    let envelope = reader.envelope().ok_or_else(|| anyhow!("Not a sealed share"))?;
+    let data_key = envelope.unwrap(reader.header_bytes(), keystore, passphrase)?;
+    Self::decode(&Zeroizing::new(reader.open(&data_key)?))
+  }
+}
+
+/// Every custodian's share protected with their own passphrase, in one file
+///
+/// Any k custodians typing their passphrases on the same machine recover the secret. Layout:
+///
+/// ```text
+/// magic        4 bytes  "ENPV"
+/// version      1 byte
+/// count        u8       custodians
+/// entries      count times: label len u8 | label, utf-8 | len u32 | bundle protected with `ShareBundle::protect`
+/// checksum     4 bytes  sha256 of everything before, truncated
+/// ```
+///
+/// Labels are not secret, they tell custodians which entry is theirs so only one passphrase is tried per attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordVault {
+  entries: Vec<(String, Vec<u8>)>,
+}
+
+impl PasswordVault {
+  /// Errors unless every entry is a protected bundle under a distinct label
+  pub fn new(entries: Vec<(String, Vec<u8>)>) -> anyhow::Result<Self> {
+    if entries.is_empty() || entries.len() > u8::MAX as usize {
+      return Err(anyhow!("A passwords vault holds 1 to {} shares, not {}", u8::MAX, entries.len()));
+    }
+    for (i, (label, protected)) in entries.iter().enumerate() {
+      if label.len() > u8::MAX as usize {
+        return Err(anyhow!("Label is too long: {label}"));
+      }
+      if entries[..i].iter().any(|(x, _)| x == label) {
+        return Err(anyhow!("{label} is in the passwords vault twice"));
+      }
+      if !ShareBundle::needs_passphrase(protected) {
+        return Err(anyhow!("The share of {label} is not protected with a passphrase"));
+      }
+    }
+    Ok(Self { entries })
+  }
+
+  /// Label and protected bundle of every custodian
+  pub fn entries(&self) -> &[(String, Vec<u8>)] {
+    &self.entries
+  }
+
+  pub fn encode(&self) -> Vec<u8> {
+    let mut res = Vec::new();
+    res.extend(PASSWORD_VAULT_MAGIC);
+    res.push(PASSWORD_VAULT_VERSION);
+    res.push(self.entries.len() as u8);
+    for (label, protected) in &self.entries {
+      res.push(label.len() as u8);
+      res.extend(label.as_bytes());
+      res.extend((protected.len() as u32).to_be_bytes());
+      res.extend(protected);
+    }
+    let checksum = checksum(&res);
+    res.extend(checksum);
+    res
+  }
+
+  pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+    if !bytes.starts_with(&PASSWORD_VAULT_MAGIC) {
+      return Err(anyhow!("Not a passwords vault (bad magic)"));
+    }
+    let (body, expected) = bytes.split_at(bytes.len().checked_sub(CHECKSUM_LEN_BYTES)
+      .filter(|x| *x > PASSWORD_VAULT_MAGIC.len() + 2)
+      .ok_or_else(|| anyhow!("Passwords vault is truncated"))?);
+    if checksum(body) != expected {
+      return Err(anyhow!("Passwords vault is corrupted (checksum mismatch)"));
+    }
+
+    let mut parser = Parser::new(&body[PASSWORD_VAULT_MAGIC.len()..]);
+    let version = parser.u8()?;
+    if version != PASSWORD_VAULT_VERSION {
+      return Err(anyhow!("Unsupported passwords vault version: {version}"));
+    }
+    let count = parser.u8()?;
+    let entries = (0..count)
+      .map(|_| {
+        let len = parser.u8()? as usize;
+        let label = String::from_utf8(parser.take(len)?.to_vec())
+          .map_err(|_| anyhow!("Passwords vault label is not utf-8"))?;
+        let len = parser.u32()? as usize;
+        Ok((label, parser.take(len)?.to_vec()))
+      })
+      .collect::<anyhow::Result<Vec<_>>>()?;
+    if !parser.rest().is_empty() {
+      return Err(anyhow!("Trailing bytes after passwords vault"));
+    }
+    Self::new(entries)
+  }
+}
+
+impl Zeroize for ShareBundle {
+  fn zeroize(&mut self) {
+    self.shares.iter_mut().for_each(Zeroize::zeroize);
+  }
+}
+
+/// Identifier of a ciphertext, recorded in every share split for it
+#[allow(dead_code)]
+pub fn file_id(ciphertext: &[u8]) -> FileId {
+  let mut hasher = file_id_hasher();
+  hasher.update(ciphertext);
+  hasher.finalize()[..FILE_ID_LEN_BYTES].try_into().expect("sha256 is longer than a file id")
+}
+
+/// `file_id` of everything `reader` yields, for ciphertexts too large to hold in memory
+pub async fn file_id_reader<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<FileId> {
+  let mut hasher = file_id_hasher();
+  let mut buf = vec![0u8; 64 * 1024];
+  loop {
+    let len = reader.read(&mut buf).await?;
+    if len == 0 {
+      break;
+    }
+    hasher.update(&buf[..len]);
+  }
+  Ok(hasher.finalize()[..FILE_ID_LEN_BYTES].try_into().expect("sha256 is longer than a file id"))
+}
+
+fn file_id_hasher() -> Sha256 {
+  let mut hasher = Sha256::new();
+  hasher.update(FILE_ID_CONTEXT);
+  hasher
+}
+
+pub(super) fn checksum(body: &[u8]) -> [u8; CHECKSUM_LEN_BYTES] {
+  Sha256::digest(body)[..CHECKSUM_LEN_BYTES].try_into().expect("sha256 is longer than a checksum")
+}
+
+/// Encrypts `data` with the master key and splits the wrapped data key into `n_shares` shares,
+/// verifiable ones carry pedersen commitments
+#[allow(dead_code)]
+pub fn split_encrypt(data: &[u8], n_shares: u16, k_thres: u16, verifiable: bool, master_key: &MasterKey) -> anyhow::Result<(Vec<Share>, Vec<u8>)> {
+  let sealing_key = SealingKey::shamir_master_key(master_key)?;
+  let enc_data = sealing_key.seal(data)?;
+  let shares = split_key(&sealing_key, n_shares, k_thres, verifiable, file_id(&enc_data))?;
+  Ok((shares, enc_data))
+}
+
+/// Like `split_encrypt`, but splits the wrapped data key across groups, see `Share::split_groups`
+#[allow(dead_code)]
+pub fn split_encrypt_groups(data: &[u8], groups: &[(u16, u16)], k_groups: u16, master_key: &MasterKey) -> anyhow::Result<(Vec<Share>, Vec<u8>)> {
+  let sealing_key = SealingKey::shamir_master_key(master_key)?;
+  let enc_data = sealing_key.seal(data)?;
+  let shares = split_key_groups(&sealing_key, groups, k_groups, file_id(&enc_data))?;
+  Ok((shares, enc_data))
+}
+
+/// Splits the wrapped data key of a container sealed with `SealingKey::shamir_master_key` into `n_shares` shares,
+/// `file_id` is that of the finished container
+pub fn split_key(sealing_key: &SealingKey, n_shares: u16, k_thres: u16, verifiable: bool, file_id: FileId) -> anyhow::Result<Vec<Share>> {
+  let wrapped_key = split_wrapped_key(sealing_key)?;
+  if verifiable {
+    Share::split_verifiable(wrapped_key, n_shares, k_thres, file_id)
+  } else {
+    Share::split(wrapped_key, n_shares, k_thres, file_id)
+  }
+}
+
+/// Like `split_key`, but across groups, see `Share::split_groups`
+pub fn split_key_groups(sealing_key: &SealingKey, groups: &[(u16, u16)], k_groups: u16, file_id: FileId) -> anyhow::Result<Vec<Share>> {
+  Share::split_groups(split_wrapped_key(sealing_key)?, groups, k_groups, file_id)
+}
+
+fn split_wrapped_key(sealing_key: &SealingKey) -> anyhow::Result<&[u8]> {
+  sealing_key.wrapped_key()
+    .filter(|_| sealing_key.header.key_wrap == KeyWrapMethod::ShamirMasterKey)
+    .ok_or_else(|| anyhow!("Only a key wrapped for splitting can be split into shares"))
+}
+
+/// Checks `shares` belong to `enc_data` and recover its wrapped key, then decrypts it
+///
+/// Also returns the bad shares that were left out, see `Share::combine_robust`.
+#[allow(dead_code)]
+pub fn combine_decrypt(enc_data: &[u8], shares: &[Share], keystore: &Keystore) -> anyhow::Result<(Vec<u8>, Vec<ShareId>)> {
+  let reader = ContainerReader::parse(enc_data)?;
+  let (data_key, bad) = combine_key(reader.header(), &file_id(enc_data), shares, keystore)?;
+  Ok((reader.open(&data_key)?, bad))
+}
+
+/// Checks `shares` belong to the container with `header` and `file_id` and recover its data key,
+/// the payload is left to the caller so it can be streamed
+///
+/// Also returns the bad shares that were left out, see `Share::combine_robust`.
+pub fn combine_key(header: &ContainerHeader, file_id: &FileId, shares: &[Share], keystore: &Keystore) -> anyhow::Result<(Zeroizing<Vec<u8>>, Vec<ShareId>)> {
+  let (wrapped_key, bad) = combine_current(file_id, shares, keystore)?;
+  Ok((unwrap_container_key(header, &wrapped_key, keystore)?, bad))
+}
+
+/// Splits the wrapped key of the container with `header` and `file_id` again into `n_shares` shares of the next epoch,
+/// the ciphertext and data key stay the same
+///
+/// The caller records the new epoch in the keystore, from then on the old shares no longer open the file.
+/// Revocation is per keystore: epochs are only checked against `Keystore::share_epoch`, so another keystore
+/// holding the master key still takes the old shares until it records the new epoch too.
+pub fn refresh_shares(header: &ContainerHeader, file_id: &FileId, shares: &[Share], n_shares: u16, k_thres: u16, verifiable: bool, keystore: &Keystore) -> anyhow::Result<Vec<Share>> {
+  let (wrapped_key, bad) = combine_current(file_id, shares, keystore)?;
+  // a wrong secret from bad unverifiable shares must not be handed out again
+  unwrap_container_key(header, &wrapped_key, keystore)?;
+  let good: Vec<Share> = shares.iter().filter(|x| !bad.contains(&x.id())).cloned().collect();
+  Share::refresh(&good, n_shares, k_thres, verifiable)
+}
+
+/// Splits a secret typed in by the user, e.g. a root password, into `n_shares` shares, any `k_thres` of them recover it
+///
+/// Unlike `split_encrypt` the shares hold the secret itself, no keystore is needed to put it back together
+/// with `Share::combine`. There is no file to derive an id from, every split gets a random one.
+pub fn split_secret(secret: &[u8], n_shares: u16, k_thres: u16, verifiable: bool) -> anyhow::Result<Vec<Share>> {
+  let mut split_id = FileId::default();
+  OsRng.fill_bytes(&mut split_id);
+  if verifiable {
+    Share::split_verifiable(secret, n_shares, k_thres, split_id)
+  } else {
+    Share::split(secret, n_shares, k_thres, split_id)
+  }
+}
+
+/// Recovers the wrapped key of the file `file_id` from `shares`, unless they belong to another file or were refreshed since
+fn combine_current(file_id: &FileId, shares: &[Share], keystore: &Keystore) -> anyhow::Result<(Zeroizing<Vec<u8>>, Vec<ShareId>)> {
+  if let Some(first) = shares.first() {
+    if first.file_id != *file_id {
+      return Err(anyhow!("Shares were split for a different file"));
+    }
+    let epoch = keystore.share_epoch(&first.file_id);
+    if first.epoch < epoch {
+      return Err(anyhow!("Shares are from epoch {}, they were refreshed to epoch {epoch} and no longer open the file", first.epoch));
+    }
+  }
+  Share::combine_robust(shares)
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{passphrase::{Passphrase, TEST_KDF_COST}, shamir_encrypt_embed_nonce_60_bytes, ContainerStreamReader};
+
+    fn test_keystore() -> anyhow::Result<Keystore> {
+      Keystore::create(&Passphrase::new("test keystore".to_string()), TEST_KDF_COST)
+    }
+
+    #[test]
+    fn test_share_encode_roundtrip() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (shares, enc_data) = split_encrypt(b"hello world", 5, 3, false, keystore.default_master_key())?;
+
+      let bytes = shares[1].encode();
+      let share = Share::decode(&bytes)?;
+      assert_eq!(shares[1], share);
+      assert_eq!((2, 3, 5), (share.index(), share.threshold(), share.total()));
+      assert_eq!(file_id(&enc_data), *share.file_id());
+      assert_eq!(share, Share::decode_from_string(&shares[1].encode_to_string())?);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_share_mnemonic() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      for verifiable in [false, true] {
+        let (shares, _) = split_encrypt(b"hello world", 3, 2, verifiable, keystore.default_master_key())?;
+        let words = shares[0].encode_to_mnemonic();
+        assert_eq!(shares[0], Share::decode_from_mnemonic(&words)?);
+        assert_eq!(shares[0], Share::decode_from_text(&words)?);
+        assert_eq!(shares[0], Share::decode_from_text(&shares[0].encode_to_string())?);
+      }
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_share_any_length_secret() -> anyhow::Result<()> {
+      for secret in [b"k".to_vec(), b"correct horse battery staple".to_vec(), vec![7u8; 10_000]] {
+        let shares = Share::split(&secret, 4, 2, [9u8; FILE_ID_LEN_BYTES])?;
+        let decoded = shares.iter()
+          .map(|x| Share::decode(&x.encode()))
+          .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(secret, *Share::combine(&decoded[2..])?);
+      }
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_split_secret() -> anyhow::Result<()> {
+      for verifiable in [false, true] {
+        let shares = split_secret(b"hunter2", 5, 3, verifiable)?;
+        let pasted = [&shares[4], &shares[0], &shares[2]].iter()
+          .map(|x| Share::decode_from_text(&x.encode_to_mnemonic()))
+          .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(b"hunter2".to_vec(), *Share::combine(&pasted)?);
+        assert!(Share::combine(&pasted[..2]).is_err());
+
+        let other = split_secret(b"hunter2", 5, 3, verifiable)?;
+        assert_ne!(shares[0].file_id(), other[0].file_id());
+        assert!(Share::combine(&[shares[0].clone(), shares[1].clone(), other[2].clone()]).is_err(), "shares of two splits don't mix");
+      }
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_share_detects_corruption() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (shares, _) = split_encrypt(b"hello world", 3, 2, false, keystore.default_master_key())?;
+      let bytes = shares[0].encode();
+
+      for pos in [5, 12, 40, bytes.len() - 1] {
+        let mut corrupted = bytes.clone();
+        corrupted[pos] ^= 0x01;
+        let err = Share::decode(&corrupted).unwrap_err();
+        assert!(err.to_string().contains("checksum"), "byte {pos}: {err}");
+      }
+      assert!(Share::decode(&bytes[..bytes.len() - 1]).is_err());
+      assert!(Share::decode(&bytes[..10]).is_err());
+      let (legacy, _) = shamir_encrypt_embed_nonce_60_bytes(b"hello world", 3, 2, keystore.default_master_key())?;
+      let legacy = legacy[0].encode(Point::BIT_SIZE_IN_BYTES);
+      assert!(Share::decode(&legacy).unwrap_err().to_string().contains("predates"));
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_version_1_shares_still_combine() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (keys, enc_data) = shamir_encrypt_embed_nonce_60_bytes(b"hello world", 3, 2, keystore.default_master_key())?;
+      let shares: Vec<Share> = keys.into_iter()
+        .map(|key| Share { threshold: 2, total: 3, file_id: file_id(&enc_data), epoch: 0, group: None, value: ShareValue::Points(key) })
+        .collect();
+      let bytes = shares[2].encode();
+      assert_eq!(POINTS_SHARE_VERSION, bytes[SHARE_MAGIC.len()]);
+
+      let decoded = [shares[0].clone(), Share::decode(&bytes)?];
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_data, &decoded, &keystore)?.0);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_verifiable_shares() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (shares, enc_data) = split_encrypt(b"hello world", 3, 2, true, keystore.default_master_key())?;
+      let decoded = shares.iter()
+        .map(|x| Share::decode(&x.encode()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+      for share in &decoded {
+        assert!(share.is_verifiable());
+        share.verify()?;
+        assert_eq!(shares[0].commitments_fingerprint(), share.commitments_fingerprint());
+      }
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_data, &decoded[1..], &keystore)?.0);
+
+      // a dealer handing one holder a share off the committed polynomial
+      let mut bogus = decoded[2].clone();
+      if let ShareValue::Pedersen(share) = &mut bogus.value {
+        let mut bytes = Vec::new();
+        share.encode_into(&mut bytes);
+        let last = bytes.len() - 40;
+        bytes[last] ^= 0x01;
+        *share = VerifiableShare::decode_from(3, 2, &mut Parser::new(&bytes))?;
+      }
+      assert!(bogus.verify().is_err());
+      let err = combine_decrypt(&enc_data, &[decoded[0].clone(), bogus.clone()], &keystore).unwrap_err();
+      assert!(err.to_string().contains("commitments"), "{err}");
+      let (plaintext, bad) = combine_decrypt(&enc_data, &[decoded[0].clone(), bogus, decoded[1].clone()], &keystore)?;
+      assert_eq!((b"hello world".to_vec(), vec![ShareId { group: None, index: 3 }]), (plaintext, bad), "enough good shares without it");
+
+      let (plain, _) = split_encrypt(b"hello world", 3, 2, false, keystore.default_master_key())?;
+      assert!(!plain[0].is_verifiable());
+      assert!(plain[0].verify().is_err());
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_combine_finds_bad_plain_share() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (shares, enc_data) = split_encrypt(b"hello world", 5, 3, false, keystore.default_master_key())?;
+      let mut tampered = shares.clone();
+      if let ShareValue::Bytes(share) = &mut tampered[3].value {
+        share.y[7] ^= 0x20;
+      }
+
+      let (plaintext, bad) = combine_decrypt(&enc_data, &tampered, &keystore)?;
+      assert_eq!((b"hello world".to_vec(), vec![ShareId { group: None, index: 4 }]), (plaintext, bad));
+      let err = combine_decrypt(&enc_data, &tampered[..4], &keystore).unwrap_err();
+      assert!(err.to_string().contains("disagree"), "{err}");
+
+      let header = ContainerReader::parse(&enc_data)?.header().clone();
+      let refreshed = refresh_shares(&header, &file_id(&enc_data), &tampered, 3, 2, false, &keystore)?;
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_data, &refreshed[..2], &keystore)?.0);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_share_bundle() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (shares, enc_data) = split_encrypt(b"hello world", 5, 3, true, keystore.default_master_key())?;
+
+      // the cto weighs as much as two engineers
+      let cto = ShareBundle::new(shares[..2].to_vec())?;
+      assert_eq!(2, cto.votes());
+      let encoded = cto.encode()?;
+      assert!(encoded.starts_with(&BUNDLE_MAGIC));
+      let decoded = ShareBundle::decode(&encoded)?;
+      assert_eq!(cto, decoded);
+      assert_eq!(cto, ShareBundle::decode_from_text(&cto.encode_to_mnemonic()?)?);
+      assert_eq!(cto, ShareBundle::decode_from_text(&hex::encode(encoded.as_slice()))?);
+
+      let engineer = ShareBundle::new(vec![shares[3].clone()])?;
+      assert_eq!(shares[3].encode(), engineer.encode()?, "single shares stay plain share files");
+      assert_eq!(engineer, ShareBundle::decode(&shares[3].encode())?);
+
+      let mut collected = decoded.into_shares();
+      assert!(combine_decrypt(&enc_data, &collected, &keystore).is_err(), "2 votes of 3");
+      collected.extend(engineer.into_shares());
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_data, &collected, &keystore)?.0);
+
+      let mut corrupted = encoded.to_vec();
+      corrupted[10] ^= 1;
+      assert!(ShareBundle::decode(&corrupted).unwrap_err().to_string().contains("checksum"));
+      assert!(ShareBundle::new(vec![shares[0].clone(), shares[0].clone()]).is_err());
+      let (other, _) = split_encrypt(b"hello world", 5, 3, true, keystore.default_master_key())?;
+      assert!(ShareBundle::new(vec![shares[0].clone(), other[1].clone()]).is_err());
+      let long = split_secret(&vec![7u8; 70_000], 3, 2, false)?;
+      assert!(ShareBundle::new(long[..2].to_vec())?.encode().is_err(), "share lengths are u16 in a bundle");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_sealed_bundle() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let custodian = test_keystore()?;
+      let recipient = custodian.default_x25519_identity().expect("new keystores have an identity").recipient();
+      let (shares, _) = split_encrypt(b"hello world", 5, 3, false, keystore.default_master_key())?;
+      let bundle = ShareBundle::new(shares[..2].to_vec())?;
+
+      let sealed = bundle.seal(&recipient)?;
+      assert!(ShareBundle::is_sealed(&sealed));
+      assert!(!ShareBundle::is_sealed(&bundle.encode()?));
+      assert_eq!(vec![recipient.id()], ShareBundle::sealed_to(&sealed)?);
+      assert!(ShareBundle::decode(&sealed).is_err(), "sealed bundles are not share files");
+
+      assert_eq!(bundle, ShareBundle::unseal(&sealed, &custodian)?);
+      assert!(ShareBundle::unseal(&sealed, &keystore).is_err(), "only the custodian's keystore opens it");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_password_vault() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (shares, enc_data) = split_encrypt(b"hello world", 3, 2, false, keystore.default_master_key())?;
+      let passphrases = ["alice's", "bob's", "carol's"].map(|x| Passphrase::new(x.to_string()));
+      let entries = ["alice", "bob", "carol"].iter().zip(&shares).zip(&passphrases)
+        .map(|((label, share), passphrase)| Ok((label.to_string(), ShareBundle::new(vec![share.clone()])?.protect(passphrase, TEST_KDF_COST)?)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+      let vault = PasswordVault::decode(&PasswordVault::new(entries.clone())?.encode())?;
+      assert_eq!(vec!["alice", "bob", "carol"], vault.entries().iter().map(|x| x.0.as_str()).collect::<Vec<_>>());
+      assert!(ShareBundle::needs_passphrase(&vault.entries()[0].1));
+
+      // any two of the three type their passphrases
+      let bob = ShareBundle::unseal_with_passphrase(&vault.entries()[1].1, &passphrases[1])?;
+      let carol = ShareBundle::unseal_with_passphrase(&vault.entries()[2].1, &passphrases[2])?;
+      let collected = [bob.into_shares(), carol.into_shares()].concat();
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_data, &collected, &keystore)?.0);
+
+      let err = ShareBundle::unseal_with_passphrase(&vault.entries()[0].1, &passphrases[1]).unwrap_err();
+      assert!(err.to_string().contains("Wrong passphrase"), "{err}");
+      assert!(PasswordVault::new(vec![entries[0].clone(), entries[0].clone()]).is_err());
+      let sealed = ShareBundle::new(vec![shares[0].clone()])?.seal(&keystore.default_x25519_identity().expect("identity").recipient())?;
+      assert!(PasswordVault::new(vec![("alice".to_string(), sealed)]).is_err(), "public key sealed shares don't belong in it");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_split_groups() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      // 2 of 3 from security and 1 of 2 from management
+      let (shares, enc_data) = split_encrypt_groups(b"hello world", &[(3, 2), (2, 1)], 2, keystore.default_master_key())?;
+      assert_eq!(5, shares.len());
+      let (security, management) = shares.split_at(3);
+      assert_eq!(Some(Group { index: 2, threshold: 2, total: 2 }), management[1].group());
+      assert_eq!(ShareId { group: Some(2), index: 2 }, management[1].id());
+      assert_eq!("#2 of group 2", management[1].id().to_string());
+      assert_eq!(management[1].id(), "#2 of group 2".parse()?);
+      assert_eq!(security[2].id(), security[2].id().to_string().parse()?);
+
+      let bytes = management[1].encode();
+      assert_eq!(GROUP_SHARE_VERSION, bytes[SHARE_MAGIC.len()]);
+      assert_eq!(management[1], Share::decode(&bytes)?);
+
+      let collected = [&security[..2], &management[1..]].concat();
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_data, &collected, &keystore)?.0);
+      let collected = [&security[1..], &management[..1]].concat();
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_data, &collected, &keystore)?.0);
+
+      let err = combine_decrypt(&enc_data, security, &keystore).unwrap_err();
+      assert!(err.to_string().contains("Need 2 groups"), "{err}");
+      let err = combine_decrypt(&enc_data, &[&security[..1], management].concat(), &keystore).unwrap_err();
+      assert!(err.to_string().contains("Need 2 groups"), "{err}");
+
+      let header = ContainerReader::parse(&enc_data)?.header().clone();
+      let err = refresh_shares(&header, &file_id(&enc_data), &shares, 3, 2, false, &keystore).unwrap_err();
+      assert!(err.to_string().contains("across groups"), "{err}");
+
+      // the first member of both groups is share #1, ids tell them apart
+      let collected = [&security[..1], &management[..1]].concat();
+      assert!(ShareBundle::new(collected).is_ok());
+      assert!(Share::split_groups(b"hello world", &[(3, 2)], 1, [0; FILE_ID_LEN_BYTES]).is_err(), "one group is a flat split");
+      assert!(Share::split_groups(b"hello world", &[(3, 2), (2, 3)], 2, [0; FILE_ID_LEN_BYTES]).is_err());
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_combine_rejects_mixed_splits() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (shares_a, enc_a) = split_encrypt(b"hello world", 3, 2, false, keystore.default_master_key())?;
+      let (shares_b, _) = split_encrypt(b"hello world", 3, 2, false, keystore.default_master_key())?;
+
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_a, &shares_a[1..], &keystore)?.0);
+
+      let mixed = [shares_a[0].clone(), shares_b[1].clone()];
+      assert!(combine_decrypt(&enc_a, &mixed, &keystore).unwrap_err().to_string().contains("different file"));
+      assert!(combine_decrypt(&enc_a, &shares_b[..2], &keystore).unwrap_err().to_string().contains("different file"));
+      assert!(combine_decrypt(&enc_a, &shares_a[..1], &keystore).unwrap_err().to_string().contains("Need 2"));
+      let twice = [shares_a[0].clone(), shares_a[0].clone()];
+      assert!(combine_decrypt(&enc_a, &twice, &keystore).unwrap_err().to_string().contains("twice"));
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_refresh_rejects_old_epoch() -> anyhow::Result<()> {
+      let mut keystore = test_keystore()?;
+      let (old, enc_data) = split_encrypt(b"hello world", 3, 2, false, keystore.default_master_key())?;
+
+      let (header, enc_id) = (ContainerReader::parse(&enc_data)?.header().clone(), file_id(&enc_data));
+      let new = refresh_shares(&header, &enc_id, &old[1..], 5, 3, true, &keystore)?;
+      assert_eq!((1, 3, 5), (new[0].epoch(), new[0].threshold(), new[0].total()));
+      assert_eq!(old[0].file_id(), new[0].file_id());
+      let decoded = new.iter()
+        .map(|x| Share::decode(&x.encode()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+      assert_eq!(new, decoded);
+      assert!(combine_decrypt(&enc_data, &decoded[..2], &keystore).is_err(), "fewer than the new threshold");
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_data, &decoded[2..], &keystore)?.0);
+
+      let mixed = [old[0].clone(), new[1].clone(), new[2].clone()];
+      assert!(combine_decrypt(&enc_data, &mixed, &keystore).unwrap_err().to_string().contains("epoch"));
+
+      keystore.set_share_epoch(*new[0].file_id(), new[0].epoch());
+      let err = combine_decrypt(&enc_data, &old[..2], &keystore).unwrap_err();
+      assert!(err.to_string().contains("refreshed"), "{err}");
+      assert!(refresh_shares(&header, &enc_id, &old[..2], 3, 2, false, &keystore).is_err());
+      assert_eq!(b"hello world".to_vec(), combine_decrypt(&enc_data, &new[1..4], &keystore)?.0);
+
+      Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_split_key_streamed() -> anyhow::Result<()> {
+      let orig: Vec<u8> = (0..200_000).map(|x| (x % 251) as u8).collect();
+      let keystore = test_keystore()?;
+
+      let sealing_key = SealingKey::shamir_master_key(keystore.default_master_key())?;
+      let mut enc_bytes = Vec::new();
+      sealing_key.seal_stream(&mut orig.as_slice(), &mut enc_bytes).await?;
+      let enc_id = file_id_reader(&mut enc_bytes.as_slice()).await?;
+      assert_eq!(file_id(&enc_bytes), enc_id);
+      let shares = split_key(&sealing_key, 5, 3, true, enc_id)?;
+      assert!(split_key(&SealingKey::enc_data_key(keystore.default_master_key())?, 5, 3, true, enc_id).is_err(),
+        "a key meant for a key file is not split");
+
+      let mut input = enc_bytes.as_slice();
+      let container = ContainerStreamReader::read_header(&mut input).await?;
+      assert!(combine_key(container.header(), &[0; FILE_ID_LEN_BYTES], &shares[..3], &keystore).is_err());
+      let (data_key, bad) = combine_key(container.header(), &enc_id, &shares[1..4], &keystore)?;
+      assert!(bad.is_empty());
+      let mut act = Vec::new();
+      container.open(&data_key, &mut input, &mut act).await?;
+      assert_eq!(orig, act);
+
+      let refreshed = refresh_shares(container.header(), &enc_id, &shares[2..], 3, 2, false, &keystore)?;
+      assert_eq!(data_key, combine_key(container.header(), &enc_id, &refreshed[..2], &keystore)?.0);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_version_2_shares_are_epoch_0() -> anyhow::Result<()> {
+      let shares = Share::split(b"hello world", 3, 2, [9u8; FILE_ID_LEN_BYTES])?;
+      // a version 2 share is a version 4 one without scheme and epoch
+      let bytes = shares[0].encode();
+      let mut old = bytes[..PREFIX_LEN_BYTES].to_vec();
+      old[SHARE_MAGIC.len()] = BYTES_SHARE_VERSION;
+      old.extend(&bytes[PREFIX_LEN_BYTES + 5..bytes.len() - CHECKSUM_LEN_BYTES]);
+      old.extend(checksum(&old));
+
+      let decoded = Share::decode(&old)?;
+      assert_eq!(0, decoded.epoch());
+      assert_eq!(b"hello world".to_vec(), *Share::combine(&[decoded, shares[2].clone()])?);
+
+      Ok(())
+    }
+  }
+
+// #endregion ----------------
@@ -0,0 +1,352 @@
+use aes_gcm::{
+  aead::{rand_core::RngCore, Aead, KeyInit, OsRng, Payload},
+  Aes256Gcm, Key,
+};
+use anyhow::anyhow;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use zeroize::Zeroizing;
+
+use super::{NONCE_LEN_BYTES, TAG_LEN_BYTES};
+
+/// plaintext bytes per segment, only the last segment may be shorter
+pub const SEGMENT_LEN_BYTES: u32 = 64 * 1024;
+/// refuse streams whose segments would need more memory than this
+const MAX_SEGMENT_LEN_BYTES: u32 = 16 * 1024 * 1024;
+/// random part of every segment nonce, the rest is the counter and the last flag
+const NONCE_PREFIX_LEN_BYTES: usize = NONCE_LEN_BYTES - 5;
+/// segment length followed by the nonce prefix
+const PREAMBLE_LEN_BYTES: usize = 4 + NONCE_PREFIX_LEN_BYTES;
+
+/// Chunked aes-256-gcm payload in the style of the STREAM construction
+///
+/// Payload layout (integers are big endian):
+///
+/// ```text
+/// segment len  u32      plaintext bytes per segment
+/// nonce prefix 7 bytes  random
+/// segments     ciphertext || tag for every segment len bytes of plaintext
+/// ```
+///
+/// Segment `i` is sealed with nonce `prefix || i as u32 || last`, where `last` is 1
+/// for the final segment and 0 otherwise, so dropping, reordering or cutting off
+/// segments fails authentication. An empty plaintext is a single empty last segment.
+/// The container header and the preamble are associated data of every segment.
+struct Preamble {
+  segment_len: u32,
+  nonce_prefix: [u8; NONCE_PREFIX_LEN_BYTES],
+}
+
+impl Preamble {
+  fn generate(segment_len: u32) -> Self {
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN_BYTES];
+    OsRng.fill_bytes(&mut nonce_prefix);
+
+    Self { segment_len, nonce_prefix }
+  }
+
+  fn encode(&self) -> [u8; PREAMBLE_LEN_BYTES] {
+    let mut res = [0u8; PREAMBLE_LEN_BYTES];
+    res[..4].copy_from_slice(&self.segment_len.to_be_bytes());
+    res[4..].copy_from_slice(&self.nonce_prefix);
+    res
+  }
+
+  fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+    if bytes.len() < PREAMBLE_LEN_BYTES {
+      return Err(anyhow!("Container payload is truncated"));
+    }
+    let mut segment_len = [0u8; 4];
+    segment_len.copy_from_slice(&bytes[..4]);
+    let segment_len = u32::from_be_bytes(segment_len);
+    if segment_len == 0 || segment_len > MAX_SEGMENT_LEN_BYTES {
+      return Err(anyhow!("Unsupported stream segment length: {segment_len}"));
+    }
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN_BYTES];
+    nonce_prefix.copy_from_slice(&bytes[4..PREAMBLE_LEN_BYTES]);
+
+    Ok(Self { segment_len, nonce_prefix })
+  }
+
+  fn encrypted_segment_len(&self) -> usize {
+    self.segment_len as usize + TAG_LEN_BYTES
+  }
+}
+
+/// Seals or opens consecutive segments, keeping track of the counter
+struct SegmentCipher {
+  cipher: Aes256Gcm,
+  nonce_prefix: [u8; NONCE_PREFIX_LEN_BYTES],
+  aad: Vec<u8>,
+  counter: u32,
+  finished: bool,
+}
+
+impl SegmentCipher {
+  fn new(key: &Key<Aes256Gcm>, header_bytes: &[u8], preamble: &Preamble) -> Self {
+    let mut aad = Vec::with_capacity(header_bytes.len() + PREAMBLE_LEN_BYTES);
+    aad.extend(header_bytes);
+    aad.extend(preamble.encode());
+
+    Self {
+      cipher: Aes256Gcm::new(key),
+      nonce_prefix: preamble.nonce_prefix,
+      aad,
+      counter: 0,
+      finished: false,
+    }
+  }
+
+  fn nonce(&self, last: bool) -> [u8; NONCE_LEN_BYTES] {
+    let mut nonce = [0u8; NONCE_LEN_BYTES];
+    nonce[..NONCE_PREFIX_LEN_BYTES].copy_from_slice(&self.nonce_prefix);
+    nonce[NONCE_PREFIX_LEN_BYTES..NONCE_LEN_BYTES - 1].copy_from_slice(&self.counter.to_be_bytes());
+    nonce[NONCE_LEN_BYTES - 1] = last as u8;
+    nonce
+  }
+
+  fn advance(&mut self, last: bool) -> anyhow::Result<()> {
+    if last {
+      self.finished = true;
+    } else {
+      self.counter = self.counter.checked_add(1)
+        .ok_or_else(|| anyhow!("Stream has too many segments"))?;
+    }
+    Ok(())
+  }
+
+  fn seal(&mut self, plaintext: &[u8], last: bool) -> anyhow::Result<Vec<u8>> {
+    if self.finished {
+      return Err(anyhow!("Stream is already finished"));
+    }
+    let nonce = self.nonce(last);
+    let ciphertext = self.cipher.encrypt(&nonce.into(), Payload { msg: plaintext, aad: &self.aad })
+      .map_err(|e| anyhow!("Unable to encrypt segment {}: {e}", self.counter))?;
+    self.advance(last)?;
+    Ok(ciphertext)
+  }
+
+  fn open(&mut self, ciphertext: &[u8], last: bool) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    if self.finished {
+      return Err(anyhow!("Stream is already finished"));
+    }
+    let nonce = self.nonce(last);
+    let plaintext = self.cipher.decrypt(&nonce.into(), Payload { msg: ciphertext, aad: &self.aad })
+      .map_err(|_| anyhow!("Unable to decrypt segment {}, data is corrupted or truncated", self.counter))?;
+    self.advance(last)?;
+    Ok(Zeroizing::new(plaintext))
+  }
+}
+
+/// Encrypts `plaintext` held in memory, returns the payload
+pub(super) fn seal(key: &Key<Aes256Gcm>, header_bytes: &[u8], plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+  let preamble = Preamble::generate(SEGMENT_LEN_BYTES);
+  let mut cipher = SegmentCipher::new(key, header_bytes, &preamble);
+
+  let mut res = Vec::with_capacity(PREAMBLE_LEN_BYTES + plaintext.len() + TAG_LEN_BYTES);
+  res.extend(preamble.encode());
+  if plaintext.is_empty() {
+    res.extend(cipher.seal(&[], true)?);
+  }
+  let mut segments = plaintext.chunks(preamble.segment_len as usize).peekable();
+  while let Some(segment) = segments.next() {
+    res.extend(cipher.seal(segment, segments.peek().is_none())?);
+  }
+
+  Ok(res)
+}
+
+/// Decrypts a payload held in memory
+pub(super) fn open(key: &Key<Aes256Gcm>, header_bytes: &[u8], payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+  let preamble = Preamble::decode(payload)?;
+  let mut cipher = SegmentCipher::new(key, header_bytes, &preamble);
+  let encrypted_segment_len = preamble.encrypted_segment_len();
+
+  let mut rest = &payload[PREAMBLE_LEN_BYTES..];
+  let mut res = Vec::with_capacity(rest.len());
+  while rest.len() > encrypted_segment_len {
+    let (segment, tail) = rest.split_at(encrypted_segment_len);
+    res.extend(cipher.open(segment, false)?.iter());
+    rest = tail;
+  }
+  res.extend(cipher.open(rest, true)?.iter());
+
+  Ok(res)
+}
+
+/// Encrypts everything `reader` yields into `writer`, one segment in memory at a time
+/// returns the number of plaintext bytes
+pub(super) async fn seal_stream<R, W>(key: &Key<Aes256Gcm>, header_bytes: &[u8], reader: &mut R, writer: &mut W) -> anyhow::Result<u64>
+where
+  R: AsyncRead + Unpin,
+  W: AsyncWrite + Unpin,
+{
+  let preamble = Preamble::generate(SEGMENT_LEN_BYTES);
+  let mut cipher = SegmentCipher::new(key, header_bytes, &preamble);
+  writer.write_all(&preamble.encode()).await?;
+
+  let segment_len = preamble.segment_len as usize;
+  let mut current = Zeroizing::new(vec![0u8; segment_len]);
+  let mut next = Zeroizing::new(vec![0u8; segment_len]);
+  let mut current_len = read_full(reader, &mut current).await?;
+  let mut total = 0u64;
+  loop {
+    // a full segment is only the last one if nothing follows it
+    let next_len = if current_len == segment_len { read_full(reader, &mut next).await? } else { 0 };
+    let last = next_len == 0;
+
+    writer.write_all(&cipher.seal(&current[..current_len], last)?).await?;
+    total += current_len as u64;
+    if last {
+      break;
+    }
+    std::mem::swap(&mut current, &mut next);
+    current_len = next_len;
+  }
+  writer.flush().await?;
+
+  Ok(total)
+}
+
+/// Decrypts a payload from `reader` into `writer`, one segment in memory at a time
+///
+/// Plaintext of the segments before a corrupted one has already been written when
+/// this errors, callers should discard the output.
+/// returns the number of plaintext bytes
+pub(super) async fn open_stream<R, W>(key: &Key<Aes256Gcm>, header_bytes: &[u8], reader: &mut R, writer: &mut W) -> anyhow::Result<u64>
+where
+  R: AsyncRead + Unpin,
+  W: AsyncWrite + Unpin,
+{
+  let mut preamble_bytes = [0u8; PREAMBLE_LEN_BYTES];
+  if read_full(reader, &mut preamble_bytes).await? != PREAMBLE_LEN_BYTES {
+    return Err(anyhow!("Container payload is truncated"));
+  }
+  let preamble = Preamble::decode(&preamble_bytes)?;
+  let mut cipher = SegmentCipher::new(key, header_bytes, &preamble);
+
+  let encrypted_segment_len = preamble.encrypted_segment_len();
+  let mut current = vec![0u8; encrypted_segment_len];
+  let mut next = vec![0u8; encrypted_segment_len];
+  let mut current_len = read_full(reader, &mut current).await?;
+  let mut total = 0u64;
+  loop {
+    let next_len = if current_len == encrypted_segment_len { read_full(reader, &mut next).await? } else { 0 };
+    let last = next_len == 0;
+
+    let plaintext = cipher.open(&current[..current_len], last)?;
+    writer.write_all(&plaintext).await?;
+    total += plaintext.len() as u64;
+    if last {
+      break;
+    }
+    std::mem::swap(&mut current, &mut next);
+    current_len = next_len;
+  }
+  writer.flush().await?;
+
+  Ok(total)
+}
+
+/// Fills `buf` unless the reader runs dry first, returns the number of bytes read
+async fn read_full<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> anyhow::Result<usize> {
+  let mut filled = 0;
+  while filled < buf.len() {
+    let n = reader.read(&mut buf[filled..]).await?;
+    if n == 0 {
+      break;
+    }
+    filled += n;
+  }
+  Ok(filled)
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &[u8] = b"test header";
+
+    fn key() -> Key<Aes256Gcm> {
+      Aes256Gcm::generate_key(OsRng)
+    }
+
+    #[test]
+    fn test_roundtrip_segment_boundaries() -> anyhow::Result<()> {
+      let key = key();
+      let seg = SEGMENT_LEN_BYTES as usize;
+      for len in [0, 1, seg - 1, seg, seg + 1, 3 * seg] {
+        let plaintext: Vec<u8> = (0..len).map(|x| x as u8).collect();
+        let payload = seal(&key, HEADER, &plaintext)?;
+        let segments = len.div_ceil(seg).max(1);
+        assert_eq!(PREAMBLE_LEN_BYTES + len + segments * TAG_LEN_BYTES, payload.len(), "length {len}");
+        assert_eq!(plaintext, open(&key, HEADER, &payload)?, "length {len}");
+      }
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_detects_truncation() -> anyhow::Result<()> {
+      let key = key();
+      let seg = SEGMENT_LEN_BYTES as usize;
+      let payload = seal(&key, HEADER, &vec![7u8; 2 * seg])?;
+
+      // cut off exactly at a segment boundary, the remaining segment is not flagged last
+      let truncated = &payload[..PREAMBLE_LEN_BYTES + seg + TAG_LEN_BYTES];
+      assert!(open(&key, HEADER, truncated).is_err(), "dropped last segment must be detected");
+      assert!(open(&key, HEADER, &payload[..payload.len() - 1]).is_err());
+
+      let mut extended = payload.clone();
+      extended.extend([0u8; TAG_LEN_BYTES]);
+      assert!(open(&key, HEADER, &extended).is_err(), "appended data must be detected");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_detects_reordering_and_other_header() -> anyhow::Result<()> {
+      let key = key();
+      let seg = SEGMENT_LEN_BYTES as usize;
+      let mut plaintext = vec![1u8; seg];
+      plaintext.extend(vec![2u8; seg]);
+      plaintext.push(3);
+      let payload = seal(&key, HEADER, &plaintext)?;
+
+      let enc_seg = seg + TAG_LEN_BYTES;
+      let mut swapped = payload[..PREAMBLE_LEN_BYTES].to_vec();
+      swapped.extend(&payload[PREAMBLE_LEN_BYTES + enc_seg..PREAMBLE_LEN_BYTES + 2 * enc_seg]);
+      swapped.extend(&payload[PREAMBLE_LEN_BYTES..PREAMBLE_LEN_BYTES + enc_seg]);
+      swapped.extend(&payload[PREAMBLE_LEN_BYTES + 2 * enc_seg..]);
+      assert!(open(&key, HEADER, &swapped).is_err(), "swapped segments must be detected");
+
+      assert!(open(&key, b"other header", &payload).is_err(), "header is associated data");
+
+      Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_matches_in_memory() -> anyhow::Result<()> {
+      let key = key();
+      let plaintext: Vec<u8> = (0..(2 * SEGMENT_LEN_BYTES as usize + 100)).map(|x| (x % 251) as u8).collect();
+
+      let mut payload = Vec::new();
+      let written = seal_stream(&key, HEADER, &mut plaintext.as_slice(), &mut payload).await?;
+      assert_eq!(plaintext.len() as u64, written);
+      assert_eq!(plaintext, open(&key, HEADER, &payload)?, "streamed payload opens in memory");
+
+      let payload = seal(&key, HEADER, &plaintext)?;
+      let mut act = Vec::new();
+      open_stream(&key, HEADER, &mut payload.as_slice(), &mut act).await?;
+      assert_eq!(plaintext, act, "in memory payload opens streamed");
+
+      let mut act = Vec::new();
+      let res = open_stream(&key, HEADER, &mut &payload[..payload.len() - TAG_LEN_BYTES - 100], &mut act).await;
+      assert!(res.is_err(), "truncated stream must be rejected");
+
+      Ok(())
+    }
+  }
+
+// #endregion ----------------
@@ -0,0 +1,248 @@
+use std::fmt;
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use anyhow::anyhow;
+use zeroize::Zeroizing;
+
+use super::{
+  container::Parser, key_id, share::{checksum, CHECKSUM_LEN_BYTES, FILE_ID_LEN_BYTES}, unwrap_data_key, unwrap_data_key_with, wrap_data_key,
+  wrap_data_key_with, FileId, Keystore, MasterKey, Share, ShareId, AES_256_LEN_BYTES,
+};
+
+/// Every vault file starts with these bytes
+pub const VAULT_MAGIC: [u8; 4] = *b"ENVK";
+/// Current vault format version
+const VAULT_VERSION: u8 = 1;
+
+/// Key the data keys of every file in a project vault are wrapped with
+///
+/// It is split into shares once, when the vault is made. A quorum of them unlocks it for the session,
+/// after that every file in the vault opens without asking for shares again.
+#[derive(Clone)]
+pub struct VaultKey(Zeroizing<[u8; AES_256_LEN_BYTES]>);
+
+impl VaultKey {
+  pub fn generate() -> Self {
+    let mut key = Zeroizing::new([0u8; AES_256_LEN_BYTES]);
+    OsRng.fill_bytes(key.as_mut_slice());
+    Self(key)
+  }
+
+  /// Fingerprint recorded in the vault file and in the stanzas of files in the vault, see `key_id`
+  pub fn id(&self) -> Vec<u8> {
+    key_id(self.0.as_slice())
+  }
+
+  /// Wraps the data key of a file added to the vault
+  pub(super) fn wrap(&self, data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+    Ok(wrap_data_key_with(&self.0, data_key, &self.id())?.0)
+  }
+
+  pub(super) fn unwrap(&self, wrapped_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+    unwrap_data_key_with(&self.0, wrapped_key, &self.id())
+  }
+}
+
+impl fmt::Debug for VaultKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "VaultKey({})", hex::encode(self.id()))
+  }
+}
+
+/// What is known about a vault without its key, kept in the vault directory next to the files
+///
+/// The shares hold the vault key wrapped with a master key, like those of a single file, so unlocking
+/// takes both the quorum and the keystore the vault was made with. Layout:
+///
+/// ```text
+/// magic         4 bytes  "ENVK"
+/// version       1 byte
+/// vault id      16 bytes recorded in every share as its file id
+/// key id        16 bytes see `VaultKey::id`
+/// master key    id len u8 | id of the master key the shared key is wrapped with
+/// checksum      4 bytes  sha256 of everything before, truncated
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaultHeader {
+  pub vault_id: FileId,
+  pub key_id: Vec<u8>,
+  pub master_key_id: Vec<u8>,
+}
+
+impl VaultHeader {
+  pub fn encode(&self) -> Vec<u8> {
+    let mut res = Vec::new();
+    res.extend(VAULT_MAGIC);
+    res.push(VAULT_VERSION);
+    res.extend(self.vault_id);
+    res.extend(&self.key_id);
+    res.push(self.master_key_id.len() as u8);
+    res.extend(&self.master_key_id);
+    let checksum = checksum(&res);
+    res.extend(checksum);
+    res
+  }
+
+  pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+    if !bytes.starts_with(&VAULT_MAGIC) {
+      return Err(anyhow!("Not a vault (bad magic)"));
+    }
+    let (body, expected) = bytes.split_at(bytes.len().checked_sub(CHECKSUM_LEN_BYTES)
+      .filter(|x| *x > VAULT_MAGIC.len())
+      .ok_or_else(|| anyhow!("Vault file is truncated"))?);
+    if checksum(body) != expected {
+      return Err(anyhow!("Vault file is corrupted (checksum mismatch)"));
+    }
+
+    let mut parser = Parser::new(&body[VAULT_MAGIC.len()..]);
+    let version = parser.u8()?;
+    if version != VAULT_VERSION {
+      return Err(anyhow!("Unsupported vault version: {version}"));
+    }
+    let vault_id = parser.take(FILE_ID_LEN_BYTES)?.try_into()?;
+    let key_id = parser.take(FILE_ID_LEN_BYTES)?.to_vec();
+    let len = parser.u8()? as usize;
+    let master_key_id = parser.take(len)?.to_vec();
+    if !parser.rest().is_empty() {
+      return Err(anyhow!("Trailing bytes after vault header"));
+    }
+    Ok(Self { vault_id, key_id, master_key_id })
+  }
+}
+
+/// Makes a vault with a fresh key and splits the key wrapped with `master_key` into `n_shares` shares,
+/// verifiable ones carry pedersen commitments
+pub fn create_vault(n_shares: u16, k_thres: u16, verifiable: bool, master_key: &MasterKey) -> anyhow::Result<(VaultHeader, VaultKey, Vec<Share>)> {
+  let (header, vault_key, wrapped_key) = new_vault(master_key)?;
+  let shares = if verifiable {
+    Share::split_verifiable(&wrapped_key, n_shares, k_thres, header.vault_id)?
+  } else {
+    Share::split(&wrapped_key, n_shares, k_thres, header.vault_id)?
+  };
+  Ok((header, vault_key, shares))
+}
+
+/// Like `create_vault`, but splits the wrapped key across groups, see `Share::split_groups`
+pub fn create_vault_groups(groups: &[(u16, u16)], k_groups: u16, master_key: &MasterKey) -> anyhow::Result<(VaultHeader, VaultKey, Vec<Share>)> {
+  let (header, vault_key, wrapped_key) = new_vault(master_key)?;
+  let shares = Share::split_groups(&wrapped_key, groups, k_groups, header.vault_id)?;
+  Ok((header, vault_key, shares))
+}
+
+fn new_vault(master_key: &MasterKey) -> anyhow::Result<(VaultHeader, VaultKey, Zeroizing<Vec<u8>>)> {
+  let vault_key = VaultKey::generate();
+  let (wrapped_key, _, _, _) = wrap_data_key(master_key, vault_key.0.as_slice())?;
+  let mut vault_id = FileId::default();
+  OsRng.fill_bytes(&mut vault_id);
+  let header = VaultHeader { vault_id, key_id: vault_key.id(), master_key_id: master_key.id().to_vec() };
+  Ok((header, vault_key, Zeroizing::new(wrapped_key)))
+}
+
+/// Recovers the vault key from `shares`, unless they belong to another vault or were refreshed since
+///
+/// Also returns the bad shares that were left out, see `Share::combine_robust`.
+pub fn unlock_vault(header: &VaultHeader, shares: &[Share], keystore: &Keystore) -> anyhow::Result<(VaultKey, Vec<ShareId>)> {
+  if let Some(first) = shares.first() {
+    if *first.file_id() != header.vault_id {
+      return Err(anyhow!("Shares were split for a different file or vault"));
+    }
+    let epoch = keystore.share_epoch(&header.vault_id);
+    if first.epoch() < epoch {
+      return Err(anyhow!("Shares are from epoch {}, they were refreshed to epoch {epoch} and no longer unlock the vault", first.epoch()));
+    }
+  }
+  let (wrapped_key, bad) = Share::combine_robust(shares)?;
+  let master_key = keystore.master_key(&header.master_key_id)
+    .ok_or_else(|| anyhow!("Master key {} is not in the keystore", hex::encode(&header.master_key_id)))?;
+  let key = Zeroizing::new(unwrap_data_key(master_key, &wrapped_key)
+    .map_err(|_| anyhow!("The shares don't give the vault key"))?);
+  let key: [u8; AES_256_LEN_BYTES] = key.as_slice().try_into()
+    .map_err(|_| anyhow!("The shares don't give the vault key"))?;
+  let vault_key = VaultKey(Zeroizing::new(key));
+  if vault_key.id() != header.key_id {
+    return Err(anyhow!("The shares give a different key than the vault's"));
+  }
+  Ok((vault_key, bad))
+}
+
+/// Splits the vault key again into `n_shares` shares of the next epoch, the files in the vault stay as they are
+///
+/// The caller records the new epoch in the keystore, from then on the old shares no longer unlock the vault.
+/// As with `refresh_shares` that only holds for this keystore, see `Keystore::share_epoch`.
+pub fn refresh_vault_shares(header: &VaultHeader, shares: &[Share], n_shares: u16, k_thres: u16, verifiable: bool, keystore: &Keystore) -> anyhow::Result<Vec<Share>> {
+  // a wrong secret from bad unverifiable shares must not be handed out again
+  let (_, bad) = unlock_vault(header, shares, keystore)?;
+  let good: Vec<Share> = shares.iter().filter(|x| !bad.contains(&x.id())).cloned().collect();
+  Share::refresh(&good, n_shares, k_thres, verifiable)
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{passphrase::{Passphrase, TEST_KDF_COST}, Recipient, SealingKey, ContainerReader};
+
+    fn test_keystore() -> anyhow::Result<Keystore> {
+      Keystore::create(&Passphrase::new("test keystore".to_string()), TEST_KDF_COST)
+    }
+
+    #[test]
+    fn test_vault_unlock_opens_every_file() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (header, vault_key, shares) = create_vault(5, 3, true, keystore.default_master_key())?;
+      let header = VaultHeader::decode(&header.encode())?;
+
+      let files = [b"first file".to_vec(), b"second file".to_vec()].map(|data| {
+        SealingKey::envelope(&[Recipient::VaultKey(vault_key.clone())]).and_then(|x| x.seal(&data))
+      });
+      let (unlocked, bad) = unlock_vault(&header, &shares[1..4], &keystore)?;
+      assert!(bad.is_empty());
+      assert_eq!(vault_key.id(), unlocked.id());
+      for (file, data) in files.into_iter().zip([b"first file".as_slice(), b"second file"]) {
+        let file = file?;
+        let reader = ContainerReader::parse(&file)?;
+        let envelope = reader.envelope().expect("envelope");
+        let data_key = envelope.unwrap_with_vault_key(reader.header_bytes(), &unlocked)?;
+        assert_eq!(data, reader.open(&data_key)?);
+        // the keystore alone doesn't open files in a vault
+        assert!(envelope.unwrap(reader.header_bytes(), Some(&keystore), None).is_err());
+      }
+
+      assert!(unlock_vault(&header, &shares[..2], &keystore).is_err());
+      let other = test_keystore()?;
+      assert!(unlock_vault(&header, &shares[..3], &other).is_err(), "the shares are wrapped with the vault owner's master key");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_vault_refresh() -> anyhow::Result<()> {
+      let mut keystore = test_keystore()?;
+      let (header, vault_key, shares) = create_vault(3, 2, true, keystore.default_master_key())?;
+      let fresh = refresh_vault_shares(&header, &shares[1..], 4, 3, false, &keystore)?;
+      keystore.set_share_epoch(header.vault_id, fresh[0].epoch());
+
+      assert_eq!(vault_key.id(), unlock_vault(&header, &fresh[1..], &keystore)?.0.id());
+      let err = unlock_vault(&header, &shares, &keystore).unwrap_err();
+      assert!(err.to_string().contains("epoch"), "{err}");
+
+      let (header, _, shares) = create_vault_groups(&[(3, 2), (2, 1)], 2, keystore.default_master_key())?;
+      let err = refresh_vault_shares(&header, &shares, 3, 2, false, &keystore).unwrap_err();
+      assert!(err.to_string().contains("across groups"), "{err}");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_vault_header_detects_corruption() -> anyhow::Result<()> {
+      let keystore = test_keystore()?;
+      let (header, _, _) = create_vault(3, 2, false, keystore.default_master_key())?;
+      let mut bytes = header.encode();
+      bytes[10] ^= 0x01;
+      assert!(VaultHeader::decode(&bytes).unwrap_err().to_string().contains("checksum"));
+      assert!(VaultHeader::decode(&bytes[..8]).is_err());
+
+      Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+use aes_gcm::aead::OsRng;
+use anyhow::anyhow;
+use curve25519_dalek::{
+  constants::RISTRETTO_BASEPOINT_POINT, ristretto::CompressedRistretto, RistrettoPoint, Scalar,
+};
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use super::container::Parser;
+
+/// Secret bytes per scalar, 31 bytes always stay below the group order
+const CHUNK_LEN_BYTES: usize = 31;
+/// Length of an encoded scalar or compressed point
+pub const ELEMENT_LEN_BYTES: usize = 32;
+/// Second generator for pedersen commitments, nobody knows its discrete log relative to the base point
+const H_GENERATOR_CONTEXT: &[u8] = b"encryption-app pedersen generator h v1";
+
+/// Pedersen commitments to the polynomials a secret was split with, the same for every share of a split
+///
+/// Every 31 byte chunk of the secret gets its own pair of polynomials f (secret) and g (blinding),
+/// `points[chunk * k + j]` commits to their j-th coefficients as `f_j * G + g_j * H`.
+/// Unlike Feldman commitments they reveal nothing about the secret, even a guessable one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commitments {
+  secret_len: u32,
+  threshold: u16,
+  points: Vec<CompressedRistretto>,
+}
+
+/// One share of a secret split with pedersen verifiable secret sharing
+#[derive(Clone, PartialEq, Eq)]
+pub struct VerifiableShare {
+  pub x: u16,
+  /// f(x) for every chunk
+  values: Vec<Scalar>,
+  /// g(x) for every chunk
+  blinding: Vec<Scalar>,
+  pub commitments: Commitments,
+}
+
+impl Zeroize for VerifiableShare {
+  fn zeroize(&mut self) {
+    self.values.zeroize();
+    self.blinding.zeroize();
+  }
+}
+
+fn h_generator() -> RistrettoPoint {
+  let digest: [u8; 64] = Sha512::digest(H_GENERATOR_CONTEXT).into();
+  RistrettoPoint::from_uniform_bytes(&digest)
+}
+
+fn chunk_count(secret_len: usize) -> usize {
+  secret_len.div_ceil(CHUNK_LEN_BYTES)
+}
+
+/// Evaluates the polynomial with coefficients `poly` (lowest degree first) at `x`
+fn eval(poly: &[Scalar], x: &Scalar) -> Scalar {
+  poly.iter().rev().fold(Scalar::ZERO, |acc, c| acc * x + c)
+}
+
+/// Splits `secret` into `n_shares` shares any `k_thres` of which recover it, each checkable against the commitments
+pub fn split(secret: &[u8], n_shares: u16, k_thres: u16) -> anyhow::Result<Vec<VerifiableShare>> {
+  if secret.is_empty() {
+    return Err(anyhow!("Nothing to split, the secret is empty"));
+  }
+  if k_thres < 2 || k_thres > n_shares {
+    return Err(anyhow!("threshold (k) must be between 2 and the number of shares (n): {k_thres} of {n_shares}"));
+  }
+  let secret_len = u32::try_from(secret.len()).map_err(|_| anyhow!("Secret is too long to split"))?;
+  let h = h_generator();
+
+  let mut polys: Vec<(Vec<Scalar>, Vec<Scalar>)> = Vec::with_capacity(chunk_count(secret.len()));
+  let mut points = Vec::with_capacity(chunk_count(secret.len()) * k_thres as usize);
+  for chunk in secret.chunks(CHUNK_LEN_BYTES) {
+    let mut bytes = Zeroizing::new([0u8; ELEMENT_LEN_BYTES]);
+    bytes[..chunk.len()].copy_from_slice(chunk);
+    let mut f = vec![Scalar::from_bytes_mod_order(*bytes)];
+    f.extend((1..k_thres).map(|_| Scalar::random(&mut OsRng)));
+    let g: Vec<Scalar> = (0..k_thres).map(|_| Scalar::random(&mut OsRng)).collect();
+    points.extend(f.iter().zip(&g).map(|(a, b)| (a * RISTRETTO_BASEPOINT_POINT + b * h).compress()));
+    polys.push((f, g));
+  }
+  let commitments = Commitments { secret_len, threshold: k_thres, points };
+
+  let shares = (1..=n_shares).map(|i| {
+    let x = Scalar::from(i as u64);
+    VerifiableShare {
+      x: i,
+      values: polys.iter().map(|(f, _)| eval(f, &x)).collect(),
+      blinding: polys.iter().map(|(_, g)| eval(g, &x)).collect(),
+      commitments: commitments.clone(),
+    }
+  }).collect();
+  for (f, g) in polys.iter_mut() {
+    f.zeroize();
+    g.zeroize();
+  }
+
+  Ok(shares)
+}
+
+/// Puts a secret back together, checking every share against its commitments first
+pub fn combine(shares: &[VerifiableShare]) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+  let first = shares.first().ok_or_else(|| anyhow!("No shares to combine"))?;
+  for (i, share) in shares.iter().enumerate() {
+    if share.commitments != first.commitments {
+      return Err(anyhow!("Share #{} was committed to differently than share #{}", share.x, first.x));
+    }
+    if shares[..i].iter().any(|x| x.x == share.x) {
+      return Err(anyhow!("Share #{} is there twice", share.x));
+    }
+    share.verify()?;
+  }
+  if shares.len() < first.commitments.threshold as usize {
+    return Err(anyhow!("Need {} shares to recover the secret, only have {}", first.commitments.threshold, shares.len()));
+  }
+
+  // lagrange basis polynomials evaluated at 0
+  let xs: Vec<Scalar> = shares.iter().map(|x| Scalar::from(x.x as u64)).collect();
+  let basis: Vec<Scalar> = xs.iter().enumerate().map(|(i, xi)| {
+    let (num, den) = xs.iter().enumerate()
+      .filter(|(j, _)| *j != i)
+      .fold((Scalar::ONE, Scalar::ONE), |(num, den), (_, xj)| (num * xj, den * (xj - xi)));
+    num * den.invert()
+  }).collect();
+
+  let secret_len = first.commitments.secret_len as usize;
+  let mut secret = Zeroizing::new(Vec::with_capacity(chunk_count(secret_len) * CHUNK_LEN_BYTES));
+  for chunk in 0..chunk_count(secret_len) {
+    let value: Scalar = shares.iter().zip(&basis).map(|(share, l)| share.values[chunk] * l).sum();
+    let bytes = Zeroizing::new(value.to_bytes());
+    if bytes[CHUNK_LEN_BYTES..].iter().any(|x| *x != 0) {
+      return Err(anyhow!("Recovered secret is not what was split"));
+    }
+    secret.extend(&bytes[..CHUNK_LEN_BYTES]);
+  }
+  secret.truncate(secret_len);
+
+  Ok(secret)
+}
+
+impl Commitments {
+  fn chunks(&self) -> usize {
+    chunk_count(self.secret_len as usize)
+  }
+
+  /// Short fingerprint, holders compare it to make sure the dealer published the same commitments to everyone
+  pub fn fingerprint(&self) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(self.secret_len.to_be_bytes());
+    hasher.update(self.threshold.to_be_bytes());
+    for point in &self.points {
+      hasher.update(point.as_bytes());
+    }
+    hex::encode(&hasher.finalize()[..8])
+  }
+
+  pub fn encode_into(&self, res: &mut Vec<u8>) {
+    res.extend(self.secret_len.to_be_bytes());
+    for point in &self.points {
+      res.extend(point.as_bytes());
+    }
+  }
+
+  pub fn decode_from(threshold: u16, parser: &mut Parser<'_>) -> anyhow::Result<Self> {
+    let secret_len = parser.u32()?;
+    if secret_len == 0 {
+      return Err(anyhow!("Commitments are for an empty secret"));
+    }
+    let count = chunk_count(secret_len as usize) * threshold as usize;
+    let points = (0..count)
+      .map(|_| CompressedRistretto::from_slice(parser.take(ELEMENT_LEN_BYTES)?)
+        .map_err(|e| anyhow!("Malformed commitment: {e}")))
+      .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(Self { secret_len, threshold, points })
+  }
+}
+
+impl VerifiableShare {
+  /// Errors unless this share lies on the polynomials the commitments were made to
+  pub fn verify(&self) -> anyhow::Result<()> {
+    let commitments = &self.commitments;
+    let k = commitments.threshold as usize;
+    if self.x == 0 || self.values.len() != commitments.chunks() || self.blinding.len() != commitments.chunks() {
+      return Err(anyhow!("Share #{} does not fit its commitments", self.x));
+    }
+    let x = Scalar::from(self.x as u64);
+    let h = h_generator();
+    for chunk in 0..commitments.chunks() {
+      let committed = commitments.points[chunk * k..(chunk + 1) * k].iter()
+        .map(|x| x.decompress().ok_or_else(|| anyhow!("Commitment is not a valid point")))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+      // sum of C_j * x^j, horner from the highest degree down
+      let expected = committed.iter().rev().fold(RistrettoPoint::default(), |acc, c| acc * x + c);
+      if self.values[chunk] * RISTRETTO_BASEPOINT_POINT + self.blinding[chunk] * h != expected {
+        return Err(anyhow!("Share #{} does not match the published commitments", self.x));
+      }
+    }
+    Ok(())
+  }
+
+  pub fn encode_into(&self, res: &mut Vec<u8>) {
+    self.commitments.encode_into(res);
+    for (value, blinding) in self.values.iter().zip(&self.blinding) {
+      res.extend(value.as_bytes());
+      res.extend(blinding.as_bytes());
+    }
+  }
+
+  pub fn decode_from(x: u16, threshold: u16, parser: &mut Parser<'_>) -> anyhow::Result<Self> {
+    let commitments = Commitments::decode_from(threshold, parser)?;
+    let mut values = Vec::with_capacity(commitments.chunks());
+    let mut blinding = Vec::with_capacity(commitments.chunks());
+    for _ in 0..commitments.chunks() {
+      values.push(decode_scalar(parser.take(ELEMENT_LEN_BYTES)?)?);
+      blinding.push(decode_scalar(parser.take(ELEMENT_LEN_BYTES)?)?);
+    }
+    Ok(Self { x, values, blinding, commitments })
+  }
+}
+
+fn decode_scalar(bytes: &[u8]) -> anyhow::Result<Scalar> {
+  let bytes: [u8; ELEMENT_LEN_BYTES] = bytes.try_into()?;
+  Option::from(Scalar::from_canonical_bytes(bytes))
+    .ok_or_else(|| anyhow!("Share value is not a canonical scalar"))
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_verify_combine() -> anyhow::Result<()> {
+      for len in [1, 31, 32, 60, 100] {
+        let secret: Vec<u8> = (0..len).map(|x| (x * 13 + 255) as u8).collect();
+        let shares = split(&secret, 5, 3)?;
+        for share in &shares {
+          share.verify()?;
+        }
+
+        assert_eq!(secret, *combine(&shares[2..])?);
+        assert_eq!(secret, *combine(&[shares[4].clone(), shares[0].clone(), shares[1].clone()])?);
+        assert!(combine(&shares[..2]).is_err(), "fewer than threshold");
+      }
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_bogus_share_is_caught() -> anyhow::Result<()> {
+      let shares = split(b"a 60 byte wrapped data key, or any other secret worth sharing", 4, 2)?;
+
+      let mut bogus = shares[1].clone();
+      bogus.values[1] += Scalar::ONE;
+      assert!(bogus.verify().is_err());
+      assert!(combine(&[shares[0].clone(), bogus]).unwrap_err().to_string().contains("commitments"));
+
+      let mut other_dealer = split(b"a 60 byte wrapped data key, or any other secret worth sharing", 4, 2)?;
+      other_dealer[1].commitments = shares[0].commitments.clone();
+      assert!(other_dealer[1].verify().is_err(), "shares of another split fail these commitments");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_encode_roundtrip() -> anyhow::Result<()> {
+      let shares = split(b"hello world", 3, 2)?;
+      let mut bytes = Vec::new();
+      shares[2].encode_into(&mut bytes);
+
+      let decoded = VerifiableShare::decode_from(3, 2, &mut Parser::new(&bytes))?;
+      assert!(decoded == shares[2]);
+      assert_eq!(shares[0].commitments.fingerprint(), decoded.commitments.fingerprint());
+
+      Ok(())
+    }
+  }
+
+// #endregion ----------------
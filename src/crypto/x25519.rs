@@ -0,0 +1,197 @@
+use std::{fmt, str::FromStr};
+
+use aes_gcm::aead::OsRng;
+use anyhow::anyhow;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use super::{key_id, unwrap_data_key_with, wrap_data_key_with, AES_256_LEN_BYTES};
+
+/// x25519 public keys and secrets are 32 bytes
+pub const X25519_KEY_LEN_BYTES: usize = 32;
+/// text form of a public key is this prefix followed by the key in hex
+const RECIPIENT_PREFIX: &str = "x25519:";
+/// hkdf info, binds derived keys to this use
+const WRAP_KDF_INFO: &[u8] = b"encryption-app x25519 data key wrap v1";
+
+/// Public half of an x25519 keypair, safe to hand out to anyone who wants to encrypt for us
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct X25519Recipient(PublicKey);
+
+/// Secret half of an x25519 keypair, kept in the keystore
+#[derive(Clone)]
+pub struct X25519Identity(StaticSecret);
+
+impl X25519Recipient {
+  pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+    let bytes: [u8; X25519_KEY_LEN_BYTES] = bytes.try_into()
+      .map_err(|_| anyhow!("Public key must be {X25519_KEY_LEN_BYTES} bytes, got {}", bytes.len()))?;
+    Ok(Self(PublicKey::from(bytes)))
+  }
+
+  pub fn as_bytes(&self) -> &[u8; X25519_KEY_LEN_BYTES] {
+    self.0.as_bytes()
+  }
+
+  /// Fingerprint recorded in container headers, see `key_id`
+  pub fn id(&self) -> Vec<u8> {
+    key_id(self.as_bytes())
+  }
+}
+
+impl fmt::Display for X25519Recipient {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{RECIPIENT_PREFIX}{}", hex::encode(self.as_bytes()))
+  }
+}
+
+impl fmt::Debug for X25519Recipient {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "X25519Recipient({self})")
+  }
+}
+
+impl FromStr for X25519Recipient {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> anyhow::Result<Self> {
+    let hex_key = s.trim().strip_prefix(RECIPIENT_PREFIX)
+      .ok_or_else(|| anyhow!("Public key must start with {RECIPIENT_PREFIX}"))?;
+    let bytes = hex::decode(hex_key)
+      .map_err(|e| anyhow!("Public key is not valid hex: {e}"))?;
+    Self::from_bytes(&bytes)
+  }
+}
+
+impl X25519Identity {
+  pub fn generate() -> Self {
+    Self(StaticSecret::random_from_rng(OsRng))
+  }
+
+  pub(super) fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+    let bytes: Zeroizing<[u8; X25519_KEY_LEN_BYTES]> = Zeroizing::new(bytes.try_into()
+      .map_err(|_| anyhow!("Secret key must be {X25519_KEY_LEN_BYTES} bytes, got {}", bytes.len()))?);
+    Ok(Self(StaticSecret::from(*bytes)))
+  }
+
+  pub(super) fn to_bytes(&self) -> Zeroizing<[u8; X25519_KEY_LEN_BYTES]> {
+    Zeroizing::new(self.0.to_bytes())
+  }
+
+  pub fn recipient(&self) -> X25519Recipient {
+    X25519Recipient(PublicKey::from(&self.0))
+  }
+}
+
+impl fmt::Debug for X25519Identity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "X25519Identity({})", self.recipient())
+  }
+}
+
+/// Wraps `data_key` so only the holder of `recipient`'s identity can unwrap it
+///
+/// ECIES style: a fresh ephemeral keypair is agreed with the recipient key, the shared
+/// secret goes through HKDF-SHA256 (salt is ephemeral public || recipient public) and
+/// the result wraps the data key with aes-256-gcm.
+/// Layout: ephemeral public key (32 bytes) || nonce || ciphertext || tag
+pub fn wrap_data_key(recipient: &X25519Recipient, data_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+  let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+  let ephemeral_public = PublicKey::from(&ephemeral);
+  let shared = ephemeral.diffie_hellman(&recipient.0);
+  if !shared.was_contributory() {
+    return Err(anyhow!("Public key {recipient} is not usable"));
+  }
+  let kek = derive_kek(shared.as_bytes(), &ephemeral_public, recipient)?;
+
+  let (wrapped, _, _, _) = wrap_data_key_with(&kek, data_key, recipient.as_bytes())?;
+  let mut res = Vec::with_capacity(X25519_KEY_LEN_BYTES + wrapped.len());
+  res.extend(ephemeral_public.as_bytes());
+  res.extend(wrapped);
+  Ok(res)
+}
+
+/// Reverses `wrap_data_key` with the recipient's secret key
+pub fn unwrap_data_key(identity: &X25519Identity, wrapped_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+  if wrapped_key.len() < X25519_KEY_LEN_BYTES {
+    return Err(anyhow!("Wrapped key is truncated"));
+  }
+  let (ephemeral_public, wrapped) = wrapped_key.split_at(X25519_KEY_LEN_BYTES);
+  let ephemeral_public = X25519Recipient::from_bytes(ephemeral_public)?.0;
+  let recipient = identity.recipient();
+
+  let shared = identity.0.diffie_hellman(&ephemeral_public);
+  if !shared.was_contributory() {
+    return Err(anyhow!("Wrapped key has an unusable ephemeral key"));
+  }
+  let kek = derive_kek(shared.as_bytes(), &ephemeral_public, &recipient)?;
+
+  unwrap_data_key_with(&kek, wrapped, recipient.as_bytes())
+    .map_err(|_| anyhow!("Data key was not wrapped for public key {recipient}"))
+}
+
+fn derive_kek(shared: &[u8], ephemeral_public: &PublicKey, recipient: &X25519Recipient) -> anyhow::Result<Zeroizing<[u8; AES_256_LEN_BYTES]>> {
+  let mut salt = [0u8; 2 * X25519_KEY_LEN_BYTES];
+  salt[..X25519_KEY_LEN_BYTES].copy_from_slice(ephemeral_public.as_bytes());
+  salt[X25519_KEY_LEN_BYTES..].copy_from_slice(recipient.as_bytes());
+
+  let mut kek = Zeroizing::new([0u8; AES_256_LEN_BYTES]);
+  Hkdf::<Sha256>::new(Some(&salt), shared)
+    .expand(WRAP_KDF_INFO, kek.as_mut_slice())
+    .map_err(|e| anyhow!("Unable to derive key wrapping key: {e}"))?;
+  Ok(kek)
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_roundtrip() -> anyhow::Result<()> {
+      let identity = X25519Identity::generate();
+      let data_key = [9u8; AES_256_LEN_BYTES];
+
+      let wrapped = wrap_data_key(&identity.recipient(), &data_key)?;
+      assert_eq!(data_key.to_vec(), unwrap_data_key(&identity, &wrapped)?);
+
+      let other = X25519Identity::generate();
+      assert!(unwrap_data_key(&other, &wrapped).is_err(), "only the recipient can unwrap");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_wrapping_is_randomized() -> anyhow::Result<()> {
+      let recipient = X25519Identity::generate().recipient();
+      let data_key = [9u8; AES_256_LEN_BYTES];
+
+      assert_ne!(wrap_data_key(&recipient, &data_key)?, wrap_data_key(&recipient, &data_key)?);
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_recipient_text_roundtrip() -> anyhow::Result<()> {
+      let recipient = X25519Identity::generate().recipient();
+      let text = recipient.to_string();
+      assert!(text.starts_with(RECIPIENT_PREFIX));
+      assert_eq!(recipient, text.parse()?);
+
+      assert!("x25519:abcd".parse::<X25519Recipient>().is_err());
+      assert!(hex::encode(recipient.as_bytes()).parse::<X25519Recipient>().is_err(), "prefix is required");
+
+      Ok(())
+    }
+
+    #[test]
+    fn test_rejects_low_order_public_key() {
+      let zero = X25519Recipient::from_bytes(&[0u8; X25519_KEY_LEN_BYTES]).expect("32 bytes");
+      assert!(wrap_data_key(&zero, &[9u8; AES_256_LEN_BYTES]).is_err());
+    }
+  }
+
+// #endregion ----------------
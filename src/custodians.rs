@@ -0,0 +1,246 @@
+use anyhow::anyhow;
+
+use crate::crypto::{FileId, ShareId, X25519Recipient};
+use crate::shares::{parse_holder, Holder};
+
+/// First line of every manifest
+const MANIFEST_HEADER: &str = "# encryption-app custodian manifest, who holds which share. It holds no secrets";
+/// Public key field of a custodian whose shares are protected with their passphrase
+const PASSPHRASE_FIELD: &str = "passphrase";
+
+/// Parses a custodian roster, one custodian per line as `name | contact note | public key`
+///
+/// Contact and public key are optional, `name:2` gives a custodian two shares like in the holders list.
+/// Blank lines and lines starting with `#` are skipped.
+pub fn parse_roster(roster: &str) -> anyhow::Result<Vec<Holder>> {
+    let mut res = Vec::new();
+    for (number, line) in roster.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split('|').map(str::trim);
+        let holder = parse_holder(fields.next().unwrap_or_default())
+            .map_err(|e| anyhow!("line {}: {e}", number + 1))?;
+        let contact = fields.next().unwrap_or_default().to_string();
+        let public_key = match fields.next().filter(|x| !x.is_empty()) {
+            Some(key) => Some(key.parse::<X25519Recipient>().map_err(|e| anyhow!("line {}: {e}", number + 1))?),
+            None => None,
+        };
+        if fields.next().is_some() {
+            return Err(anyhow!("line {}: expected `name | contact note | public key`", number + 1));
+        }
+        if holder.name.is_empty() {
+            return Err(anyhow!("line {}: custodian has no name", number + 1));
+        }
+        res.push(Holder { contact, public_key, ..holder });
+    }
+    if res.is_empty() {
+        return Err(anyhow!("The roster names no custodians"));
+    }
+    if let Some(name) = res.iter().enumerate().find(|(i, x)| res[..*i].iter().any(|y| y.name == x.name)).map(|(_, x)| &x.name) {
+        return Err(anyhow!("{name} is on the roster twice"));
+    }
+    Ok(res)
+}
+
+/// Who holds which shares of a file, written next to it and to the packets directory
+///
+/// Plain text so anyone can read it without the app:
+///
+/// ```text
+/// # encryption-app custodian manifest, who holds which share. It holds no secrets
+/// file: notes_enc.bin
+/// file id: 00112233445566778899aabbccddeeff
+/// epoch: 0
+/// policy: 3 of 5 shares
+/// custodian: Alice | alice@example.com | x25519:... | #1, #2 | 01-alice
+/// custodian: Bob | | passphrase | #3 | 02-bob
+/// ```
+///
+/// A custodian line is name, contact note, public key, shares and packet directory, blank fields stay empty.
+/// Shares protected with their custodian's passphrase say `passphrase` in place of the public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub file_name: String,
+    pub file_id: FileId,
+    pub epoch: u32,
+    /// as `shares::Policy` displays it
+    pub policy: String,
+    pub custodians: Vec<Custodian>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Custodian {
+    pub name: String,
+    pub contact: String,
+    pub public_key: Option<X25519Recipient>,
+    /// the shares are protected with the custodian's passphrase, see `ShareBundle::protect`
+    pub passphrase: bool,
+    pub shares: Vec<ShareId>,
+    /// directory name of the custodian's packet, blank when the files went next to the file
+    pub packet: String,
+}
+
+impl Manifest {
+    pub fn encode(&self) -> String {
+        let mut res = format!("{MANIFEST_HEADER}\nfile: {}\nfile id: {}\nepoch: {}\npolicy: {}\n",
+            self.file_name, hex::encode(self.file_id), self.epoch, self.policy);
+        for custodian in &self.custodians {
+            let shares = custodian.shares.iter().map(ShareId::to_string).collect::<Vec<_>>().join(", ");
+            let public_key = match (custodian.public_key, custodian.passphrase) {
+                (Some(public_key), _) => public_key.to_string(),
+                (None, true) => PASSPHRASE_FIELD.to_string(),
+                (None, false) => String::new(),
+            };
+            res.push_str(&format!("custodian: {} | {} | {public_key} | {shares} | {}\n",
+                custodian.name, custodian.contact, custodian.packet));
+        }
+        res
+    }
+
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut lines = s.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err(anyhow!("Not a custodian manifest"));
+        }
+        let mut field = |name: &str| {
+            lines.next()
+                .and_then(|x| x.strip_prefix(name))
+                .and_then(|x| x.strip_prefix(": "))
+                .ok_or_else(|| anyhow!("Custodian manifest has no {name}"))
+        };
+        let file_name = field("file")?.to_string();
+        let file_id = hex::decode(field("file id")?)?.try_into()
+            .map_err(|_| anyhow!("Custodian manifest has a malformed file id"))?;
+        let epoch = field("epoch")?.parse()?;
+        let policy = field("policy")?.to_string();
+
+        let custodians = lines
+            .filter(|x| !x.trim().is_empty())
+            .map(|line| {
+                let line = line.strip_prefix("custodian: ").ok_or_else(|| anyhow!("Unexpected manifest line: {line}"))?;
+                let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+                let [name, contact, public_key, shares, packet] = fields.as_slice() else {
+                    return Err(anyhow!("Malformed custodian line: {line}"));
+                };
+                Ok(Custodian {
+                    name: name.to_string(),
+                    contact: contact.to_string(),
+                    public_key: match *public_key {
+                        "" | PASSPHRASE_FIELD => None,
+                        key => Some(key.parse()?),
+                    },
+                    passphrase: *public_key == PASSPHRASE_FIELD,
+                    shares: shares.split(',').filter(|x| !x.trim().is_empty()).map(str::parse).collect::<anyhow::Result<_>>()?,
+                    packet: packet.to_string(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self { file_name, file_id, epoch, policy, custodians })
+    }
+
+    /// Custodians none of whose shares are among `collected`
+    pub fn waiting_for(&self, collected: &[ShareId]) -> Vec<&Custodian> {
+        self.custodians.iter().filter(|x| !x.shares.iter().any(|id| collected.contains(id))).collect()
+    }
+
+    /// Name of the custodian holding share `id`
+    pub fn holder_of(&self, id: ShareId) -> Option<&str> {
+        self.custodians.iter().find(|x| x.shares.contains(&id)).map(|x| x.name.as_str())
+    }
+}
+
+/// Directory name of the packet for holder `number` (1 based), e.g. `02-alice-smith` for Alice Smith
+pub fn packet_dir_name(number: usize, name: &str) -> String {
+    let slug = name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        format!("{number:02}")
+    } else {
+        format!("{number:02}-{slug}")
+    }
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::X25519Identity;
+
+    #[test]
+    fn test_parse_roster() -> anyhow::Result<()> {
+        let key = X25519Identity::generate().recipient();
+        let roster = parse_roster(&format!("# name | contact | public key\nAlice Smith | alice@example.com, desk 4 | {key}\n\n cto:2 | ext. 12\nbob\n"))?;
+        assert_eq!(3, roster.len());
+        assert_eq!(("Alice Smith", "alice@example.com, desk 4", Some(key)), (roster[0].name.as_str(), roster[0].contact.as_str(), roster[0].public_key));
+        assert_eq!(("cto", 2, "ext. 12", None), (roster[1].name.as_str(), roster[1].votes, roster[1].contact.as_str(), roster[1].public_key));
+        assert_eq!(("bob", 1), (roster[2].name.as_str(), roster[2].votes));
+
+        assert!(parse_roster("alice | a | x25519:00").unwrap_err().to_string().contains("line 1"));
+        assert!(parse_roster("alice\nalice").is_err());
+        assert!(parse_roster("# nobody").is_err());
+        assert!(parse_roster("alice | a | | b").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_roundtrip() -> anyhow::Result<()> {
+        let manifest = Manifest {
+            file_name: "notes_enc.bin".to_string(),
+            file_id: [7; 16],
+            epoch: 2,
+            policy: "2 of 4 shares".to_string(),
+            custodians: vec![
+                Custodian {
+                    name: "Alice Smith".to_string(),
+                    contact: "alice@example.com".to_string(),
+                    public_key: Some(X25519Identity::generate().recipient()),
+                    passphrase: false,
+                    shares: vec![ShareId { group: None, index: 1 }, ShareId { group: None, index: 2 }],
+                    packet: packet_dir_name(1, "Alice Smith"),
+                },
+                Custodian {
+                    name: "holder of share #3".to_string(),
+                    contact: String::new(),
+                    public_key: None,
+                    passphrase: false,
+                    shares: vec![ShareId { group: None, index: 3 }],
+                    packet: String::new(),
+                },
+                Custodian {
+                    name: "Bob".to_string(),
+                    contact: String::new(),
+                    public_key: None,
+                    passphrase: true,
+                    shares: vec![ShareId { group: None, index: 4 }],
+                    packet: packet_dir_name(3, "Bob"),
+                },
+            ],
+        };
+        let parsed = Manifest::parse(&manifest.encode())?;
+        assert_eq!(manifest, parsed);
+        assert_eq!("01-alice-smith", parsed.custodians[0].packet);
+
+        let waiting: Vec<&str> = parsed.waiting_for(&[ShareId { group: None, index: 2 }]).iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(vec!["holder of share #3", "Bob"], waiting);
+        assert!(manifest.encode().contains("| passphrase | #4 |"));
+        assert_eq!(Some("Alice Smith"), parsed.holder_of(ShareId { group: None, index: 2 }));
+        assert!(Manifest::parse("file: x").is_err());
+
+        // a name with `|` would split the custodian line, holders can't be named that way
+        assert!(parse_holder("ops|infra:2").is_err());
+        let mut piped = manifest.clone();
+        piped.custodians[1].name = "ops|infra".to_string();
+        assert!(Manifest::parse(&piped.encode()).unwrap_err().to_string().contains("Malformed custodian line"));
+
+        Ok(())
+    }
+}
+
+// #endregion ----------------
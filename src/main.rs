@@ -1,17 +1,30 @@
-use std::path::PathBuf;
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::Arc};
 
 use iced::{
-    Element, Font, Length, Task, alignment::{Horizontal, Vertical}, color, widget::{Text, button, column, container, horizontal_rule, row, scrollable, text, text_input}
+    Element, Font, Length, Subscription, Task, alignment::{Horizontal, Vertical}, color, widget::{Text, button, column, container, horizontal_rule, row, scrollable, text, text_input}
 };
 use iced_font_awesome as ifa;
 use iced_modern_theme::Modern;
 use iced_optional_element_shim::to_elem;
-use tracing::{info, Level};
+use tracing::{error, info, Level};
 use tracing_subscriber::FmtSubscriber;
 
 use foo::FileMeta;
+use prompt::{PassphrasePrompt, PromptPurpose};
+use recipients::RecipientPrompt;
+use recovery::RecoveryScreen;
+use secret::SecretPanel;
+use shares::SharesPrompt;
 
 mod crypto;
+mod custodians;
+mod prompt;
+mod qr;
+mod recipients;
+mod recovery;
+mod secret;
+mod shares;
+mod sheet;
 mod tools;
 
 fn main() -> iced::Result {
@@ -26,6 +39,7 @@ fn main() -> iced::Result {
         .expect("setting default subscriber failed");
 
     iced::application("encryption-app", App::update, App::view)
+        .subscription(App::subscription)
         .theme(|_app| iced_modern_theme::Modern::dark_theme())
         // .theme(|_app| Theme::SolarizedDark)
         // .theme(|_app| Theme::TokyoNight)
@@ -37,6 +51,22 @@ fn main() -> iced::Result {
 struct App {
     directory: PathBuf,
     filelist: Vec<FileMeta>,
+    /// file index and path of a decrypt waiting on the user to confirm an overwrite
+    pending_overwrite: Option<(usize, String)>,
+    passphrase_prompt: Option<PassphrasePrompt>,
+    recipient_prompt: Option<RecipientPrompt>,
+    shares_prompt: Option<SharesPrompt>,
+    /// shares being collected to decrypt a file, dropping it wipes them
+    recovery: Option<RecoveryScreen>,
+    /// splitting or combining a typed in secret, dropping it wipes the secret and shares
+    secret_panel: Option<SecretPanel>,
+    /// keys of the vaults unlocked this session, by vault directory
+    vault_keys: HashMap<PathBuf, crypto::VaultKey>,
+    keystore_path: PathBuf,
+    /// unlocked keystore, holds the master keys used to wrap data keys
+    keystore: Option<Arc<crypto::Keystore>>,
+    /// signing key typed in to be trusted
+    signer_input: String,
 }
 
 #[derive(Debug, Clone)]
@@ -47,6 +77,31 @@ enum Message {
     DirectoryUp,
     FileList(Result<Vec<FileMeta>, Error>),
     Action(usize, foo::Message),
+    ConfirmOverwrite(usize, String),
+    OverwriteConfirmed,
+    OverwriteCancelled,
+    PromptPassphrase(PromptPurpose),
+    Prompt(prompt::Message),
+    PromptRecipient(usize),
+    EditRecipients(usize, Vec<crypto::Stanza>),
+    Recipient(recipients::Message),
+    PromptShares(usize),
+    Shares(shares::Message),
+    OpenRecovery(usize),
+    Recovery(recovery::Message),
+    OpenSecret,
+    Secret(secret::Message),
+    PromptVault,
+    VaultCreated(Result<foo::VaultStruct, String>),
+    OpenVaultRecovery,
+    LockVault,
+    CopyPublicKey,
+    CopySigningKey,
+    SignerInputChanged(String),
+    TrustSigner,
+    KeystoreChecked(bool),
+    KeystoreLoaded(Result<Arc<crypto::Keystore>, Error>),
+    KeystoreSaved(Result<Arc<crypto::Keystore>, Error>),
 }
 
 impl App {
@@ -55,8 +110,21 @@ impl App {
             Self {
                 directory: std::env::current_dir().unwrap_or_else(|_e| PathBuf::from(".")),
                 filelist: Vec::new(),
+                pending_overwrite: None,
+                passphrase_prompt: None,
+                recipient_prompt: None,
+                shares_prompt: None,
+                recovery: None,
+                secret_panel: None,
+                vault_keys: HashMap::new(),
+                keystore_path: keystore_path(),
+                keystore: None,
+                signer_input: String::new(),
             },
-            Task::done(Message::RefreshList)
+            Task::batch([
+                Task::done(Message::RefreshList),
+                Task::perform(keystore_exists(keystore_path()), Message::KeystoreChecked),
+            ])
         )
     }
 
@@ -79,21 +147,62 @@ impl App {
                 Task::done(Message::RefreshList)
             }
             Message::FileList(result) => {
-                if let Ok(mut files) = result {
-                    files.sort_by_key(|x| x.name.clone());
-                    self.filelist = files;
+                let Ok(mut files) = result else {
+                    return Task::none();
+                };
+                files.sort_by_key(|x| x.name.clone());
+                self.filelist = files;
+                // indices may point at different files now
+                self.pending_overwrite = None;
+                self.recipient_prompt = None;
+                if self.shares_prompt.as_ref().is_some_and(|x| matches!(x.target, shares::Target::File(_))) {
+                    self.shares_prompt = None;
                 }
-                Task::none()
+                if self.passphrase_prompt.as_ref().is_some_and(|x| matches!(x.purpose, PromptPurpose::File(..))) {
+                    self.passphrase_prompt = None;
+                }
+
+                // check every file that has a detached signature next to it
+                let names: HashSet<&str> = self.filelist.iter().map(|x| x.name.as_str()).collect();
+                Task::batch(self.filelist.iter()
+                    .enumerate()
+                    .filter(|(_, x)| x.is_file && names.contains(format!("{}.sig", x.name).as_str()))
+                    .map(|(index, _)| Task::done(Message::Action(index, foo::Message::CheckSignature))))
             }
             Message::Action(index, fm_message) => {
                 if let Some(filemeta) = self.filelist.get_mut(index) {
-                    foo::update(filemeta, fm_message)
-                        .then(|fm_msg| {
+                    let vault_key = self.vault_keys.get(&self.directory).cloned();
+                    foo::update(filemeta, fm_message, self.keystore.clone(), vault_key)
+                        .then(move |fm_msg| {
                             match fm_msg {
                                 foo::Message::LinkClicked(url) => {
                                     info!("Should be changing dir to {}", url);
                                     Task::done(Message::DirectoryDown(url))
                                 }
+                                foo::Message::OverwriteRequested(path) => {
+                                    Task::done(Message::ConfirmOverwrite(index, path))
+                                }
+                                foo::Message::PassphraseRequested(purpose) => {
+                                    Task::done(Message::PromptPassphrase(PromptPurpose::File(index, purpose)))
+                                }
+                                foo::Message::RecipientRequested => {
+                                    Task::done(Message::PromptRecipient(index))
+                                }
+                                foo::Message::SharesRequested => {
+                                    Task::done(Message::PromptShares(index))
+                                }
+                                foo::Message::RecoveryRequested => {
+                                    Task::done(Message::OpenRecovery(index))
+                                }
+                                foo::Message::RecipientsLoaded(Ok(stanzas)) => {
+                                    Task::done(Message::EditRecipients(index, stanzas))
+                                }
+                                // hand results back to the file row so they get reported
+                                foo::Message::EncryptResult(_) | foo::Message::DecryptResult(_)
+                                | foo::Message::RecipientsLoaded(_) | foo::Message::RecipientsChanged(_)
+                                | foo::Message::SignResult(_) | foo::Message::SignatureChecked(_) => {
+                                    Task::done(Message::Action(index, fm_msg))
+                                }
                                 _ => Task::done(Message::RefreshList)
                             }
                         })
@@ -101,21 +210,332 @@ impl App {
                     Task::none()
                 }
             }
+            Message::ConfirmOverwrite(index, path) => {
+                self.pending_overwrite = Some((index, path));
+                Task::none()
+            }
+            Message::OverwriteConfirmed => {
+                if let Some((index, _path)) = self.pending_overwrite.take() {
+                    Task::done(Message::Action(index, foo::Message::DecryptOverwrite))
+                } else {
+                    Task::none()
+                }
+            }
+            Message::OverwriteCancelled => {
+                self.pending_overwrite = None;
+                Task::none()
+            }
+            Message::PromptPassphrase(purpose) => {
+                self.passphrase_prompt = Some(PassphrasePrompt::new(purpose));
+                Task::none()
+            }
+            Message::Prompt(prompt_msg) => {
+                let Some(prompt) = self.passphrase_prompt.as_mut() else {
+                    return Task::none();
+                };
+                match prompt.update(prompt_msg) {
+                    prompt::Outcome::Pending => Task::none(),
+                    prompt::Outcome::Cancelled => {
+                        self.passphrase_prompt = None;
+                        Task::none()
+                    }
+                    prompt::Outcome::Submitted(passphrase) => {
+                        let purpose = prompt.purpose;
+                        self.passphrase_prompt = None;
+                        match purpose {
+                            PromptPurpose::File(index, foo::PassphrasePurpose::Encrypt) => {
+                                Task::done(Message::Action(index, foo::Message::EncryptWithPassphrase(passphrase)))
+                            }
+                            PromptPurpose::File(index, foo::PassphrasePurpose::Decrypt { overwrite }) => {
+                                Task::done(Message::Action(index, foo::Message::DecryptWithPassphrase(passphrase, overwrite)))
+                            }
+                            PromptPurpose::CreateKeystore => {
+                                Task::perform(create_keystore(self.keystore_path.clone(), passphrase), Message::KeystoreLoaded)
+                            }
+                            PromptPurpose::UnlockKeystore => {
+                                Task::perform(unlock_keystore(self.keystore_path.clone(), passphrase), Message::KeystoreLoaded)
+                            }
+                        }
+                    }
+                }
+            }
+            Message::PromptRecipient(index) => {
+                self.recipient_prompt = Some(RecipientPrompt::new(index));
+                Task::none()
+            }
+            Message::EditRecipients(index, stanzas) => {
+                self.recipient_prompt = Some(RecipientPrompt::edit(index, stanzas));
+                Task::none()
+            }
+            Message::Recipient(recipient_msg) => {
+                let Some(prompt) = self.recipient_prompt.as_mut() else {
+                    return Task::none();
+                };
+                match prompt.update(recipient_msg) {
+                    recipients::Outcome::Pending => Task::none(),
+                    recipients::Outcome::Cancelled => {
+                        self.recipient_prompt = None;
+                        Task::none()
+                    }
+                    recipients::Outcome::Encrypt(selection) => {
+                        let index = prompt.index;
+                        self.recipient_prompt = None;
+                        Task::done(Message::Action(index, foo::Message::EncryptToRecipients(selection)))
+                    }
+                    recipients::Outcome::Change(changes) => {
+                        let index = prompt.index;
+                        self.recipient_prompt = None;
+                        Task::done(Message::Action(index, foo::Message::ChangeRecipients(changes)))
+                    }
+                }
+            }
+            Message::PromptShares(index) => {
+                self.shares_prompt = Some(SharesPrompt::new(shares::Target::File(index)));
+                Task::none()
+            }
+            Message::Shares(shares_msg) => {
+                let Some(prompt) = self.shares_prompt.as_mut() else {
+                    return Task::none();
+                };
+                match prompt.update(shares_msg) {
+                    shares::Outcome::Pending => Task::none(),
+                    shares::Outcome::Task(task) => task.map(Message::Shares),
+                    shares::Outcome::Cancelled => {
+                        self.shares_prompt = None;
+                        Task::none()
+                    }
+                    shares::Outcome::Submitted(policy) => {
+                        let target = prompt.target.clone();
+                        self.shares_prompt = None;
+                        match target {
+                            shares::Target::File(index) => Task::done(Message::Action(index, foo::Message::EncryptWithShares(policy))),
+                            shares::Target::Vault(vault_dir) => {
+                                Task::perform(foo::create_vault(vault_dir, policy, self.keystore.clone()),
+                                    |res| Message::VaultCreated(res.map_err(|e| format!("{e:#}"))))
+                            }
+                        }
+                    }
+                }
+            }
+            Message::OpenRecovery(index) => {
+                let Some(filemeta) = self.filelist.get(index) else {
+                    return Task::none();
+                };
+                let screen = RecoveryScreen::new(filemeta.path.clone());
+                let task = screen.load_manifest();
+                self.recovery = Some(screen);
+                task.map(Message::Recovery)
+            }
+            Message::Recovery(recovery::Message::Close) => {
+                self.recovery = None;
+                Task::none()
+            }
+            Message::Recovery(recovery_msg) => {
+                let Some(screen) = self.recovery.as_mut() else {
+                    return Task::none();
+                };
+                let refresh = matches!(recovery_msg, recovery::Message::Decrypted(Ok(_)) | recovery::Message::VaultUnlocked(Ok(_))
+                    | recovery::Message::Refreshed(Ok(_)));
+                if let recovery::Message::VaultUnlocked(Ok(unlocked)) = &recovery_msg {
+                    self.vault_keys.insert(unlocked.vault_dir.clone(), unlocked.vault_key.clone());
+                }
+                // old shares are only rejected once the keystore knows about the new epoch
+                let save = match (&recovery_msg, self.keystore.as_ref()) {
+                    (recovery::Message::Refreshed(Ok(refreshed)), Some(keystore)) => {
+                        let mut keystore = crypto::Keystore::clone(keystore);
+                        keystore.set_share_epoch(refreshed.file_id, refreshed.epoch);
+                        Some(Task::perform(save_keystore(self.keystore_path.clone(), keystore), Message::KeystoreSaved))
+                    }
+                    _ => None,
+                };
+                let task = screen.update(recovery_msg, self.keystore.clone()).map(Message::Recovery);
+                match (save, refresh) {
+                    (Some(save), _) => Task::batch([task, save]),
+                    (None, true) => Task::batch([task, Task::done(Message::RefreshList)]),
+                    (None, false) => task,
+                }
+            }
+            Message::PromptVault => {
+                self.shares_prompt = Some(SharesPrompt::new(shares::Target::Vault(self.directory.clone())));
+                Task::none()
+            }
+            Message::VaultCreated(Ok(created)) => {
+                info!("Made {} a vault, files encrypted in it go into the vault", created.vault_dir.display());
+                for share_filepath in &created.share_filepaths {
+                    info!("  share file: {share_filepath}");
+                }
+                // whoever made the vault can fill it right away
+                self.vault_keys.insert(created.vault_dir, created.vault_key);
+                Task::done(Message::RefreshList)
+            }
+            Message::VaultCreated(Err(msg)) => {
+                error!("Making a vault failed: {msg}");
+                Task::none()
+            }
+            Message::OpenVaultRecovery => {
+                let screen = RecoveryScreen::for_vault(self.directory.join(foo::VAULT_FILE_NAME));
+                let task = screen.load_manifest();
+                self.recovery = Some(screen);
+                task.map(Message::Recovery)
+            }
+            Message::LockVault => {
+                self.vault_keys.remove(&self.directory);
+                Task::none()
+            }
+            Message::OpenSecret => {
+                self.secret_panel = Some(SecretPanel::new());
+                Task::none()
+            }
+            Message::Secret(secret::Message::Close) => {
+                self.secret_panel = None;
+                Task::none()
+            }
+            Message::Secret(secret_msg) => match self.secret_panel.as_mut() {
+                Some(panel) => panel.update(secret_msg).map(Message::Secret),
+                None => Task::none(),
+            },
+            Message::CopyPublicKey => {
+                match self.keystore.as_ref().and_then(|x| x.default_x25519_identity()) {
+                    Some(identity) => iced::clipboard::write(identity.recipient().to_string()),
+                    None => Task::none(),
+                }
+            }
+            Message::CopySigningKey => {
+                match self.keystore.as_ref().and_then(|x| x.default_ed25519_identity()) {
+                    Some(identity) => iced::clipboard::write(identity.signer().to_string()),
+                    None => Task::none(),
+                }
+            }
+            Message::SignerInputChanged(signer) => {
+                self.signer_input = signer;
+                Task::none()
+            }
+            Message::TrustSigner => {
+                let Some(keystore) = self.keystore.as_ref() else {
+                    return Task::none();
+                };
+                match self.signer_input.parse::<crypto::Ed25519Signer>() {
+                    Ok(signer) => {
+                        let mut keystore = crypto::Keystore::clone(keystore);
+                        keystore.add_known_signer(signer);
+                        self.signer_input.clear();
+                        info!("Trusting signatures by {signer}");
+                        Task::perform(save_keystore(self.keystore_path.clone(), keystore), Message::KeystoreSaved)
+                    }
+                    Err(e) => {
+                        error!("Unable to trust signer: {e}");
+                        Task::none()
+                    }
+                }
+            }
+            Message::KeystoreChecked(exists) => {
+                let purpose = if exists {
+                    PromptPurpose::UnlockKeystore
+                } else {
+                    PromptPurpose::CreateKeystore
+                };
+                Task::done(Message::PromptPassphrase(purpose))
+            }
+            Message::KeystoreLoaded(Ok(keystore)) => {
+                info!("Keystore unlocked, master key {}", hex::encode(keystore.default_master_key().id()));
+                self.keystore = Some(keystore);
+                // signatures can only be checked against known signers once the keystore is open
+                Task::done(Message::RefreshList)
+            }
+            Message::KeystoreSaved(Ok(keystore)) => {
+                self.keystore = Some(keystore);
+                Task::done(Message::RefreshList)
+            }
+            Message::KeystoreSaved(Err(Error::KeystoreError(msg) | Error::IoError(msg))) => {
+                error!("Unable to save keystore: {msg}");
+                Task::none()
+            }
+            Message::KeystoreLoaded(Err(Error::KeystoreError(msg) | Error::IoError(msg))) => {
+                error!("Keystore unavailable: {msg}");
+                // ask again, creating it if it never got written
+                let purpose = if self.keystore_path.exists() {
+                    PromptPurpose::UnlockKeystore
+                } else {
+                    PromptPurpose::CreateKeystore
+                };
+                self.passphrase_prompt = Some(PassphrasePrompt::new(purpose).with_error(msg));
+                Task::none()
+            }
         }
     }
 
     fn view(&self) -> Element<'_, Message> {
         let icon = ifa::fa_icon("folder-open").size(16.0).color(color!(249, 170, 51));
         let label = text("Enter directory:");
-        let dir_input = text_input("Directory", self.directory.to_str().unwrap_or_else(|| "."))
+        let dir_input = text_input("Directory", self.directory.to_str().unwrap_or("."))
             .style(Modern::text_input())
             .on_input(Message::DirectoryChanged)
             .width(600);
+        let keystore_status = match &self.keystore {
+            Some(keystore) => row!(
+                ifa::fa_icon_solid("key").size(16.0).color(color!(0, 255, 0)),
+                text(format!("master key {}", hex::encode(keystore.default_master_key().id()))),
+                if keystore.default_x25519_identity().is_some() {
+                    to_elem(Some(button(text("copy public key"))
+                        .style(Modern::secondary_button())
+                        .on_press(Message::CopyPublicKey)))
+                } else {
+                    to_elem::<Message, Text>(None)
+                },
+                if keystore.default_ed25519_identity().is_some() {
+                    to_elem(Some(button(text("copy signing key"))
+                        .style(Modern::secondary_button())
+                        .on_press(Message::CopySigningKey)))
+                } else {
+                    to_elem::<Message, Text>(None)
+                },
+                text_input("ed25519:...", &self.signer_input)
+                    .style(Modern::text_input())
+                    .on_input(Message::SignerInputChanged)
+                    .on_submit(Message::TrustSigner)
+                    .width(300),
+                button(text("trust signer"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::TrustSigner),
+            ),
+            None => row!(
+                ifa::fa_icon_solid("lock").size(16.0).color(color!(255, 0, 0)),
+                button(text("unlock keystore"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::KeystoreChecked(self.keystore_path.exists())),
+            ),
+        };
+        let is_vault = self.filelist.iter().any(|x| x.is_file && x.name == foo::VAULT_FILE_NAME);
+        let vault_status = match (is_vault, self.vault_keys.contains_key(&self.directory)) {
+            (true, true) => row!(
+                ifa::fa_icon_solid("vault").size(16.0).color(color!(0, 255, 0)),
+                text("vault unlocked, files encrypted here go into it"),
+                button(text("lock vault"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::LockVault),
+            ),
+            (true, false) => row!(
+                ifa::fa_icon_solid("vault").size(16.0).color(color!(255, 0, 0)),
+                text("vault locked"),
+                button(text("unlock vault..."))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::OpenVaultRecovery),
+            ),
+            (false, _) => row!(
+                button(text("make vault..."))
+                    .style(Modern::secondary_button())
+                    .on_press_maybe(self.keystore.is_some().then_some(Message::PromptVault)),
+            ),
+        };
         let input_col = column!(
-            row!(icon, label).spacing(10).align_y(Vertical::Center),
+            row!(icon, label, keystore_status.spacing(10).align_y(Vertical::Center)).spacing(10).align_y(Vertical::Center),
             row!(
                 button(ifa::fa_icon_solid("arrow-up").size(16.0)).on_press(Message::DirectoryUp),
-                dir_input
+                dir_input,
+                button(text("split a secret..."))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::OpenSecret),
+                vault_status.spacing(10).align_y(Vertical::Center),
             ).spacing(10).align_y(Vertical::Center)
         )
             .align_x(Horizontal::Left)
@@ -140,13 +560,44 @@ impl App {
             input_ctr,
             horizontal_rule(2),
 
-            if true {
-                to_elem(Some(text(format!("if true succeeded"))))
+            if let Some((_index, path)) = &self.pending_overwrite {
+                to_elem(Some(
+                    row!(
+                        ifa::fa_icon_solid("triangle-exclamation").size(16.0).color(color!(255, 170, 0)),
+                        text(format!("{path} already exists. Overwrite it?")),
+                        button(text("overwrite"))
+                            .style(Modern::danger_button())
+                            .on_press(Message::OverwriteConfirmed),
+                        button(text("cancel"))
+                            .style(Modern::secondary_button())
+                            .on_press(Message::OverwriteCancelled),
+                    ).spacing(10).align_y(Vertical::Center)
+                ))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            if let Some(prompt) = &self.passphrase_prompt {
+                to_elem(Some(prompt.view().map(Message::Prompt)))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            if let Some(prompt) = &self.recipient_prompt {
+                to_elem(Some(prompt.view().map(Message::Recipient)))
             } else {
                 to_elem::<Message, Text>(None)
             },
-            if false {
-                to_elem(Some(text(format!("if false failed"))))
+            if let Some(prompt) = &self.shares_prompt {
+                to_elem(Some(prompt.view().map(Message::Shares)))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            if let Some(screen) = &self.recovery {
+                to_elem(Some(screen.view().map(Message::Recovery)))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            if let Some(panel) = &self.secret_panel {
+                to_elem(Some(panel.view().map(Message::Secret)))
             } else {
                 to_elem::<Message, Text>(None)
             },
@@ -159,6 +610,24 @@ impl App {
         .spacing(20)
         .into()
     }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let secret = match &self.secret_panel {
+            Some(panel) => panel.subscription().map(Message::Secret),
+            None => Subscription::none(),
+        };
+        if self.recovery.is_none() {
+            return secret;
+        }
+        // files dropped on the window are shares for the recovery screen
+        let dropped = iced::event::listen_with(|event, _status, _window| match event {
+            iced::Event::Window(iced::window::Event::FileDropped(path)) => {
+                Some(Message::Recovery(recovery::Message::FileDropped(path)))
+            }
+            _ => None,
+        });
+        Subscription::batch([dropped, secret])
+    }
 }
 
 async fn list_files(base: PathBuf) -> Result<Vec<FileMeta>, Error> {
@@ -185,6 +654,7 @@ async fn list_files(base: PathBuf) -> Result<Vec<FileMeta>, Error> {
             is_symlink,
             ino,
             path,
+            signature: None,
         };
         files.push(fm);
     }
@@ -192,28 +662,128 @@ async fn list_files(base: PathBuf) -> Result<Vec<FileMeta>, Error> {
     Ok(files)
 }
 
+/// Where the keystore lives, `ENCRYPTION_APP_KEYSTORE` overrides the per-user config dir
+fn keystore_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("ENCRYPTION_APP_KEYSTORE") {
+        return PathBuf::from(path);
+    }
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("encryption-app")
+        .join("keystore.bin")
+}
+
+async fn keystore_exists(path: PathBuf) -> bool {
+    tokio::fs::try_exists(path).await.unwrap_or(false)
+}
+
+async fn unlock_keystore(path: PathBuf, passphrase: crypto::Passphrase) -> Result<Arc<crypto::Keystore>, Error> {
+    let bytes = tokio::fs::read(&path).await
+        .map_err(|e| Error::IoError(format!("Unable to read keystore {}: {e}", path.display())))?;
+
+    // key derivation is deliberately slow, keep it off the async workers
+    tokio::task::spawn_blocking(move || crypto::Keystore::unlock(&bytes, &passphrase))
+        .await
+        .map_err(|e| Error::KeystoreError(format!("Keystore unlock did not finish: {e}")))?
+        .map(Arc::new)
+        .map_err(|e| Error::KeystoreError(format!("{e}")))
+}
+
+/// Creates a keystore holding a fresh master key, never overwrites an existing one
+async fn create_keystore(path: PathBuf, passphrase: crypto::Passphrase) -> Result<Arc<crypto::Keystore>, Error> {
+    let keystore = tokio::task::spawn_blocking(move || {
+        let cost = crypto::calibrated_cost()?;
+        crypto::Keystore::create(&passphrase, cost)
+    })
+        .await
+        .map_err(|e| Error::KeystoreError(format!("Keystore creation did not finish: {e}")))?
+        .map_err(|e| Error::KeystoreError(format!("{e}")))?;
+    let bytes = keystore.seal()
+        .map_err(|e| Error::KeystoreError(format!("{e}")))?;
+
+    write_private_file(&path, &bytes).await
+        .map_err(|e| Error::IoError(format!("Unable to write keystore {}: {e}", path.display())))?;
+    info!("Created keystore {}", path.display());
+
+    Ok(Arc::new(keystore))
+}
+
+/// Replaces the keystore file, see `replace_private_file`
+async fn save_keystore(path: PathBuf, keystore: crypto::Keystore) -> Result<Arc<crypto::Keystore>, Error> {
+    let bytes = keystore.seal()
+        .map_err(|e| Error::KeystoreError(format!("{e}")))?;
+
+    replace_private_file(&path, &bytes).await
+        .map_err(|e| Error::IoError(format!("Unable to replace keystore {}: {e}", path.display())))?;
+
+    Ok(Arc::new(keystore))
+}
+
+/// Writes a new file only the current user can read
+async fn write_private_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(path).await?;
+    file.write_all(content).await?;
+    file.flush().await
+}
+
+/// Writes a file only the current user can read in place of `path`, through a private sibling so a failure keeps the old one
+async fn replace_private_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut partial_path = path.as_os_str().to_os_string();
+    partial_path.push(".partial");
+    let partial_path = PathBuf::from(partial_path);
+    let _ = tokio::fs::remove_file(&partial_path).await;
+    let res = match write_private_file(&partial_path, content).await {
+        Ok(()) => tokio::fs::rename(&partial_path, path).await,
+        Err(e) => Err(e),
+    };
+    if res.is_err() {
+        let _ = tokio::fs::remove_file(&partial_path).await;
+    }
+    res
+}
+
 #[derive(Debug, Clone)]
 pub enum Error {
     IoError(String),
+    KeystoreError(String),
 }
 
 mod foo {
-    use std::path::PathBuf;
+    use std::{path::{Path, PathBuf}, sync::Arc};
 
-    use anyhow::Context;
-    use iced::{Background, Color, Element, Length, Task, Theme, alignment::Vertical, color, widget::{Space, Text, button, column, container, rich_text, row, span, text}};
+    use anyhow::{anyhow, Context};
+    use iced::{Background, Element, Length, Task, Theme, alignment::Vertical, color, widget::{Space, Text, button, column, container, rich_text, row, span, text}};
     use iced_font_awesome as ifa;
     use iced_modern_theme::Modern;
     use iced_optional_element_shim::to_elem;
     use tokio::{
         fs::File,
-        io::AsyncWriteExt,
+        io::{AsyncWriteExt, BufReader, BufWriter},
     };
     use tracing::{error, info};
+    use zeroize::Zeroizing;
 
     use crate::crypto;
+    use crate::custodians;
+    use crate::replace_private_file;
+    use crate::recipients;
+    use crate::shares;
+    use crate::sheet;
     use crate::tools;
-    
+
+    /// Marks a directory as a project vault, see `create_vault`
+    pub const VAULT_FILE_NAME: &str = "vault.bin";
+
     #[allow(dead_code)]
     #[derive(Debug, Clone, Default)]
     pub struct FileMeta {
@@ -223,6 +793,8 @@ mod foo {
         pub is_symlink: bool,
         pub ino: u64,
         pub path: PathBuf,
+        /// result of checking the detached signature, None when there is none
+        pub signature: Option<crypto::SignatureStatus>,
     }
 
     impl FileMeta {
@@ -239,33 +811,50 @@ mod foo {
         }
     }
 
-    pub fn update(file_meta: &mut FileMeta, message: Message) -> Task<Message> {
+    /// `vault_key` is the key of the directory's vault when it is unlocked, files encrypted there go into the vault
+    pub fn update(file_meta: &mut FileMeta, message: Message, keystore: Option<Arc<crypto::Keystore>>, vault_key: Option<crypto::VaultKey>) -> Task<Message> {
         match message {
-            Message::Encrypt => {
-                let orig_filepath = file_meta.clone();
-                let enc_filepath = gen_encrypted_filepath(&orig_filepath.path);
-                let key_filepath = gen_key_filepath(&orig_filepath.path);
-                info!("encrypting {} to {}", orig_filepath.name, enc_filepath.display());
+            Message::Encrypt | Message::EncryptWithPassphrase(_) | Message::EncryptToRecipients(_) => {
+                let protection = match (message, vault_key) {
+                    (Message::EncryptWithPassphrase(passphrase), _) => Protection::Passphrase(passphrase),
+                    (Message::EncryptToRecipients(selection), _) => Protection::Recipients(selection),
+                    (_, Some(vault_key)) => Protection::Vault(vault_key),
+                    (_, None) => Protection::MasterKey,
+                };
+                let orig_filepath = file_meta.path.clone();
                 Task::future(async move {
-                    match (async move || {
-                        let data = tokio::fs::read(&orig_filepath.path.as_path()).await
-                            .with_context(|| format!("Failed to source file: {}", &orig_filepath.path.display()))?;
-
-                        let (aes_key, enc_data) = crypto::symmetric_encrypt_embed_nonce_enc_data_key(data.as_slice())
-                            .with_context(|| format!("Failed to encrypt file: {}", &orig_filepath.path.display()))?;
-                        write_bin_file(&enc_filepath, enc_data.as_slice()).await
-                            .with_context(|| format!("Failed to write encrypted file: {}", &enc_filepath.display()))?;
-                        write_bin_file(&key_filepath, aes_key.as_slice()).await
-                            .with_context(|| format!("Failed to write key file: {}", &key_filepath.display()))?;
-                        // Message::FileSystemUpdated
-                        // super::Message::RefreshList
-                        Ok::<EncryptStruct, anyhow::Error>(EncryptStruct {
-                            original_filepath: orig_filepath.path.display().to_string(),
-                            encrypted_filepath: enc_filepath.display().to_string(),
-                            key_filepath: key_filepath.display().to_string(),
-                        })
-                    })()
-                    .await {
+                    match encrypt_file(orig_filepath, protection, keystore).await {
+                        Ok(x) => {
+                            Message::EncryptResult(Ok(x))
+                        }
+                        Err(e) => {
+                            Message::EncryptResult(Err(format!("{e}")))
+                        }
+                    }
+                })
+            }
+            Message::EncryptPassphrase => {
+                // wrap message in task so parent can prompt for the passphrase
+                Task::future(async move {
+                    Message::PassphraseRequested(PassphrasePurpose::Encrypt)
+                })
+            }
+            Message::Recover | Message::RecoveryRequested => {
+                // wrap message in task so parent can open the recovery screen
+                Task::future(async move {
+                    Message::RecoveryRequested
+                })
+            }
+            Message::EncryptShares | Message::SharesRequested => {
+                // wrap message in task so parent can ask for n and k
+                Task::future(async move {
+                    Message::SharesRequested
+                })
+            }
+            Message::EncryptWithShares(policy) => {
+                let orig_filepath = file_meta.path.clone();
+                Task::future(async move {
+                    match encrypt_file_shares(orig_filepath, policy, keystore).await {
                         Ok(x) => {
                             Message::EncryptResult(Ok(x))
                         }
@@ -275,12 +864,26 @@ mod foo {
                     }
                 })
             }
+            Message::EncryptForRecipient | Message::RecipientRequested => {
+                // wrap message in task so parent can ask for the public key
+                Task::future(async move {
+                    Message::RecipientRequested
+                })
+            }
             Message::EncryptResult(Ok(enc_struct)) => {
                 {
                     // display messages about success
                     info!("Encrypted {}", enc_struct.original_filepath);
                     info!("  cipher file: {}", enc_struct.encrypted_filepath);
-                    info!("  key file: {}", enc_struct.key_filepath);
+                    if let Some(signer) = &enc_struct.signer {
+                        info!("  signed by: {signer}");
+                    }
+                    for share_filepath in &enc_struct.share_filepaths {
+                        info!("  share file: {share_filepath}");
+                    }
+                    if let Some(key_filepath) = &enc_struct.key_filepath {
+                        info!("  key file: {key_filepath}");
+                    }
                 }
                 Task::done(Message::FileSystemUpdated)
             }
@@ -288,10 +891,151 @@ mod foo {
                 error!("Encryption failed: {msg}");
                 Task::none()
             }
-            Message::Decrypt => {
-                info!("decrypt {}", file_meta.name);
+            Message::Decrypt | Message::DecryptOverwrite => {
+                let overwrite = matches!(message, Message::DecryptOverwrite);
+                let enc_filepath = file_meta.path.clone();
+                let orig_filepath = gen_original_filepath(&enc_filepath);
+                info!("decrypting {} to {}", file_meta.name, orig_filepath.display());
                 Task::future(async move {
-                    Message::FileSystemUpdated
+                    // ask before clobbering whatever already lives under the original name
+                    if !overwrite && tokio::fs::try_exists(&orig_filepath).await.unwrap_or(true) {
+                        return Message::OverwriteRequested(orig_filepath.display().to_string());
+                    }
+                    // unreadable headers are reported by decrypt_file below
+                    if let Ok(container) = read_container_header(&enc_filepath).await
+                        && needs_passphrase(&container, keystore.as_deref()) {
+                        return Message::PassphraseRequested(PassphrasePurpose::Decrypt { overwrite });
+                    }
+
+                    match decrypt_file(enc_filepath, overwrite, None, keystore, vault_key).await {
+                        Ok(x) => {
+                            Message::DecryptResult(Ok(x))
+                        }
+                        Err(e) => {
+                            Message::DecryptResult(Err(format!("{e}")))
+                        }
+                    }
+                })
+            }
+            Message::DecryptWithPassphrase(passphrase, overwrite) => {
+                let enc_filepath = file_meta.path.clone();
+                Task::future(async move {
+                    match decrypt_file(enc_filepath, overwrite, Some(passphrase), keystore, vault_key).await {
+                        Ok(x) => {
+                            Message::DecryptResult(Ok(x))
+                        }
+                        Err(e) => {
+                            Message::DecryptResult(Err(format!("{e}")))
+                        }
+                    }
+                })
+            }
+            Message::DecryptResult(Ok(dec_struct)) => {
+                {
+                    // display messages about success
+                    info!("Decrypted {}", dec_struct.encrypted_filepath);
+                    if let Some(key_filepath) = &dec_struct.key_filepath {
+                        info!("  key file: {key_filepath}");
+                    }
+                    info!("  clear file: {}", dec_struct.decrypted_filepath);
+                    if let Some(status) = dec_struct.signature {
+                        info!("  {}", describe_signature(status));
+                    }
+                }
+                Task::done(Message::FileSystemUpdated)
+            }
+            Message::DecryptResult(Err(msg)) => {
+                error!("Decryption failed: {msg}");
+                Task::none()
+            }
+            Message::ManageRecipients => {
+                let enc_filepath = file_meta.path.clone();
+                Task::future(async move {
+                    let stanzas = read_container_header(&enc_filepath).await
+                        .and_then(|container| {
+                            container.envelope()
+                                .map(|envelope| envelope.stanzas().to_vec())
+                                .ok_or_else(|| anyhow!("{} has a single key file, not a list of recipients", enc_filepath.display()))
+                        });
+                    Message::RecipientsLoaded(stanzas.map_err(|e| format!("{e}")))
+                })
+            }
+            Message::RecipientsLoaded(Ok(stanzas)) => {
+                // wrap message in task so parent can show the recipients
+                Task::future(async move {
+                    Message::RecipientsLoaded(Ok(stanzas))
+                })
+            }
+            Message::RecipientsLoaded(Err(msg)) => {
+                error!("Unable to read recipients: {msg}");
+                Task::none()
+            }
+            Message::ChangeRecipients(changes) => {
+                let enc_filepath = file_meta.path.clone();
+                Task::future(async move {
+                    match change_recipients(enc_filepath, changes, keystore).await {
+                        Ok(x) => Message::RecipientsChanged(Ok(x)),
+                        Err(e) => Message::RecipientsChanged(Err(format!("{e}"))),
+                    }
+                })
+            }
+            Message::RecipientsChanged(Ok(count)) => {
+                info!("File now has {count} recipients");
+                Task::done(Message::FileSystemUpdated)
+            }
+            Message::RecipientsChanged(Err(msg)) => {
+                error!("Changing recipients failed: {msg}");
+                Task::none()
+            }
+            Message::Sign => {
+                let filepath = file_meta.path.clone();
+                Task::future(async move {
+                    match sign_file(filepath, keystore).await {
+                        Ok(x) => Message::SignResult(Ok(x)),
+                        Err(e) => Message::SignResult(Err(format!("{e}"))),
+                    }
+                })
+            }
+            Message::SignResult(Ok(sig_filepath)) => {
+                info!("Signed {}", file_meta.name);
+                info!("  signature file: {sig_filepath}");
+                Task::done(Message::FileSystemUpdated)
+            }
+            Message::SignResult(Err(msg)) => {
+                error!("Signing failed: {msg}");
+                Task::none()
+            }
+            Message::CheckSignature => {
+                let filepath = file_meta.path.clone();
+                Task::future(async move {
+                    match check_detached_signature(filepath, keystore).await {
+                        Ok(x) => Message::SignatureChecked(Ok(x)),
+                        Err(e) => Message::SignatureChecked(Err(format!("{e}"))),
+                    }
+                })
+            }
+            Message::SignatureChecked(Ok(status)) => {
+                if status == crypto::SignatureStatus::Invalid {
+                    error!("Signature of {} does not match the file", file_meta.name);
+                }
+                file_meta.signature = Some(status);
+                Task::none()
+            }
+            Message::SignatureChecked(Err(msg)) => {
+                error!("Unable to check signature of {}: {msg}", file_meta.name);
+                file_meta.signature = Some(crypto::SignatureStatus::Invalid);
+                Task::none()
+            }
+            Message::OverwriteRequested(path) => {
+                // wrap message in task so parent can ask the user
+                Task::future(async move {
+                    Message::OverwriteRequested(path)
+                })
+            }
+            Message::PassphraseRequested(purpose) => {
+                // wrap message in task so parent can prompt for the passphrase
+                Task::future(async move {
+                    Message::PassphraseRequested(purpose)
                 })
             }
             Message::Delete => {
@@ -313,6 +1057,7 @@ mod foo {
         }
     }
 
+    #[allow(dead_code)]
     pub fn view(file_meta: &FileMeta) -> Element<'_, Message> {
         let is_file = file_meta.is_file;
         let is_dir = file_meta.is_dir;
@@ -355,7 +1100,7 @@ mod foo {
             // Display file or directory name
             // text(&file_meta.name).width(300),
             column!(
-                rich_text([span(&file_meta.name).color_maybe(text_color).link_maybe(link.map(Message::LinkClicked)).into()])
+                rich_text([span(&file_meta.name).color_maybe(text_color).link_maybe(link.map(Message::LinkClicked))])
             ).width(300),
 
             column!(
@@ -420,6 +1165,10 @@ mod foo {
         let is_symlink = file_meta.is_symlink;
         let is_enc_file = is_file && is_encrypted(&file_meta.path);
         let is_key_file = is_file && is_keyfile(&file_meta.path);
+        let is_sig_file = is_file && is_sigfile(&file_meta.path);
+        let is_share_file = is_file && is_sharefile(&file_meta.path);
+        let is_vault_file = is_file && is_vaultfile(&file_meta.path);
+        let can_encrypt = is_file && !is_enc_file && !is_key_file && !is_sig_file && !is_share_file && !is_vault_file;
         let text_color = if is_dir {
                 Some(color!(80, 80, 255))
             } else if is_symlink {
@@ -440,7 +1189,7 @@ mod foo {
             // ... other styling properties
             ..Default::default()
         };
-        let use_style = if index % 2 == 0 {
+        let use_style = if index.is_multiple_of(2) {
             style1
         } else {
             style0
@@ -453,6 +1202,12 @@ mod foo {
                         to_elem(Some(ifa::fa_icon_solid("lock").size(16.0).color(color!(255, 0, 0))))
                     } else if is_key_file {
                         to_elem(Some(ifa::fa_icon_solid("key").size(16.0).color(color!(0, 255, 0))))
+                    } else if is_sig_file {
+                        to_elem(Some(ifa::fa_icon_solid("file-signature").size(16.0)))
+                    } else if is_share_file {
+                        to_elem(Some(ifa::fa_icon_solid("puzzle-piece").size(16.0).color(color!(0, 255, 0))))
+                    } else if is_vault_file {
+                        to_elem(Some(ifa::fa_icon_solid("vault").size(16.0).color(color!(0, 255, 0))))
                     } else if is_file {
                         to_elem(Some(ifa::fa_icon_solid("lock-open").size(16.0)))
                         // to_elem(Some(Space::with_width(16)))
@@ -468,7 +1223,7 @@ mod foo {
                 // Display file or directory name
                 // text(&file_meta.name).width(300),
                 column!(
-                    rich_text([span(&file_meta.name).color_maybe(text_color).link_maybe(link.map(Message::LinkClicked)).into()])
+                    rich_text([span(&file_meta.name).color_maybe(text_color).link_maybe(link.map(Message::LinkClicked))])
                 ).width(300),
 
                 column!(
@@ -476,7 +1231,34 @@ mod foo {
                 ).width(50),
 
                 column!(
-                    if is_file && !is_enc_file && !is_key_file {
+                    match file_meta.signature {
+                        Some(status) => {
+                            let (icon, icon_color, label) = match status {
+                                crypto::SignatureStatus::Trusted(_) => ("circle-check", color!(0, 255, 0), "signed"),
+                                crypto::SignatureStatus::UnknownSigner(_) => ("circle-question", color!(255, 170, 0), "unknown signer"),
+                                crypto::SignatureStatus::Invalid => ("circle-xmark", color!(255, 0, 0), "bad signature"),
+                            };
+                            to_elem(Some(row!(
+                                ifa::fa_icon_solid(icon).size(16.0).color(icon_color),
+                                text(label),
+                            ).spacing(5).align_y(Vertical::Center)))
+                        }
+                        None => to_elem::<Message, Text>(None),
+                    }
+                ).width(170),
+
+                column!(
+                    if is_file && !is_key_file && !is_sig_file && !is_share_file && !is_vault_file {
+                        to_elem(Some(button(text("sign"))
+                            .style(Modern::secondary_button())
+                            .on_press(Message::Sign)))
+                    } else {
+                        to_elem::<Message, Text>(None)
+                    }
+                ).width(70),
+
+                column!(
+                    if can_encrypt {
                         to_elem(Some(button(text("encrypt"))
                             .style(Modern::primary_button())
                             .on_press(Message::Encrypt)))
@@ -495,6 +1277,46 @@ mod foo {
                     // }
                 ).width(100),
 
+                column!(
+                    if can_encrypt {
+                        to_elem(Some(button(text("passphrase"))
+                            .style(Modern::secondary_button())
+                            .on_press(Message::EncryptPassphrase)))
+                    } else {
+                        to_elem::<Message, Text>(None)
+                    }
+                ).width(120),
+
+                column!(
+                    if can_encrypt {
+                        to_elem(Some(button(text("for..."))
+                            .style(Modern::secondary_button())
+                            .on_press(Message::EncryptForRecipient)))
+                    } else {
+                        to_elem::<Message, Text>(None)
+                    }
+                ).width(100),
+
+                column!(
+                    if can_encrypt {
+                        to_elem(Some(button(text("shares..."))
+                            .style(Modern::secondary_button())
+                            .on_press(Message::EncryptShares)))
+                    } else {
+                        to_elem::<Message, Text>(None)
+                    }
+                ).width(110),
+
+                column!(
+                    if is_enc_file {
+                        to_elem(Some(button(text("recipients"))
+                            .style(Modern::secondary_button())
+                            .on_press(Message::ManageRecipients)))
+                    } else {
+                        to_elem::<Message, Text>(None)
+                    }
+                ).width(120),
+
                 column!(
                     if is_enc_file {
                         to_elem(Some(button(text("decrypt"))
@@ -508,6 +1330,16 @@ mod foo {
                     }
                 ).width(100),
 
+                column!(
+                    if is_enc_file {
+                        to_elem(Some(button(text("recover"))
+                            .style(Modern::secondary_button())
+                            .on_press(Message::Recover)))
+                    } else {
+                        to_elem::<Message, Text>(None)
+                    }
+                ).width(100),
+
                 if is_file {
                     row!(
                         Space::with_width(80),
@@ -531,7 +1363,605 @@ mod foo {
         .into()
     }
 
-    fn gen_encrypted_filepath(pb: &PathBuf) -> PathBuf {
+    async fn encrypt_file(orig_filepath: PathBuf, protection: Protection, keystore: Option<Arc<crypto::Keystore>>) -> anyhow::Result<EncryptStruct> {
+        let enc_filepath = gen_encrypted_filepath(&orig_filepath);
+        let key_filepath = gen_key_filepath(&orig_filepath);
+        info!("encrypting {} to {}", orig_filepath.display(), enc_filepath.display());
+
+        let signer = match &protection {
+            Protection::Recipients(selection) if selection.sign => {
+                let identity = keystore.as_ref()
+                    .ok_or_else(|| anyhow!("Unlock the keystore to sign"))?
+                    .default_ed25519_identity()
+                    .ok_or_else(|| anyhow!("Keystore has no signing key"))?;
+                Some(identity.clone())
+            }
+            _ => None,
+        };
+        let mut sealing_key = match protection {
+            Protection::Passphrase(passphrase) => {
+                // key derivation is deliberately slow, keep it off the async workers
+                tokio::task::spawn_blocking(move || {
+                    let cost = crypto::calibrated_cost()?;
+                    crypto::SealingKey::envelope(&[crypto::Recipient::Passphrase(passphrase, cost)])
+                })
+                .await?
+            }
+            Protection::MasterKey => {
+                let keystore = keystore.ok_or_else(|| anyhow!("Unlock the keystore to encrypt with the master key"))?;
+                crypto::SealingKey::enc_data_key(keystore.default_master_key())
+            }
+            Protection::Recipients(selection) => {
+                let mut recipients = Vec::new();
+                if selection.include_self {
+                    let keystore = keystore.as_ref().ok_or_else(|| anyhow!("Unlock the keystore to include your master key"))?;
+                    recipients.push(crypto::Recipient::MasterKey(keystore.default_master_key().clone()));
+                }
+                recipients.extend(selection.public_keys.into_iter().map(crypto::Recipient::X25519));
+                crypto::SealingKey::envelope(&recipients)
+            }
+            Protection::Vault(vault_key) => crypto::SealingKey::envelope(&[crypto::Recipient::VaultKey(vault_key)]),
+        }
+            .with_context(|| format!("Failed to encrypt file: {}", &orig_filepath.display()))?;
+
+        // sign-then-encrypt: the signature over the plaintext travels encrypted inside the container
+        if let Some(identity) = &signer {
+            let digest = digest_file(&orig_filepath).await?;
+            sealing_key.sign(identity, &digest)
+                .with_context(|| format!("Failed to sign file: {}", &orig_filepath.display()))?;
+        }
+
+        let mut reader = BufReader::new(File::open(&orig_filepath).await
+            .with_context(|| format!("Failed to source file: {}", &orig_filepath.display()))?);
+        let (partial_filepath, mut writer) = create_partial_file(&enc_filepath).await?;
+        let written = sealing_key.seal_stream(&mut reader, &mut writer).await;
+        finish_partial_file(&partial_filepath, &enc_filepath, true, written).await
+            .with_context(|| format!("Failed to write encrypted file: {}", &enc_filepath.display()))?;
+        // envelopes carry their wrapped keys inside
+        let key_filepath = match sealing_key.wrapped_key() {
+            Some(wrapped_key) => {
+                write_bin_file(&key_filepath, wrapped_key).await
+                    .with_context(|| format!("Failed to write key file: {}", &key_filepath.display()))?;
+                Some(key_filepath.display().to_string())
+            }
+            None => None,
+        };
+
+        Ok(EncryptStruct {
+            original_filepath: orig_filepath.display().to_string(),
+            encrypted_filepath: enc_filepath.display().to_string(),
+            key_filepath,
+            signer: signer.map(|x| x.signer().to_string()),
+            share_filepaths: Vec::new(),
+        })
+    }
+
+    /// Encrypts with the master key and splits the wrapped data key into share files as the policy says,
+    /// `k_thres` of `n_shares` or across groups
+    async fn encrypt_file_shares(orig_filepath: PathBuf, policy: shares::Policy, keystore: Option<Arc<crypto::Keystore>>) -> anyhow::Result<EncryptStruct> {
+        let enc_filepath = gen_encrypted_filepath(&orig_filepath);
+        info!("encrypting {} to {} with {policy}", orig_filepath.display(), enc_filepath.display());
+        let keystore = keystore.ok_or_else(|| anyhow!("Unlock the keystore to encrypt with the master key"))?;
+
+        let sealing_key = crypto::SealingKey::shamir_master_key(keystore.default_master_key())
+            .with_context(|| format!("Failed to encrypt file: {}", &orig_filepath.display()))?;
+        let mut reader = BufReader::new(File::open(&orig_filepath).await
+            .with_context(|| format!("Failed to source file: {}", &orig_filepath.display()))?);
+        let (partial_filepath, mut writer) = create_partial_file(&enc_filepath).await?;
+        let written = sealing_key.seal_stream(&mut reader, &mut writer).await;
+        finish_partial_file(&partial_filepath, &enc_filepath, true, written).await
+            .with_context(|| format!("Failed to write encrypted file: {}", &enc_filepath.display()))?;
+
+        // shares name the file by a hash of its ciphertext, see `crypto::file_id`
+        let split = async {
+            let file_id = read_file_id(&enc_filepath).await?;
+            let shares = if policy.groups.is_empty() {
+                crypto::split_key(&sealing_key, policy.n_shares, policy.k_thres, policy.verifiable, file_id)
+            } else {
+                crypto::split_key_groups(&sealing_key, &policy.group_sizes(), policy.k_groups, file_id)
+            }
+                .with_context(|| format!("Failed to split the key of {}", &enc_filepath.display()))?;
+            let share_filepaths = write_share_files(&orig_filepath, &shares, &policy).await?;
+            anyhow::Ok((shares, share_filepaths))
+        }.await;
+        let (shares, share_filepaths) = match split {
+            Ok(split) => split,
+            Err(e) => {
+                // nobody could open the file without its shares
+                let _ = tokio::fs::remove_file(&enc_filepath).await;
+                return Err(e);
+            }
+        };
+        if let Some(fingerprint) = shares.first().and_then(|x| x.commitments_fingerprint()) {
+            info!("  share commitments {fingerprint}, every holder should see the same");
+        }
+
+        Ok(EncryptStruct {
+            original_filepath: orig_filepath.display().to_string(),
+            encrypted_filepath: enc_filepath.display().to_string(),
+            key_filepath: None,
+            signer: None,
+            share_filepaths,
+        })
+    }
+
+    async fn decrypt_file(enc_filepath: PathBuf, overwrite: bool, passphrase: Option<crypto::Passphrase>, keystore: Option<Arc<crypto::Keystore>>,
+            vault_key: Option<crypto::VaultKey>) -> anyhow::Result<DecryptStruct> {
+        let orig_filepath = gen_original_filepath(&enc_filepath);
+        let key_filepath = gen_key_filepath(&orig_filepath);
+
+        let mut reader = BufReader::new(File::open(&enc_filepath).await
+            .with_context(|| format!("Failed to read encrypted file: {}", &enc_filepath.display()))?);
+        let container = Arc::new(crypto::ContainerStreamReader::read_header(&mut reader).await
+            .with_context(|| format!("Failed to read encrypted file: {}", &enc_filepath.display()))?);
+
+        let vault_key_id = container.envelope().and_then(|x| x.vault_key_id());
+        let (data_key, key_filepath) = if let Some(vault_key_id) = vault_key_id {
+            let vault_key = vault_key.filter(|x| x.id() == vault_key_id)
+                .ok_or_else(|| anyhow!("{} is in a vault, unlock the vault with its shares first", enc_filepath.display()))?;
+            let envelope = container.envelope().ok_or_else(|| anyhow!("Container has no recipient stanzas"))?;
+            (envelope.unwrap_with_vault_key(container.header_bytes(), &vault_key), None)
+        } else if container.envelope().is_some() {
+            let container = container.clone();
+            let keystore = keystore.clone();
+            let data_key = tokio::task::spawn_blocking(move || {
+                unwrap_envelope_key(&container, keystore.as_deref(), passphrase.as_ref())
+            })
+            .await?;
+            (data_key, None)
+        } else if matches!(container.header().key_wrap, crypto::KeyWrapMethod::Shamir | crypto::KeyWrapMethod::ShamirBuiltinKek | crypto::KeyWrapMethod::ShamirMasterKey) {
+            return Err(anyhow!("{} was split into shares, recover it from them", enc_filepath.display()));
+        } else {
+            // master key files, and passphrase files from before the passphrase went into the container, keep their key next to it
+            let wrapped_key = tokio::fs::read(&key_filepath).await
+                .with_context(|| format!("Failed to read key file: {}", &key_filepath.display()))?;
+            let data_key = match passphrase {
+                Some(passphrase) => {
+                    let header = container.header().clone();
+                    tokio::task::spawn_blocking(move || {
+                        crypto::unwrap_container_key_passphrase(&header, wrapped_key.as_slice(), &passphrase)
+                    })
+                    .await?
+                }
+                None => {
+                    let keystore = keystore.as_ref().ok_or_else(|| anyhow!("Unlock the keystore to decrypt with the master key"))?;
+                    crypto::unwrap_container_key(container.header(), wrapped_key.as_slice(), keystore)
+                }
+            };
+            (data_key, Some(key_filepath.display().to_string()))
+        };
+        let data_key = data_key
+            .with_context(|| format!("Failed to decrypt file: {}", &enc_filepath.display()))?;
+        let signature = container.envelope()
+            .map(|envelope| envelope.signature(&data_key))
+            .transpose()
+            .with_context(|| format!("Failed to decrypt file: {}", &enc_filepath.display()))?
+            .flatten();
+
+        let (partial_filepath, mut writer) = create_partial_file(&orig_filepath).await?;
+        let written = container.open(&data_key, &mut reader, &mut writer).await
+            .with_context(|| format!("Failed to decrypt file: {}", &enc_filepath.display()));
+        // a signed file only goes into place when the signature matches what was decrypted
+        let mut signature_status = None;
+        let written = match (written, &signature) {
+            (Ok(len), Some(signature)) => check_signature(&partial_filepath, signature, keystore.as_deref()).await
+                .and_then(|status| match status {
+                    crypto::SignatureStatus::Invalid => {
+                        Err(anyhow!("Signature by {} does not match the decrypted content", signature.signer()))
+                    }
+                    status => {
+                        signature_status = Some(status);
+                        Ok(len)
+                    }
+                }),
+            (written, _) => written,
+        };
+        finish_partial_file(&partial_filepath, &orig_filepath, overwrite, written).await
+            .with_context(|| format!("Failed to write decrypted file: {}", &orig_filepath.display()))?;
+
+        Ok(DecryptStruct {
+            encrypted_filepath: enc_filepath.display().to_string(),
+            key_filepath,
+            decrypted_filepath: orig_filepath.display().to_string(),
+            signature: signature_status,
+        })
+    }
+
+    /// Writes a detached signature of the file next to it, returns the signature file path
+    async fn sign_file(filepath: PathBuf, keystore: Option<Arc<crypto::Keystore>>) -> anyhow::Result<String> {
+        let keystore = keystore.ok_or_else(|| anyhow!("Unlock the keystore to sign"))?;
+        let identity = keystore.default_ed25519_identity()
+            .ok_or_else(|| anyhow!("Keystore has no signing key"))?;
+        let digest = digest_file(&filepath).await?;
+
+        let sig_filepath = gen_signature_filepath(&filepath);
+        let signature = crypto::Signature::sign(identity, &digest);
+        write_bin_file(&sig_filepath, &signature.encode()).await
+            .with_context(|| format!("Failed to write signature file: {}", &sig_filepath.display()))?;
+
+        Ok(sig_filepath.display().to_string())
+    }
+
+    /// Checks the detached signature next to the file
+    async fn check_detached_signature(filepath: PathBuf, keystore: Option<Arc<crypto::Keystore>>) -> anyhow::Result<crypto::SignatureStatus> {
+        let sig_filepath = gen_signature_filepath(&filepath);
+        let bytes = tokio::fs::read(&sig_filepath).await
+            .with_context(|| format!("Failed to read signature file: {}", &sig_filepath.display()))?;
+        let signature = crypto::Signature::decode(&bytes)?;
+
+        check_signature(&filepath, &signature, keystore.as_deref()).await
+    }
+
+    async fn check_signature(filepath: &Path, signature: &crypto::Signature, keystore: Option<&crypto::Keystore>) -> anyhow::Result<crypto::SignatureStatus> {
+        let digest = digest_file(filepath).await?;
+        Ok(signature.check(&digest, keystore))
+    }
+
+    async fn digest_file(filepath: &Path) -> anyhow::Result<crypto::ContentDigest> {
+        let mut reader = BufReader::new(File::open(filepath).await
+            .with_context(|| format!("Failed to read file: {}", filepath.display()))?);
+        crypto::digest_reader(&mut reader).await
+            .with_context(|| format!("Failed to read file: {}", filepath.display()))
+    }
+
+    /// Id the shares of an encrypted file name it by, see `crypto::file_id`
+    async fn read_file_id(filepath: &Path) -> anyhow::Result<crypto::FileId> {
+        let mut reader = BufReader::new(File::open(filepath).await
+            .with_context(|| format!("Failed to read encrypted file: {}", filepath.display()))?);
+        crypto::file_id_reader(&mut reader).await
+            .with_context(|| format!("Failed to read encrypted file: {}", filepath.display()))
+    }
+
+    fn describe_signature(status: crypto::SignatureStatus) -> String {
+        match status {
+            crypto::SignatureStatus::Trusted(signer) => format!("signed by {signer}"),
+            crypto::SignatureStatus::UnknownSigner(signer) => format!("signed by unknown signer {signer}"),
+            crypto::SignatureStatus::Invalid => "signature does not match".to_string(),
+        }
+    }
+
+    /// Unwraps the data key from the recipient stanzas with whatever credential we have
+    fn unwrap_envelope_key(container: &crypto::ContainerStreamReader, keystore: Option<&crypto::Keystore>, passphrase: Option<&crypto::Passphrase>) -> anyhow::Result<zeroize::Zeroizing<Vec<u8>>> {
+        let envelope = container.envelope()
+            .ok_or_else(|| anyhow!("Container has no recipient stanzas"))?;
+        if keystore.is_none() && passphrase.is_none() {
+            return Err(anyhow!("Unlock the keystore to decrypt with the master key"));
+        }
+        envelope.unwrap(container.header_bytes(), keystore, passphrase)
+    }
+
+    /// Adds and removes recipients of an envelope file, the payload is copied as is
+    /// returns the number of recipients afterwards
+    async fn change_recipients(enc_filepath: PathBuf, changes: recipients::Changes, keystore: Option<Arc<crypto::Keystore>>) -> anyhow::Result<usize> {
+        let keystore = keystore.ok_or_else(|| anyhow!("Unlock the keystore to change recipients"))?;
+        let mut reader = BufReader::new(File::open(&enc_filepath).await
+            .with_context(|| format!("Failed to read encrypted file: {}", &enc_filepath.display()))?);
+        let container = crypto::ContainerStreamReader::read_header(&mut reader).await
+            .with_context(|| format!("Failed to read encrypted file: {}", &enc_filepath.display()))?;
+        let data_key = unwrap_envelope_key(&container, Some(&keystore), None)
+            .with_context(|| format!("Only a recipient can change the recipients of {}", &enc_filepath.display()))?;
+
+        let mut envelope = container.envelope().cloned()
+            .ok_or_else(|| anyhow!("Container has no recipient stanzas"))?;
+        for key_id in &changes.remove {
+            envelope.remove(key_id)?;
+        }
+        for public_key in changes.add {
+            envelope.add(&crypto::Recipient::X25519(public_key), &data_key)?;
+        }
+
+        let (partial_filepath, mut writer) = create_partial_file(&enc_filepath).await?;
+        let written = container.rewrap(&data_key, &envelope, &mut reader, &mut writer).await;
+        finish_partial_file(&partial_filepath, &enc_filepath, true, written).await
+            .with_context(|| format!("Failed to write encrypted file: {}", &enc_filepath.display()))?;
+
+        Ok(envelope.stanzas().len())
+    }
+
+    /// True when the file can only be opened with a passphrase we still have to ask for
+    fn needs_passphrase(container: &crypto::ContainerStreamReader, keystore: Option<&crypto::Keystore>) -> bool {
+        match container.envelope() {
+            Some(envelope) => envelope.needs_passphrase(keystore),
+            None => container.header().key_wrap == crypto::KeyWrapMethod::Passphrase,
+        }
+    }
+
+    /// Reads just enough of an encrypted file to parse its container header and recipients
+    async fn read_container_header(filepath: &Path) -> anyhow::Result<crypto::ContainerStreamReader> {
+        let mut reader = BufReader::new(File::open(filepath).await?);
+        crypto::ContainerStreamReader::read_header(&mut reader).await
+    }
+
+    /// Makes `vault_dir` a project vault and splits its key into share files as the policy says
+    ///
+    /// The vault file and the shares go into the directory, named after `VAULT_FILE_NAME`. Files encrypted in the directory
+    /// while the vault is unlocked get their data key wrapped with the vault key, see `Protection::Vault`.
+    pub async fn create_vault(vault_dir: PathBuf, policy: shares::Policy, keystore: Option<Arc<crypto::Keystore>>) -> anyhow::Result<VaultStruct> {
+        let vault_filepath = vault_dir.join(VAULT_FILE_NAME);
+        info!("making {} a vault with {policy}", vault_dir.display());
+        let keystore = keystore.ok_or_else(|| anyhow!("Unlock the keystore to make a vault"))?;
+        if tokio::fs::try_exists(&vault_filepath).await.unwrap_or(true) {
+            return Err(anyhow!("{} is already a vault", vault_dir.display()));
+        }
+
+        let (header, vault_key, shares) = if policy.groups.is_empty() {
+            crypto::create_vault(policy.n_shares, policy.k_thres, policy.verifiable, keystore.default_master_key())
+        } else {
+            crypto::create_vault_groups(&policy.group_sizes(), policy.k_groups, keystore.default_master_key())
+        }
+            .with_context(|| format!("Failed to make a vault of {}", vault_dir.display()))?;
+
+        write_bin_file(&vault_filepath, &header.encode()).await
+            .with_context(|| format!("Failed to write vault file: {}", &vault_filepath.display()))?;
+        let share_filepaths = write_share_files(&vault_filepath, &shares, &policy).await?;
+        if let Some(fingerprint) = shares.first().and_then(|x| x.commitments_fingerprint()) {
+            info!("  share commitments {fingerprint}, every holder should see the same");
+        }
+
+        Ok(VaultStruct { vault_dir, vault_key, share_filepaths, bad_shares: Vec::new() })
+    }
+
+    /// Combines `shares` of a vault key, the key stays with the caller for the session
+    pub async fn unlock_vault(vault_filepath: PathBuf, shares: Zeroizing<Vec<crypto::Share>>, keystore: Option<Arc<crypto::Keystore>>) -> anyhow::Result<VaultStruct> {
+        let vault_dir = vault_filepath.parent().map(Path::to_path_buf).unwrap_or_default();
+        let keystore = keystore.ok_or_else(|| anyhow!("Unlock the keystore to unlock the vault"))?;
+        let header = crypto::VaultHeader::decode(&tokio::fs::read(&vault_filepath).await
+            .with_context(|| format!("Failed to read vault file: {}", &vault_filepath.display()))?)
+            .with_context(|| format!("Failed to read vault file: {}", &vault_filepath.display()))?;
+
+        let (vault_key, bad_shares) = tokio::task::spawn_blocking(move || crypto::unlock_vault(&header, &shares, &keystore))
+            .await?
+            .with_context(|| format!("Failed to unlock the vault with the collected shares: {}", vault_dir.display()))?;
+
+        Ok(VaultStruct { vault_dir, vault_key, share_filepaths: Vec::new(), bad_shares })
+    }
+
+    /// Combines `shares` of a split key and decrypts `enc_filepath` next to it
+    pub async fn recover_file(enc_filepath: PathBuf, shares: Zeroizing<Vec<crypto::Share>>, keystore: Option<Arc<crypto::Keystore>>) -> anyhow::Result<RecoverStruct> {
+        let orig_filepath = gen_original_filepath(&enc_filepath);
+        let keystore = keystore.ok_or_else(|| anyhow!("Unlock the keystore to decrypt with the master key"))?;
+        let mut reader = BufReader::new(File::open(&enc_filepath).await
+            .with_context(|| format!("Failed to read encrypted file: {}", &enc_filepath.display()))?);
+        let container = crypto::ContainerStreamReader::read_header(&mut reader).await
+            .with_context(|| format!("Failed to read encrypted file: {}", &enc_filepath.display()))?;
+        let file_id = read_file_id(&enc_filepath).await?;
+
+        let header = container.header().clone();
+        let (data_key, bad_shares) = tokio::task::spawn_blocking(move || crypto::combine_key(&header, &file_id, &shares, &keystore))
+            .await?
+            .with_context(|| format!("Failed to decrypt file with the collected shares: {}", &enc_filepath.display()))?;
+
+        let (partial_filepath, mut writer) = create_partial_file(&orig_filepath).await?;
+        let written = container.open(&data_key, &mut reader, &mut writer).await
+            .with_context(|| format!("Failed to decrypt file: {}", &enc_filepath.display()));
+        finish_partial_file(&partial_filepath, &orig_filepath, false, written).await
+            .with_context(|| format!("Failed to write decrypted file: {}", &orig_filepath.display()))?;
+
+        Ok(RecoverStruct { decrypted_filepath: orig_filepath.display().to_string(), bad_shares })
+    }
+
+    /// Splits the key of `enc_filepath` again into `n_shares` share files of the next epoch, replacing the old share files,
+    /// for a vault file that is the vault key
+    ///
+    /// The encrypted file is left alone, the caller records the new epoch in the keystore so the old shares stop working.
+    /// Splits with sealed or passphrase protected shares are refused, the new shares would go out in the clear.
+    pub async fn refresh_file_shares(enc_filepath: PathBuf, shares: Zeroizing<Vec<crypto::Share>>, policy: shares::Policy, keystore: Option<Arc<crypto::Keystore>>) -> anyhow::Result<RefreshStruct> {
+        let orig_filepath = gen_original_filepath(&enc_filepath);
+        let keystore = keystore.ok_or_else(|| anyhow!("Unlock the keystore to refresh shares"))?;
+        let old_total = shares.first().map_or(0, |x| x.total());
+        // the new shares go out unsealed, sealed or protected custodians would quietly lose their protection
+        if old_split_is_sealed(&enc_filepath, &orig_filepath, old_total).await? {
+            return Err(anyhow!("Shares of {} are sealed to their custodians' keys or protected with their passphrases, refreshing would hand them out unprotected",
+                enc_filepath.display()));
+        }
+
+        // only the header and the id of the file are needed, its payload stays untouched
+        let new_shares = if is_vaultfile(&enc_filepath) {
+            let header = crypto::VaultHeader::decode(&tokio::fs::read(&enc_filepath).await
+                .with_context(|| format!("Failed to read vault file: {}", &enc_filepath.display()))?)
+                .with_context(|| format!("Failed to read vault file: {}", &enc_filepath.display()))?;
+            let (n_shares, k_thres, verifiable) = (policy.n_shares, policy.k_thres, policy.verifiable);
+            tokio::task::spawn_blocking(move || crypto::refresh_vault_shares(&header, &shares, n_shares, k_thres, verifiable, &keystore)).await?
+        } else {
+            let container = read_container_header(&enc_filepath).await
+                .with_context(|| format!("Failed to read encrypted file: {}", &enc_filepath.display()))?;
+            let file_id = read_file_id(&enc_filepath).await?;
+            let header = container.header().clone();
+            let (n_shares, k_thres, verifiable) = (policy.n_shares, policy.k_thres, policy.verifiable);
+            tokio::task::spawn_blocking(move || crypto::refresh_shares(&header, &file_id, &shares, n_shares, k_thres, verifiable, &keystore)).await?
+        }
+        .with_context(|| format!("Failed to refresh the shares of {}", &enc_filepath.display()))?;
+        let first = new_shares.first().ok_or_else(|| anyhow!("No shares were issued"))?;
+        info!("refreshed shares of {} to epoch {}, {} of {}", enc_filepath.display(), first.epoch(), policy.k_thres, policy.n_shares);
+
+        let share_filepaths = write_share_files(&orig_filepath, &new_shares, &policy).await?;
+        // the manifest names the holders of the old shares
+        let _ = tokio::fs::remove_file(gen_manifest_filepath(&orig_filepath)).await;
+        // leftovers of the old split would only be rejected
+        let files = policy.holdings().len();
+        for i in 1..=files.max(old_total as usize) {
+            if i > files {
+                let _ = tokio::fs::remove_file(gen_share_filepath(&orig_filepath, i)).await;
+            }
+            if i > files || !policy.words {
+                let _ = tokio::fs::remove_file(gen_share_words_filepath(&orig_filepath, i)).await;
+            }
+            if i > files || !policy.sheets {
+                let _ = tokio::fs::remove_file(gen_share_sheet_filepath(&orig_filepath, i)).await;
+            }
+        }
+
+        Ok(RefreshStruct {
+            file_id: *first.file_id(),
+            epoch: first.epoch(),
+            share_filepaths,
+        })
+    }
+
+    /// True when any share of the split of `enc_filepath` was sealed to a public key or protected with a passphrase,
+    /// as its custodian manifest, passwords vault or sealed share files next to it tell
+    async fn old_split_is_sealed(enc_filepath: &Path, orig_filepath: &Path, old_total: u16) -> anyhow::Result<bool> {
+        let manifest = read_manifest(enc_filepath.to_path_buf()).await?;
+        if manifest.iter().flat_map(|x| &x.custodians).any(|x| x.public_key.is_some() || x.passphrase) {
+            return Ok(true);
+        }
+        let mut sealed_filepaths = vec![gen_passwords_filepath(orig_filepath)];
+        sealed_filepaths.extend((1..=old_total as usize).map(|i| gen_encrypted_filepath(&gen_share_filepath(orig_filepath, i))));
+        for filepath in sealed_filepaths {
+            if tokio::fs::try_exists(&filepath).await.unwrap_or(true) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Writes the shares of every holder the policy names to `gen_share_filepath`, numbered by holder,
+    /// along with the words and printable sheet the policy asks for
+    ///
+    /// Shares sealed to a public key or protected with a passphrase are written as containers, see
+    /// `crypto::ShareBundle::seal`, protected shares go to one passwords vault when the policy asks for it.
+    ///
+    /// With a packets directory every holder's files go to a directory of their own in there, see
+    /// `custodians::packet_dir_name`, and a manifest of who holds which share goes next to the file and the packets.
+    async fn write_share_files(orig_filepath: &Path, shares: &[crypto::Share], policy: &shares::Policy) -> anyhow::Result<Vec<String>> {
+        let enc_file_name = gen_encrypted_filepath(orig_filepath).file_name()
+            .map(|x| x.display().to_string())
+            .unwrap_or_default();
+        let mut share_filepaths = Vec::with_capacity(shares.len());
+        let mut custodians = Vec::new();
+        let mut vault = Vec::new();
+        for (i, holding) in policy.holdings().into_iter().enumerate() {
+            let bundle = crypto::ShareBundle::new(shares.iter().filter(|x| holding.shares.contains(&x.id())).cloned().collect())?;
+            let packet = policy.packets_dir.as_ref().map(|_| custodians::packet_dir_name(i + 1, &holding.label));
+            // share files keep their names inside a packet
+            let base_filepath = match (&policy.packets_dir, &packet) {
+                (Some(dir), Some(packet)) => {
+                    let packet_dir = dir.join(packet);
+                    tokio::fs::create_dir_all(&packet_dir).await
+                        .with_context(|| format!("Failed to create packet directory: {}", packet_dir.display()))?;
+                    packet_dir.join(orig_filepath.file_name().unwrap_or_default())
+                }
+                _ => orig_filepath.to_path_buf(),
+            };
+            let share_filepath = gen_share_filepath(&base_filepath, i + 1);
+            let public_key = holding.holder.as_ref().and_then(|x| x.public_key);
+            let sealed = match (&public_key, policy.passwords.get(i)) {
+                (Some(public_key), _) => Some(bundle.seal(public_key)
+                    .with_context(|| format!("Failed to seal the share of {} to {public_key}", holding.label))?),
+                (None, Some(passphrase)) => {
+                    // key derivation is deliberately slow, keep it off the async workers
+                    let (bundle, passphrase) = (bundle.clone(), passphrase.clone());
+                    Some(tokio::task::spawn_blocking(move || bundle.protect(&passphrase, crypto::calibrated_cost()?)).await?
+                        .with_context(|| format!("Failed to protect the share of {} with their passphrase", holding.label))?)
+                }
+                (None, None) => None,
+            };
+            let in_clear = sealed.is_none();
+            match sealed {
+                Some(sealed) if policy.vault => vault.push((holding.label.clone(), sealed)),
+                Some(sealed) => {
+                    // opened like any file encrypted to the custodian, decrypting it gives back `share_filepath`
+                    let sealed_filepath = gen_encrypted_filepath(&share_filepath);
+                    replace_private_file(&sealed_filepath, &sealed).await
+                        .with_context(|| format!("Failed to write share file: {}", &sealed_filepath.display()))?;
+                    share_filepaths.push(sealed_filepath.display().to_string());
+                }
+                None => {
+                    replace_private_file(&share_filepath, &bundle.encode()?).await
+                        .with_context(|| format!("Failed to write share file: {}", &share_filepath.display()))?;
+                    share_filepaths.push(share_filepath.display().to_string());
+                }
+            }
+            // words and sheets would carry a sealed share in the clear
+            if !in_clear && (policy.words || policy.sheets) {
+                info!("  no words or sheet for {}, their share is sealed", holding.label);
+            } else if policy.words {
+                let words_filepath = gen_share_words_filepath(&base_filepath, i + 1);
+                let mut mnemonic = bundle.encode_to_mnemonic()?;
+                mnemonic.push('\n');
+                replace_private_file(&words_filepath, mnemonic.as_bytes()).await
+                    .with_context(|| format!("Failed to write share file: {}", &words_filepath.display()))?;
+                share_filepaths.push(words_filepath.display().to_string());
+            }
+            if policy.sheets && in_clear {
+                let sheet_filepath = gen_share_sheet_filepath(&base_filepath, i + 1);
+                let sheet = sheet::share_sheet(&bundle, &holding.label, &enc_file_name)?;
+                replace_private_file(&sheet_filepath, sheet.as_bytes()).await
+                    .with_context(|| format!("Failed to write share sheet: {}", &sheet_filepath.display()))?;
+                share_filepaths.push(sheet_filepath.display().to_string());
+            }
+            custodians.push(custodians::Custodian {
+                name: holding.label,
+                contact: holding.holder.as_ref().map(|x| x.contact.clone()).unwrap_or_default(),
+                public_key,
+                passphrase: public_key.is_none() && !in_clear,
+                shares: holding.shares,
+                packet: packet.unwrap_or_default(),
+            });
+        }
+
+        if !vault.is_empty() {
+            let vault_filepath = gen_passwords_filepath(orig_filepath);
+            replace_private_file(&vault_filepath, &crypto::PasswordVault::new(vault)?.encode()).await
+                .with_context(|| format!("Failed to write passwords vault: {}", &vault_filepath.display()))?;
+            share_filepaths.push(vault_filepath.display().to_string());
+        }
+
+        if let (Some(dir), Some(first)) = (&policy.packets_dir, shares.first()) {
+            let manifest = custodians::Manifest {
+                file_name: enc_file_name,
+                file_id: *first.file_id(),
+                epoch: first.epoch(),
+                policy: policy.to_string(),
+                custodians,
+            }.encode();
+            let manifest_filepath = gen_manifest_filepath(orig_filepath);
+            for filepath in [manifest_filepath.clone(), dir.join(manifest_filepath.file_name().unwrap_or_default())] {
+                write_bin_file(&filepath, manifest.as_bytes()).await
+                    .with_context(|| format!("Failed to write custodian manifest: {}", &filepath.display()))?;
+                share_filepaths.push(filepath.display().to_string());
+            }
+        }
+        Ok(share_filepaths)
+    }
+
+    /// Reads the custodian manifest of `enc_filepath`, None when it was split without one
+    pub async fn read_manifest(enc_filepath: PathBuf) -> anyhow::Result<Option<custodians::Manifest>> {
+        let manifest_filepath = gen_manifest_filepath(&gen_original_filepath(&enc_filepath));
+        match tokio::fs::read_to_string(&manifest_filepath).await {
+            Ok(manifest) => Ok(Some(custodians::Manifest::parse(&manifest)
+                .with_context(|| format!("Failed to read custodian manifest: {}", manifest_filepath.display()))?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read custodian manifest: {}", manifest_filepath.display())),
+        }
+    }
+
+    /// Output is streamed into a temporary sibling first, so a failure never leaves a half written file
+    async fn create_partial_file(filepath: &Path) -> anyhow::Result<(PathBuf, BufWriter<File>)> {
+        let partial_filepath = gen_partial_filepath(filepath);
+        let file = File::create(&partial_filepath).await
+            .with_context(|| format!("Failed to create file: {}", &partial_filepath.display()))?;
+        Ok((partial_filepath, BufWriter::new(file)))
+    }
+
+    /// Moves a completely written partial file into place, or removes it when writing failed
+    async fn finish_partial_file(partial_filepath: &Path, filepath: &Path, overwrite: bool, written: anyhow::Result<u64>) -> anyhow::Result<u64> {
+        let res = match written {
+            Ok(_) if !overwrite && tokio::fs::try_exists(filepath).await.unwrap_or(true) => {
+                Err(anyhow!("File already exists: {}", filepath.display()))
+            }
+            Ok(len) => tokio::fs::rename(partial_filepath, filepath).await
+                .map(|_| len)
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        if res.is_err() {
+            let _ = tokio::fs::remove_file(partial_filepath).await;
+        }
+        res
+    }
+
+    fn gen_encrypted_filepath(pb: &Path) -> PathBuf {
         let mut npb = PathBuf::new();
         if let Some(parent) = pb.parent() {
             npb = npb.join(parent);
@@ -547,7 +1977,63 @@ mod foo {
         npb
     }
 
-    fn gen_key_filepath(pb: &PathBuf) -> PathBuf {
+    /// Reverses `gen_encrypted_filepath`, dropping the `_enc` suffix from the file stem
+    fn gen_original_filepath(pb: &Path) -> PathBuf {
+        let mut npb = PathBuf::new();
+        if let Some(parent) = pb.parent() {
+            npb = npb.join(parent);
+        }
+        if let Some(file_stem) = pb.file_stem() {
+            let file_stem = file_stem.display().to_string();
+            npb = npb.join(file_stem.strip_suffix("_enc").unwrap_or(&file_stem));
+        }
+        if let Some(extension) = pb.extension() {
+            let _ = npb.set_extension(extension);
+        }
+        npb
+    }
+
+    fn gen_partial_filepath(pb: &Path) -> PathBuf {
+        let mut file_name = pb.file_name().map(|x| x.to_os_string()).unwrap_or_default();
+        file_name.push(".partial");
+        pb.with_file_name(file_name)
+    }
+
+    fn gen_signature_filepath(pb: &Path) -> PathBuf {
+        let mut file_name = pb.file_name().map(|x| x.to_os_string()).unwrap_or_default();
+        file_name.push(".sig");
+        pb.with_file_name(file_name)
+    }
+
+    /// Share file `number` (1 based, one per holder) of the key for `pb`, e.g. `notes_share_2.bin` for `notes.txt`
+    fn gen_share_filepath(pb: &Path, number: usize) -> PathBuf {
+        let file_stem = pb.file_stem().map(|x| x.display().to_string()).unwrap_or_default();
+        pb.with_file_name(format!("{file_stem}_share_{number}.bin"))
+    }
+
+    /// Share `number` written as words, e.g. `notes_share_2.txt` for `notes.txt`
+    fn gen_share_words_filepath(pb: &Path, number: usize) -> PathBuf {
+        gen_share_filepath(pb, number).with_extension("txt")
+    }
+
+    /// Printable sheet of share `number`, e.g. `notes_share_2.html` for `notes.txt`
+    fn gen_share_sheet_filepath(pb: &Path, number: usize) -> PathBuf {
+        gen_share_filepath(pb, number).with_extension("html")
+    }
+
+    /// Who holds which share of the key for `pb`, e.g. `notes_custodians.txt` for `notes.txt`
+    fn gen_manifest_filepath(pb: &Path) -> PathBuf {
+        let file_stem = pb.file_stem().map(|x| x.display().to_string()).unwrap_or_default();
+        pb.with_file_name(format!("{file_stem}_custodians.txt"))
+    }
+
+    /// Passwords vault holding every custodian's protected share of the key for `pb`, e.g. `notes_passwords.bin` for `notes.txt`
+    fn gen_passwords_filepath(pb: &Path) -> PathBuf {
+        let file_stem = pb.file_stem().map(|x| x.display().to_string()).unwrap_or_default();
+        pb.with_file_name(format!("{file_stem}_passwords.bin"))
+    }
+
+    fn gen_key_filepath(pb: &Path) -> PathBuf {
         let mut npb = PathBuf::new();
         if let Some(parent) = pb.parent() {
             npb = npb.join(parent);
@@ -561,7 +2047,7 @@ mod foo {
         npb
     }
 
-    fn is_encrypted(pb: &PathBuf) -> bool {
+    fn is_encrypted(pb: &Path) -> bool {
         if let Some(file_stem) = pb.file_stem() {
             file_stem.display().to_string().ends_with("_enc")
         } else {
@@ -569,7 +2055,7 @@ mod foo {
         }
     }
 
-    fn is_keyfile(pb: &PathBuf) -> bool {
+    fn is_keyfile(pb: &Path) -> bool {
         if let Some(file_stem) = pb.file_stem() {
             file_stem.display().to_string().ends_with("_key")
         } else {
@@ -577,43 +2063,139 @@ mod foo {
         }
     }
 
-    async fn write_file(filepath: &PathBuf, content: &str) -> Option<bool> {
-        (async move || {
-            let mut file = File::create(filepath).await?;
-            file. write_all(content.as_bytes()).await?;
-            file.flush().await?;
+    fn is_sharefile(pb: &Path) -> bool {
+        if let Some(file_stem) = pb.file_stem() {
+            let file_stem = file_stem.display().to_string();
+            file_stem.rsplit_once("_share_")
+                .is_some_and(|(_, number)| !number.is_empty() && number.chars().all(|x| x.is_ascii_digit()))
+        } else {
+            false
+        }
+    }
 
-            Ok::<bool, Box<dyn std::error::Error>>(true)
-        })()
-        .await.ok()
+    /// Describes the vault of the directory it is in, see `crypto::VaultHeader`
+    fn is_vaultfile(pb: &Path) -> bool {
+        pb.file_name().is_some_and(|x| x == VAULT_FILE_NAME)
     }
 
-    async fn write_bin_file(filepath: &PathBuf, content: &[u8]) -> Option<bool> {
-        (async move || {
+    fn is_sigfile(pb: &Path) -> bool {
+        pb.extension().is_some_and(|x| x == "sig")
+    }
+
+    #[allow(dead_code)]
+    async fn write_file(filepath: &Path, content: &str) -> Option<bool> {
+        async move {
             let mut file = File::create(filepath).await?;
-            file. write_all(content).await?;
+            file. write_all(content.as_bytes()).await?;
             file.flush().await?;
 
             Ok::<bool, Box<dyn std::error::Error>>(true)
-        })()
+        }
         .await.ok()
     }
 
+    async fn write_bin_file(filepath: &Path, content: &[u8]) -> std::io::Result<()> {
+        let mut file = File::create(filepath).await?;
+        file.write_all(content).await?;
+        file.flush().await
+    }
+
     #[derive(Debug, Clone)]
     pub enum Message {
         Encrypt,
+        EncryptPassphrase,
+        EncryptWithPassphrase(crypto::Passphrase),
+        EncryptForRecipient,
+        EncryptToRecipients(recipients::Selection),
+        EncryptShares,
+        /// n shares, k threshold, with pedersen commitments
+        EncryptWithShares(shares::Policy),
         EncryptResult(Result<EncryptStruct, String>),
         Decrypt,
+        DecryptOverwrite,
+        DecryptWithPassphrase(crypto::Passphrase, bool),
+        DecryptResult(Result<DecryptStruct, String>),
+        OverwriteRequested(String),
+        PassphraseRequested(PassphrasePurpose),
+        RecipientRequested,
+        SharesRequested,
+        Recover,
+        RecoveryRequested,
+        ManageRecipients,
+        RecipientsLoaded(Result<Vec<crypto::Stanza>, String>),
+        ChangeRecipients(recipients::Changes),
+        RecipientsChanged(Result<usize, String>),
+        Sign,
+        SignResult(Result<String, String>),
+        CheckSignature,
+        SignatureChecked(Result<crypto::SignatureStatus, String>),
         Delete,
         FileSystemUpdated,
         LinkClicked(String),
     }
 
+    /// What a passphrase typed into the prompt will be used for
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum PassphrasePurpose {
+        Encrypt,
+        Decrypt { overwrite: bool },
+    }
+
+    /// How the data key of a newly encrypted file is protected
+    enum Protection {
+        MasterKey,
+        Passphrase(crypto::Passphrase),
+        Recipients(recipients::Selection),
+        /// the file goes into an unlocked project vault
+        Vault(crypto::VaultKey),
+    }
+
     #[derive(Debug, Clone)]
     pub struct EncryptStruct {
         original_filepath: String,
         encrypted_filepath: String,
-        key_filepath: String,
+        /// None when the wrapped keys live in the file's recipient stanzas
+        key_filepath: Option<String>,
+        /// signing key of a sign-then-encrypt file
+        signer: Option<String>,
+        /// files holding the key shares when the key was split
+        share_filepaths: Vec<String>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct DecryptStruct {
+        encrypted_filepath: String,
+        /// None when the wrapped keys live in the file's recipient stanzas
+        key_filepath: Option<String>,
+        decrypted_filepath: String,
+        /// present when the sender signed before encrypting
+        signature: Option<crypto::SignatureStatus>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct RecoverStruct {
+        pub decrypted_filepath: String,
+        /// indices of shares that disagreed with the rest and were left out
+        pub bad_shares: Vec<crypto::ShareId>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct VaultStruct {
+        pub vault_dir: PathBuf,
+        /// kept for the session, every file in the vault opens with it
+        pub vault_key: crypto::VaultKey,
+        /// files holding the vault key shares when the vault was just made
+        pub share_filepaths: Vec<String>,
+        /// indices of shares that disagreed with the rest and were left out
+        pub bad_shares: Vec<crypto::ShareId>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct RefreshStruct {
+        pub file_id: crypto::FileId,
+        /// recorded in the keystore, shares of older epochs are rejected from then on
+        pub epoch: u32,
+        pub share_filepaths: Vec<String>,
     }
 }
 
@@ -0,0 +1,153 @@
+use iced::{
+    Element, alignment::Vertical, color, widget::{Text, button, column, row, text, text_input}
+};
+use iced_font_awesome as ifa;
+use iced_modern_theme::Modern;
+use iced_optional_element_shim::to_elem;
+use zeroize::Zeroize;
+
+use crate::crypto::Passphrase;
+use crate::foo::PassphrasePurpose;
+
+/// Asks for a passphrase, twice when choosing a new one so typos don't lock anything away
+pub struct PassphrasePrompt {
+    pub purpose: PromptPurpose,
+    passphrase: String,
+    confirm: String,
+    error: Option<String>,
+}
+
+/// What the passphrase will unlock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptPurpose {
+    /// for the file at this file list index
+    File(usize, PassphrasePurpose),
+    CreateKeystore,
+    UnlockKeystore,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    PassphraseChanged(String),
+    ConfirmChanged(String),
+    Submit,
+    Cancel,
+}
+
+/// What the owner of the prompt should do after an update
+pub enum Outcome {
+    Pending,
+    Submitted(Passphrase),
+    Cancelled,
+}
+
+impl PassphrasePrompt {
+    pub fn new(purpose: PromptPurpose) -> Self {
+        Self {
+            purpose,
+            passphrase: String::new(),
+            confirm: String::new(),
+            error: None,
+        }
+    }
+
+    /// Shows `error` under the inputs, e.g. after a wrong passphrase
+    pub fn with_error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    fn needs_confirm(&self) -> bool {
+        matches!(self.purpose, PromptPurpose::File(_, PassphrasePurpose::Encrypt) | PromptPurpose::CreateKeystore)
+    }
+
+    pub fn update(&mut self, message: Message) -> Outcome {
+        match message {
+            Message::PassphraseChanged(passphrase) => {
+                self.passphrase.zeroize();
+                self.passphrase = passphrase;
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::ConfirmChanged(confirm) => {
+                self.confirm.zeroize();
+                self.confirm = confirm;
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::Submit => {
+                if self.passphrase.is_empty() {
+                    self.error = Some("Passphrase must not be empty".to_string());
+                    Outcome::Pending
+                } else if self.needs_confirm() && self.passphrase != self.confirm {
+                    self.error = Some("Passphrases do not match".to_string());
+                    Outcome::Pending
+                } else {
+                    Outcome::Submitted(Passphrase::new(std::mem::take(&mut self.passphrase)))
+                }
+            }
+            Message::Cancel => Outcome::Cancelled,
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let label = match self.purpose {
+            PromptPurpose::File(_, PassphrasePurpose::Encrypt) => "Choose a passphrase for the new file:",
+            PromptPurpose::File(_, PassphrasePurpose::Decrypt { .. }) => "Enter the passphrase for this file:",
+            PromptPurpose::CreateKeystore => "No keystore yet. Choose a passphrase to protect your new master key:",
+            PromptPurpose::UnlockKeystore => "Enter the passphrase for your keystore:",
+        };
+        let submit_label = match self.purpose {
+            PromptPurpose::File(_, PassphrasePurpose::Encrypt) => "encrypt",
+            PromptPurpose::File(_, PassphrasePurpose::Decrypt { .. }) => "decrypt",
+            PromptPurpose::CreateKeystore => "create",
+            PromptPurpose::UnlockKeystore => "unlock",
+        };
+        let passphrase_input = text_input("Passphrase", &self.passphrase)
+            .secure(true)
+            .style(Modern::text_input())
+            .on_input(Message::PassphraseChanged)
+            .on_submit(Message::Submit)
+            .width(300);
+
+        column!(
+            row!(
+                ifa::fa_icon_solid("key").size(16.0).color(color!(0, 255, 0)),
+                text(label),
+            ).spacing(10).align_y(Vertical::Center),
+            row!(
+                passphrase_input,
+                if self.needs_confirm() {
+                    to_elem(Some(text_input("Confirm passphrase", &self.confirm)
+                        .secure(true)
+                        .style(Modern::text_input())
+                        .on_input(Message::ConfirmChanged)
+                        .on_submit(Message::Submit)
+                        .width(300)))
+                } else {
+                    to_elem::<Message, Text>(None)
+                },
+                button(text(submit_label))
+                    .style(Modern::primary_button())
+                    .on_press(Message::Submit),
+                button(text("cancel"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::Cancel),
+            ).spacing(10).align_y(Vertical::Center),
+            if let Some(error) = &self.error {
+                to_elem(Some(text(error).color(color!(255, 80, 80))))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+        )
+        .spacing(10)
+        .into()
+    }
+}
+
+impl Drop for PassphrasePrompt {
+    fn drop(&mut self) {
+        self.passphrase.zeroize();
+        self.confirm.zeroize();
+    }
+}
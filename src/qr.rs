@@ -0,0 +1,155 @@
+use anyhow::anyhow;
+use zeroize::Zeroizing;
+
+/// Larger images are scaled down before looking for the code
+const MAX_IMAGE_DIM: u32 = 2400;
+
+/// Whether `bytes` look like a PNG or JPEG image
+pub fn is_image(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x89PNG\r\n\x1a\n") || bytes.starts_with(&[0xff, 0xd8, 0xff])
+}
+
+/// Contents of the QR code in a PNG or JPEG image, a photo or a scan of a printed code
+///
+/// Everything happens locally. A code that can't be found or read gives an error naming what went wrong.
+pub fn decode_image(bytes: &[u8]) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| anyhow!("Unable to read the image: {e}"))?
+        .into_luma8();
+    let image = if image.width().max(image.height()) > MAX_IMAGE_DIM {
+        let scale = MAX_IMAGE_DIM as f64 / image.width().max(image.height()) as f64;
+        image::imageops::thumbnail(
+            &image,
+            ((image.width() as f64 * scale) as u32).max(1),
+            ((image.height() as f64 * scale) as u32).max(1),
+        )
+    } else {
+        image
+    };
+
+    let mut prepared = rqrr::PreparedImage::prepare(image);
+    let grids = prepared.detect_grids();
+    // a photo may show more than the code, the first one that reads wins
+    let mut first_err = None;
+    for grid in grids {
+        let mut content = Zeroizing::new(Vec::new());
+        match grid.decode_to(&mut *content) {
+            Ok(_) => return Ok(content),
+            Err(e) => {
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+    match first_err {
+        Some(e) => Err(anyhow!("Found a QR code but can't read it: {e}")),
+        None => Err(anyhow!("No QR code found in the image")),
+    }
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Share;
+    use image::{GrayImage, ImageFormat, Luma};
+    use qrcode::{Color, EcLevel, QrCode};
+    use std::io::Cursor;
+
+    /// Modules of a code as a PNG, `scale` pixels per module, turned by `angle` radians
+    fn render(colors: &[Color], scale: f64, angle: f64) -> anyhow::Result<Vec<u8>> {
+        let width = (colors.len() as f64).sqrt() as usize;
+        let side = ((width + 8) as f64 * scale * 1.5) as u32;
+        let centre = side as f64 / 2.0;
+        let (sin, cos) = angle.sin_cos();
+        let image = GrayImage::from_fn(side, side, |x, y| {
+            let (dx, dy) = (x as f64 + 0.5 - centre, y as f64 + 0.5 - centre);
+            let u = (dx * cos + dy * sin) / scale + width as f64 / 2.0;
+            let v = (-dx * sin + dy * cos) / scale + width as f64 / 2.0;
+            let inside = u >= 0.0 && v >= 0.0 && (u as usize) < width && (v as usize) < width;
+            let dark = inside && colors[v as usize * width + u as usize] == Color::Dark;
+            Luma([if dark { 20 } else { 235 }])
+        });
+        encode_png(&image)
+    }
+
+    fn encode_png(image: &GrayImage) -> anyhow::Result<Vec<u8>> {
+        let mut png = Cursor::new(vec![]);
+        image.write_to(&mut png, ImageFormat::Png)?;
+        Ok(png.into_inner())
+    }
+
+    #[test]
+    fn test_decode_image() -> anyhow::Result<()> {
+        for (data, level) in [
+            (b"hello".to_vec(), EcLevel::M),
+            ((0..=255u8).collect(), EcLevel::M),
+            ((0..600).map(|x| (x * 7) as u8).collect(), EcLevel::L),
+            (b"0123456789 AND SOME ALPHANUMERIC TEXT 0123456789".to_vec(), EcLevel::H),
+        ] {
+            let code = QrCode::with_error_correction_level(&data, level)?;
+            for scale in [3.0, 4.5] {
+                assert_eq!(data, *decode_image(&render(&code.to_colors(), scale, 0.0)?)?, "{} bytes at scale {scale}", data.len());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_turned_image() -> anyhow::Result<()> {
+        let data: Vec<u8> = (0..120).collect();
+        let code = QrCode::with_error_correction_level(&data, EcLevel::M)?;
+        for angle in [0.3, std::f64::consts::FRAC_PI_2, 2.5, -0.7] {
+            assert_eq!(data, *decode_image(&render(&code.to_colors(), 5.0, angle)?)?, "angle {angle}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_share_photo() -> anyhow::Result<()> {
+        let shares = Share::split_verifiable(&[7; 40], 5, 3, [1; 16])?;
+        let code = QrCode::with_error_correction_level(shares[2].encode(), EcLevel::M)?;
+        let png = render(&code.to_colors(), 4.0, 0.2)?;
+        let mut jpeg = Cursor::new(vec![]);
+        image::load_from_memory(&png)?.write_to(&mut jpeg, ImageFormat::Jpeg)?;
+        let jpeg = jpeg.into_inner();
+        assert!(is_image(&jpeg));
+        assert_eq!(shares[2], Share::decode(&decode_image(&jpeg)?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_corrects_damage() -> anyhow::Result<()> {
+        let data = b"a share with a few modules smudged".to_vec();
+        let code = QrCode::with_version(&data, qrcode::Version::Normal(3), EcLevel::M)?;
+        let mut colors = code.to_colors();
+        for (x, y) in [(12, 12), (13, 14), (20, 10), (15, 22), (10, 26), (26, 14)] {
+            colors[y * code.width() + x] = !colors[y * code.width() + x];
+        }
+        assert_eq!(data, *decode_image(&render(&colors, 4.0, 0.0)?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_errors() -> anyhow::Result<()> {
+        let png = encode_png(&GrayImage::from_pixel(200, 200, Luma([255])))?;
+        assert!(is_image(&png));
+        assert!(decode_image(&png).unwrap_err().to_string().contains("No QR code found"));
+
+        assert!(!is_image(b"not an image"));
+        assert!(decode_image(b"not an image").unwrap_err().to_string().contains("Unable to read the image"));
+        assert!(decode_image(&png[..png.len() / 2]).is_err(), "cut off file");
+
+        // the top half of a code still shows all three finders, but can't be read
+        let code = QrCode::with_error_correction_level(b"only half of this survives", EcLevel::L)?;
+        let image = image::load_from_memory(&render(&code.to_colors(), 4.0, 0.0)?)?.into_luma8();
+        let half = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            if y > image.height() / 2 && x > image.width() / 2 { Luma([235]) } else { *image.get_pixel(x, y) }
+        });
+        assert!(decode_image(&encode_png(&half)?).is_err());
+
+        Ok(())
+    }
+}
+
+// #endregion ----------------
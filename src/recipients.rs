@@ -0,0 +1,235 @@
+use iced::{
+    Element, alignment::Vertical, color, widget::{Column, Text, button, checkbox, column, row, text, text_input}
+};
+use iced_font_awesome as ifa;
+use iced_modern_theme::Modern;
+use iced_optional_element_shim::to_elem;
+
+use crate::crypto::{KeyWrapMethod, Stanza, X25519Recipient};
+
+/// Picks who a file is encrypted for, or changes who can open an already encrypted file
+pub struct RecipientPrompt {
+    /// file list index the recipients are for
+    pub index: usize,
+    /// stanzas of the file when changing recipients, None when encrypting a new file
+    existing: Option<Vec<Stanza>>,
+    /// key ids of existing stanzas marked for removal
+    removed: Vec<Vec<u8>>,
+    added: Vec<X25519Recipient>,
+    include_self: bool,
+    sign: bool,
+    public_key: String,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    PublicKeyChanged(String),
+    AddPublicKey,
+    RemoveAdded(usize),
+    ToggleExisting(usize),
+    IncludeSelfToggled(bool),
+    SignToggled(bool),
+    Submit,
+    Cancel,
+}
+
+/// Recipients picked for a new file
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub public_keys: Vec<X25519Recipient>,
+    /// also wrap the data key with our own master key
+    pub include_self: bool,
+    /// sign the plaintext with our signing key before encrypting
+    pub sign: bool,
+}
+
+/// Changes to the recipients of an existing file
+#[derive(Debug, Clone)]
+pub struct Changes {
+    pub add: Vec<X25519Recipient>,
+    /// key ids of the stanzas to drop
+    pub remove: Vec<Vec<u8>>,
+}
+
+/// What the owner of the prompt should do after an update
+pub enum Outcome {
+    Pending,
+    Encrypt(Selection),
+    Change(Changes),
+    Cancelled,
+}
+
+impl RecipientPrompt {
+    pub fn new(index: usize) -> Self {
+        Self {
+            index,
+            existing: None,
+            removed: Vec::new(),
+            added: Vec::new(),
+            include_self: true,
+            sign: true,
+            public_key: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn edit(index: usize, stanzas: Vec<Stanza>) -> Self {
+        Self {
+            existing: Some(stanzas),
+            include_self: false,
+            ..Self::new(index)
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> Outcome {
+        match message {
+            Message::PublicKeyChanged(public_key) => {
+                self.public_key = public_key;
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::AddPublicKey => {
+                if let Err(e) = self.add_typed_key() {
+                    self.error = Some(e);
+                }
+                Outcome::Pending
+            }
+            Message::RemoveAdded(i) => {
+                if i < self.added.len() {
+                    self.added.remove(i);
+                }
+                Outcome::Pending
+            }
+            Message::ToggleExisting(i) => {
+                if let Some(stanza) = self.existing.as_ref().and_then(|x| x.get(i)) {
+                    if let Some(pos) = self.removed.iter().position(|x| *x == stanza.key_id) {
+                        self.removed.remove(pos);
+                    } else {
+                        self.removed.push(stanza.key_id.clone());
+                    }
+                }
+                Outcome::Pending
+            }
+            Message::IncludeSelfToggled(include_self) => {
+                self.include_self = include_self;
+                Outcome::Pending
+            }
+            Message::SignToggled(sign) => {
+                self.sign = sign;
+                Outcome::Pending
+            }
+            Message::Submit => {
+                // a key still sitting in the input counts as added
+                if !self.public_key.trim().is_empty()
+                    && let Err(e) = self.add_typed_key() {
+                    self.error = Some(e);
+                    return Outcome::Pending;
+                }
+                match &self.existing {
+                    None if self.added.is_empty() && !self.include_self => {
+                        self.error = Some("Add at least one recipient".to_string());
+                        Outcome::Pending
+                    }
+                    None => Outcome::Encrypt(Selection {
+                        public_keys: self.added.clone(),
+                        include_self: self.include_self,
+                        sign: self.sign,
+                    }),
+                    Some(_) => Outcome::Change(Changes {
+                        add: self.added.clone(),
+                        remove: self.removed.clone(),
+                    }),
+                }
+            }
+            Message::Cancel => Outcome::Cancelled,
+        }
+    }
+
+    fn add_typed_key(&mut self) -> Result<(), String> {
+        let recipient: X25519Recipient = self.public_key.parse().map_err(|e| format!("{e}"))?;
+        if !self.added.contains(&recipient) {
+            self.added.push(recipient);
+        }
+        self.public_key.clear();
+        Ok(())
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let label = if self.existing.is_some() {
+            "Recipients of this file:"
+        } else {
+            "Encrypt for these recipients:"
+        };
+        let submit_label = if self.existing.is_some() { "save" } else { "encrypt" };
+
+        let existing = self.existing.iter().flatten().enumerate().map(|(i, stanza)| {
+            let removed = self.removed.contains(&stanza.key_id);
+            row!(
+                text(format!("{} {}", method_label(stanza.method), hex::encode(&stanza.key_id)))
+                    .color_maybe(removed.then_some(color!(128, 128, 128))),
+                button(text(if removed { "keep" } else { "remove" }))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::ToggleExisting(i)),
+            ).spacing(10).align_y(Vertical::Center).into()
+        });
+        let added = self.added.iter().enumerate().map(|(i, recipient)| {
+            row!(
+                text(format!("+ {recipient}")),
+                button(text("remove"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::RemoveAdded(i)),
+            ).spacing(10).align_y(Vertical::Center).into()
+        });
+
+        column!(
+            row!(
+                ifa::fa_icon_solid("users").size(16.0).color(color!(0, 255, 0)),
+                text(label),
+            ).spacing(10).align_y(Vertical::Center),
+            Column::with_children(existing.chain(added)).spacing(5),
+            if self.existing.is_none() {
+                to_elem(Some(row!(
+                    checkbox("include my master key", self.include_self)
+                        .on_toggle(Message::IncludeSelfToggled),
+                    checkbox("sign with my key", self.sign)
+                        .on_toggle(Message::SignToggled),
+                ).spacing(20)))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            row!(
+                text_input("x25519:...", &self.public_key)
+                    .style(Modern::text_input())
+                    .on_input(Message::PublicKeyChanged)
+                    .on_submit(Message::AddPublicKey)
+                    .width(620),
+                button(text("add"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::AddPublicKey),
+                button(text(submit_label))
+                    .style(Modern::primary_button())
+                    .on_press(Message::Submit),
+                button(text("cancel"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::Cancel),
+            ).spacing(10).align_y(Vertical::Center),
+            if let Some(error) = &self.error {
+                to_elem(Some(text(error).color(color!(255, 80, 80))))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+        )
+        .spacing(10)
+        .into()
+    }
+}
+
+fn method_label(method: KeyWrapMethod) -> &'static str {
+    match method {
+        KeyWrapMethod::MasterKey => "master key",
+        KeyWrapMethod::X25519 => "public key",
+        KeyWrapMethod::Passphrase => "passphrase",
+        _ => "other",
+    }
+}
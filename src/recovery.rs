@@ -0,0 +1,594 @@
+use std::{path::PathBuf, sync::Arc};
+
+use iced::{
+    Element, Task, alignment::Vertical, color, widget::{Column, Row, Text, button, checkbox, column, row, text, text_input}
+};
+use iced_font_awesome as ifa;
+use iced_modern_theme::Modern;
+use iced_optional_element_shim::to_elem;
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::crypto::{self, Keystore, Passphrase, PasswordVault, Share, ShareBundle, ShareId};
+use crate::custodians::Manifest;
+use crate::foo::{self, RecoverStruct, RefreshStruct, VaultStruct};
+use crate::qr;
+use crate::shares::{parse_policy, Policy};
+
+/// Collects shares of a split file key until enough are present to decrypt the file
+///
+/// Shares only live here, they are wiped when the screen is dropped.
+/// Enough shares can also be traded for a fresh set under a new policy, see `foo::refresh_file_shares`.
+/// For a project vault the shares unlock the vault key instead, the owner keeps it for the session.
+pub struct RecoveryScreen {
+    pub enc_filepath: PathBuf,
+    /// `enc_filepath` is a vault file, see `foo::create_vault`
+    vault: bool,
+    shares: Zeroizing<Vec<Share>>,
+    pasted: Zeroizing<String>,
+    /// policy of the shares issued on refresh
+    new_n_shares: String,
+    new_k_thres: String,
+    new_verifiable: bool,
+    new_words: bool,
+    new_sheets: bool,
+    busy: bool,
+    error: Option<String>,
+    recovered: Option<RecoverStruct>,
+    refreshed: Option<RefreshStruct>,
+    /// who holds which share, when the file was split with a manifest
+    manifest: Option<Manifest>,
+    /// shares protected with their custodian's passphrase, waiting for them to type it
+    locked: Vec<Locked>,
+}
+
+/// A share protected with `ShareBundle::protect`, from a passwords vault or a file of its own
+struct Locked {
+    label: String,
+    protected: Vec<u8>,
+    passphrase: Zeroizing<String>,
+    busy: bool,
+}
+
+/// What a dropped or picked file turned out to hold
+#[derive(Debug, Clone)]
+pub enum Loaded {
+    Bundle(ShareBundle),
+    /// label and bytes of every protected share in it
+    Locked(Vec<(String, Vec<u8>)>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    PastedChanged(String),
+    AddPasted,
+    PickFile,
+    FilePicked(Option<PathBuf>),
+    FileDropped(PathBuf),
+    ShareLoaded(Result<Loaded, String>),
+    RemoveShare(ShareId),
+    LockedPassphraseChanged(String, String),
+    /// the custodian of the labelled share typed their passphrase
+    Unlock(String),
+    Unlocked(String, Result<ShareBundle, String>),
+    Decrypt,
+    Decrypted(Result<RecoverStruct, String>),
+    /// the owner keeps the vault key
+    VaultUnlocked(Result<VaultStruct, String>),
+    NewSharesChanged(String),
+    NewThresholdChanged(String),
+    NewVerifiableToggled(bool),
+    NewWordsToggled(bool),
+    NewSheetsToggled(bool),
+    Refresh,
+    /// the owner records the new epoch in the keystore
+    Refreshed(Result<RefreshStruct, String>),
+    ManifestLoaded(Result<Option<Manifest>, String>),
+    Close,
+}
+
+impl RecoveryScreen {
+    pub fn new(enc_filepath: PathBuf) -> Self {
+        Self {
+            enc_filepath,
+            vault: false,
+            shares: Zeroizing::new(Vec::new()),
+            pasted: Zeroizing::new(String::new()),
+            new_n_shares: "5".to_string(),
+            new_k_thres: "3".to_string(),
+            new_verifiable: true,
+            new_words: false,
+            new_sheets: false,
+            busy: false,
+            error: None,
+            recovered: None,
+            refreshed: None,
+            manifest: None,
+            locked: Vec::new(),
+        }
+    }
+
+    /// Collects shares of the key of the vault described by `vault_filepath`
+    pub fn for_vault(vault_filepath: PathBuf) -> Self {
+        Self { vault: true, ..Self::new(vault_filepath) }
+    }
+
+    /// Reads the custodian manifest, if the file has one
+    pub fn load_manifest(&self) -> Task<Message> {
+        let enc_filepath = self.enc_filepath.clone();
+        Task::future(async move {
+            Message::ManifestLoaded(foo::read_manifest(enc_filepath).await.map_err(|e| format!("{e:#}")))
+        })
+    }
+
+    /// Key id and name of every custodian with a public key, to tell whose sealed share was dropped
+    fn custodian_keys(&self) -> Vec<(Vec<u8>, String)> {
+        self.manifest.iter()
+            .flat_map(|x| &x.custodians)
+            .filter_map(|x| x.public_key.map(|key| (key.id(), x.name.clone())))
+            .collect()
+    }
+
+    /// The manifest, unless it describes another split than the collected shares
+    fn current_manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref().filter(|manifest| {
+            self.shares.first().is_none_or(|x| *x.file_id() == manifest.file_id && x.epoch() == manifest.epoch)
+        })
+    }
+
+    /// Shares needed, known once the first share is in
+    fn threshold(&self) -> Option<usize> {
+        self.shares.first().map(|x| x.threshold() as usize)
+    }
+
+    /// Members collected and needed of every group, for shares split across groups,
+    /// how many a group needs is known once one of its members is in
+    fn group_progress(&self) -> Vec<(u16, usize, Option<usize>)> {
+        let Some(group) = self.shares.first().and_then(|x| x.group()) else {
+            return Vec::new();
+        };
+        (1..=group.total).map(|index| {
+            let members: Vec<&Share> = self.shares.iter().filter(|x| x.id().group == Some(index)).collect();
+            (index, members.len(), members.first().map(|x| x.threshold() as usize))
+        }).collect()
+    }
+
+    /// Groups with enough of their members in
+    fn groups_complete(&self) -> usize {
+        self.group_progress().iter().filter(|(_, have, need)| need.is_some_and(|k| *have >= k)).count()
+    }
+
+    fn can_decrypt(&self) -> bool {
+        !self.busy && match self.shares.first().and_then(|x| x.group()) {
+            Some(group) => self.groups_complete() >= group.threshold as usize,
+            None => self.threshold().is_some_and(|k| self.shares.len() >= k),
+        }
+    }
+
+    /// A holder with several votes brings several shares in one file
+    fn add_bundle(&mut self, bundle: ShareBundle) {
+        for share in bundle.into_shares() {
+            self.add_share(share);
+            if self.error.is_some() {
+                break;
+            }
+        }
+    }
+
+    fn add_share(&mut self, share: Share) {
+        if share.is_verifiable() && let Err(e) = share.verify() {
+            self.error = Some(format!("{e}"));
+        } else if self.shares.iter().any(|x| x.id() == share.id()) {
+            self.error = Some(format!("Share {} was already collected", share.id()));
+        } else if let Some(Err(e)) = self.shares.first().map(|x| x.check_same_split(&share)) {
+            self.error = Some(format!("{e}"));
+        } else {
+            self.shares.push(share);
+            self.shares.sort_by_key(|x| x.id());
+            self.error = None;
+        }
+    }
+
+    pub fn update(&mut self, message: Message, keystore: Option<Arc<Keystore>>) -> Task<Message> {
+        match message {
+            Message::PastedChanged(pasted) => {
+                self.pasted = Zeroizing::new(pasted);
+                Task::none()
+            }
+            Message::AddPasted => {
+                match ShareBundle::decode_from_text(&self.pasted) {
+                    Ok(bundle) => {
+                        self.pasted = Zeroizing::new(String::new());
+                        self.add_bundle(bundle);
+                    }
+                    Err(e) => self.error = Some(format!("Not a share: {e}")),
+                }
+                Task::none()
+            }
+            Message::PickFile => {
+                let directory = self.enc_filepath.parent().map(|x| x.to_path_buf()).unwrap_or_default();
+                Task::future(async move {
+                    let picked = rfd::AsyncFileDialog::new()
+                        .set_title("Pick a share file")
+                        .set_directory(directory)
+                        .pick_file()
+                        .await;
+                    Message::FilePicked(picked.map(|x| x.path().to_path_buf()))
+                })
+            }
+            Message::FilePicked(None) => Task::none(),
+            Message::FilePicked(Some(path)) | Message::FileDropped(path) => {
+                let custodians = self.custodian_keys();
+                Task::future(async move {
+                    Message::ShareLoaded(read_share_file(path, keystore, custodians).await.map_err(|e| format!("{e}")))
+                })
+            }
+            Message::ShareLoaded(Ok(Loaded::Bundle(bundle))) => {
+                self.add_bundle(bundle);
+                Task::none()
+            }
+            Message::ShareLoaded(Ok(Loaded::Locked(entries))) => {
+                for (label, protected) in entries {
+                    if !self.locked.iter().any(|x| x.label == label) {
+                        self.locked.push(Locked { label, protected, passphrase: Zeroizing::new(String::new()), busy: false });
+                    }
+                }
+                self.error = None;
+                Task::none()
+            }
+            Message::ShareLoaded(Err(e)) => {
+                self.error = Some(e);
+                Task::none()
+            }
+            Message::RemoveShare(id) => {
+                self.shares.retain(|x| x.id() != id);
+                Task::none()
+            }
+            Message::LockedPassphraseChanged(label, passphrase) => {
+                if let Some(locked) = self.locked.iter_mut().find(|x| x.label == label) {
+                    locked.passphrase = Zeroizing::new(passphrase);
+                }
+                Task::none()
+            }
+            Message::Unlock(label) => {
+                let Some(locked) = self.locked.iter_mut().find(|x| x.label == label && !x.busy && !x.passphrase.is_empty()) else {
+                    return Task::none();
+                };
+                locked.busy = true;
+                let protected = locked.protected.clone();
+                let passphrase = Passphrase::new(std::mem::take(&mut *locked.passphrase));
+                Task::future(async move {
+                    // key derivation is deliberately slow, keep it off the async workers
+                    let res = tokio::task::spawn_blocking(move || ShareBundle::unseal_with_passphrase(&protected, &passphrase)).await
+                        .map_err(anyhow::Error::from)
+                        .and_then(|x| x);
+                    Message::Unlocked(label, res.map_err(|e| format!("{e}")))
+                })
+            }
+            Message::Unlocked(label, Ok(bundle)) => {
+                self.locked.retain(|x| x.label != label);
+                self.add_bundle(bundle);
+                Task::none()
+            }
+            Message::Unlocked(label, Err(e)) => {
+                if let Some(locked) = self.locked.iter_mut().find(|x| x.label == label) {
+                    locked.busy = false;
+                }
+                self.error = Some(format!("Share of {label}: {e}"));
+                Task::none()
+            }
+            Message::Decrypt => {
+                if !self.can_decrypt() {
+                    return Task::none();
+                }
+                self.busy = true;
+                self.error = None;
+                let enc_filepath = self.enc_filepath.clone();
+                let shares = self.shares.clone();
+                if self.vault {
+                    return Task::future(async move {
+                        let res = foo::unlock_vault(enc_filepath, shares, keystore).await;
+                        Message::VaultUnlocked(res.map_err(|e| format!("{e:#}")))
+                    });
+                }
+                Task::future(async move {
+                    let res = foo::recover_file(enc_filepath, shares, keystore).await;
+                    Message::Decrypted(res.map_err(|e| format!("{e:#}")))
+                })
+            }
+            Message::Decrypted(Ok(recovered)) => {
+                info!("recovered {} to {}", self.enc_filepath.display(), recovered.decrypted_filepath);
+                self.busy = false;
+                // the key is out, nothing left to collect shares for
+                self.shares = Zeroizing::new(Vec::new());
+                self.locked.clear();
+                self.recovered = Some(recovered);
+                Task::none()
+            }
+            Message::VaultUnlocked(Ok(unlocked)) => {
+                info!("unlocked the vault in {}", unlocked.vault_dir.display());
+                self.busy = false;
+                // the key is out, nothing left to collect shares for
+                self.shares = Zeroizing::new(Vec::new());
+                self.locked.clear();
+                self.recovered = Some(RecoverStruct { decrypted_filepath: unlocked.vault_dir.display().to_string(), bad_shares: unlocked.bad_shares });
+                Task::none()
+            }
+            Message::Decrypted(Err(e)) | Message::VaultUnlocked(Err(e)) | Message::Refreshed(Err(e)) => {
+                self.busy = false;
+                self.error = Some(e);
+                Task::none()
+            }
+            Message::NewSharesChanged(n_shares) => {
+                self.new_n_shares = n_shares;
+                Task::none()
+            }
+            Message::NewThresholdChanged(k_thres) => {
+                self.new_k_thres = k_thres;
+                Task::none()
+            }
+            Message::NewVerifiableToggled(verifiable) => {
+                self.new_verifiable = verifiable;
+                Task::none()
+            }
+            Message::NewWordsToggled(words) => {
+                self.new_words = words;
+                Task::none()
+            }
+            Message::NewSheetsToggled(sheets) => {
+                self.new_sheets = sheets;
+                Task::none()
+            }
+            Message::Refresh => {
+                if !self.can_decrypt() {
+                    return Task::none();
+                }
+                let policy = match parse_policy(&self.new_n_shares, &self.new_k_thres, self.new_verifiable, self.new_words) {
+                    Ok(policy) => Policy { sheets: self.new_sheets, ..policy },
+                    Err(e) => {
+                        self.error = Some(e);
+                        return Task::none();
+                    }
+                };
+                self.busy = true;
+                self.error = None;
+                let enc_filepath = self.enc_filepath.clone();
+                let shares = self.shares.clone();
+                Task::future(async move {
+                    let res = foo::refresh_file_shares(enc_filepath, shares, policy, keystore).await;
+                    Message::Refreshed(res.map_err(|e| format!("{e:#}")))
+                })
+            }
+            Message::Refreshed(Ok(refreshed)) => {
+                self.busy = false;
+                // the collected shares are from the old epoch now
+                self.shares = Zeroizing::new(Vec::new());
+                self.locked.clear();
+                self.refreshed = Some(refreshed);
+                Task::none()
+            }
+            Message::ManifestLoaded(Ok(manifest)) => {
+                self.manifest = manifest;
+                Task::none()
+            }
+            Message::ManifestLoaded(Err(e)) => {
+                self.error = Some(e);
+                Task::none()
+            }
+            // the owner drops the screen
+            Message::Close => Task::none(),
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let file_name = self.enc_filepath.file_name().map(|x| x.display().to_string()).unwrap_or_default();
+        let collected = match self.shares.first() {
+            Some(share) if let Some(group) = share.group() => {
+                format!("{} of {} groups complete, {} groups in total", self.groups_complete(), group.threshold, group.total)
+            }
+            Some(share) => format!("{} of {} collected, {} shares in total", self.shares.len(), share.threshold(), share.total()),
+            None => "0 shares collected".to_string(),
+        };
+        let shares = Column::with_children(self.shares.iter().map(|share| {
+            row!(
+                ifa::fa_icon_solid("puzzle-piece").size(16.0).color(color!(0, 255, 0)),
+                text(match self.current_manifest().and_then(|x| x.holder_of(share.id())) {
+                    Some(holder) => format!("share {} from {holder}", share.id()),
+                    None => format!("share {}", share.id()),
+                }),
+                if share.is_verifiable() {
+                    to_elem(Some(row!(
+                        ifa::fa_icon_solid("circle-check").size(16.0).color(color!(0, 255, 0)),
+                        text("verified"),
+                    ).spacing(5).align_y(Vertical::Center)))
+                } else {
+                    to_elem::<Message, Text>(None)
+                },
+                button(text("remove"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::RemoveShare(share.id())),
+            ).spacing(10).align_y(Vertical::Center).into()
+        })).spacing(5);
+        let locked = Column::with_children(self.locked.iter().map(|locked| {
+            let label = locked.label.clone();
+            row!(
+                ifa::fa_icon_solid("key").size(16.0).color(color!(255, 180, 0)),
+                text(format!("share of {}", locked.label)).width(240),
+                text_input("their passphrase", &locked.passphrase)
+                    .style(Modern::text_input())
+                    .on_input(move |x| Message::LockedPassphraseChanged(label.clone(), x))
+                    .on_submit(Message::Unlock(locked.label.clone()))
+                    .secure(true)
+                    .width(240),
+                button(text(if locked.busy { "unlocking..." } else { "unlock" }))
+                    .style(Modern::secondary_button())
+                    .on_press_maybe((!locked.busy).then(|| Message::Unlock(locked.label.clone()))),
+            ).spacing(10).align_y(Vertical::Center).into()
+        })).spacing(5);
+        let groups = Row::with_children(self.group_progress().into_iter().map(|(index, have, need)| {
+            let complete = need.is_some_and(|k| have >= k);
+            row!(
+                ifa::fa_icon_solid(if complete { "circle-check" } else { "circle" }).size(16.0).color(color!(0, 255, 0)),
+                text(match need {
+                    Some(k) => format!("group {index}: {have} of {k} members"),
+                    None => format!("group {index}: no members yet"),
+                }),
+            ).spacing(5).align_y(Vertical::Center).into()
+        })).spacing(20);
+
+        column!(
+            row!(
+                ifa::fa_icon_solid("users").size(16.0).color(color!(0, 255, 0)),
+                text(if self.vault {
+                    let vault_dir = self.enc_filepath.parent().map(|x| x.display().to_string()).unwrap_or_default();
+                    format!("Unlock the vault {vault_dir} with its shares")
+                } else {
+                    format!("Recover {file_name} from its shares")
+                }),
+                text(collected),
+                if let Some(fingerprint) = self.shares.first().and_then(|x| x.commitments_fingerprint()) {
+                    to_elem(Some(text(format!("commitments {fingerprint}"))))
+                } else {
+                    to_elem::<Message, Text>(None)
+                },
+            ).spacing(10).align_y(Vertical::Center),
+            row!(
+                button(text("add share file..."))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::PickFile),
+                text_input("or paste a share in hex or words", &self.pasted)
+                    .style(Modern::text_input())
+                    .on_input(Message::PastedChanged)
+                    .on_submit(Message::AddPasted)
+                    .secure(true)
+                    .width(400),
+                button(text("add"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::AddPasted),
+                text("or drop share files, sealed shares, passwords vaults or photos of share QR codes on the window"),
+            ).spacing(10).align_y(Vertical::Center),
+            groups,
+            shares,
+            locked,
+            if let Some(manifest) = self.current_manifest().filter(|_| self.recovered.is_none()) {
+                let collected: Vec<ShareId> = self.shares.iter().map(Share::id).collect();
+                let waiting = manifest.waiting_for(&collected).iter()
+                    .map(|x| if x.contact.is_empty() { x.name.clone() } else { format!("{} ({})", x.name, x.contact) })
+                    .collect::<Vec<_>>();
+                to_elem(Some(text(if waiting.is_empty() {
+                    "every custodian's shares are in".to_string()
+                } else {
+                    format!("waiting for {}", waiting.join(", "))
+                })))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            row!(
+                button(text(match (self.busy, self.vault) {
+                    (true, false) => "decrypting...",
+                    (true, true) => "unlocking...",
+                    (false, false) => "decrypt",
+                    (false, true) => "unlock",
+                }))
+                    .style(Modern::primary_button())
+                    .on_press_maybe(self.can_decrypt().then_some(Message::Decrypt)),
+                button(text("close"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::Close),
+            ).spacing(10).align_y(Vertical::Center),
+            row!(
+                text("or issue new shares (n)"),
+                text_input("5", &self.new_n_shares)
+                    .style(Modern::text_input())
+                    .on_input(Message::NewSharesChanged)
+                    .width(80),
+                text("threshold (k)"),
+                text_input("3", &self.new_k_thres)
+                    .style(Modern::text_input())
+                    .on_input(Message::NewThresholdChanged)
+                    .width(80),
+                checkbox("verifiable", self.new_verifiable)
+                    .on_toggle(Message::NewVerifiableToggled),
+                checkbox("words", self.new_words)
+                    .on_toggle(Message::NewWordsToggled),
+                checkbox("sheets", self.new_sheets)
+                    .on_toggle(Message::NewSheetsToggled),
+                button(text(if self.busy { "working..." } else { "refresh shares" }))
+                    .style(Modern::secondary_button())
+                    .on_press_maybe(self.can_decrypt().then_some(Message::Refresh)),
+                text("the old shares stop working"),
+            ).spacing(10).align_y(Vertical::Center),
+            if let Some(recovered) = &self.recovered {
+                to_elem(Some(text(if self.vault {
+                    format!("Unlocked {} for this session, its files decrypt without shares until the vault is locked", recovered.decrypted_filepath)
+                } else {
+                    format!("Decrypted to {}", recovered.decrypted_filepath)
+                }).color(color!(0, 255, 0))))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            if let Some(recovered) = self.recovered.as_ref().filter(|x| !x.bad_shares.is_empty()) {
+                let bad = recovered.bad_shares.iter().map(ShareId::to_string).collect::<Vec<_>>().join(", ");
+                to_elem(Some(text(format!("Left out bad shares {bad}, they disagree with the others. Refresh the shares to replace them")).color(color!(255, 180, 0))))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            if let Some(refreshed) = &self.refreshed {
+                to_elem(Some(text(format!("Issued epoch {} shares: {}", refreshed.epoch, refreshed.share_filepaths.join(", "))).color(color!(0, 255, 0))))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            if let Some(error) = &self.error {
+                to_elem(Some(text(error).color(color!(255, 80, 80))))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+        )
+        .spacing(10)
+        .into()
+    }
+}
+
+/// Reads a share file, hex or words, a PNG/JPEG of a QR code or a share sealed to a custodian, as the bundle of a holder's shares
+///
+/// Sealed shares open with an identity in `keystore`, `custodians` names whose key a share is sealed to otherwise.
+/// Shares protected with a passphrase and passwords vaults come back locked, their custodians type the passphrases.
+async fn read_share_file(path: PathBuf, keystore: Option<Arc<Keystore>>, custodians: Vec<(Vec<u8>, String)>) -> anyhow::Result<Loaded> {
+    let bytes = Zeroizing::new(tokio::fs::read(&path).await
+        .map_err(|e| anyhow::anyhow!("Unable to read share {}: {e}", path.display()))?);
+    if bytes.starts_with(&crypto::PASSWORD_VAULT_MAGIC) {
+        let vault = PasswordVault::decode(&bytes).map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+        return Ok(Loaded::Locked(vault.entries().to_vec()));
+    }
+    if ShareBundle::needs_passphrase(&bytes) {
+        let label = path.file_name().map(|x| x.display().to_string()).unwrap_or_default();
+        return Ok(Loaded::Locked(vec![(label, bytes.to_vec())]));
+    }
+    if ShareBundle::is_sealed(&bytes) {
+        let sealed_to = ShareBundle::sealed_to(&bytes)?;
+        let opened = keystore.as_deref().map(|keystore| ShareBundle::unseal(&bytes, keystore));
+        return match opened {
+            Some(Ok(bundle)) => Ok(Loaded::Bundle(bundle)),
+            Some(Err(_)) | None => {
+                let names = custodians.iter()
+                    .filter(|(id, _)| sealed_to.contains(id))
+                    .map(|(_, name)| name.as_str())
+                    .collect::<Vec<_>>();
+                let whom = if names.is_empty() { "a custodian's public key".to_string() } else { format!("the public key of {}", names.join(", ")) };
+                let unlock = if keystore.is_none() { "Unlock the keystore if it is yours, otherwise the" } else { "The" };
+                Err(anyhow::anyhow!("{} is sealed to {whom}. {unlock} custodian decrypts it with their own keystore and hands over the share", path.display()))
+            }
+        };
+    }
+    let bundle = if qr::is_image(&bytes) {
+        // finding the code in a large photo takes a moment
+        tokio::task::spawn_blocking(move || qr::decode_image(&bytes).and_then(|x| ShareBundle::decode(&x))).await?
+    } else {
+        let binary = bytes.starts_with(&crypto::SHARE_MAGIC) || bytes.starts_with(&crypto::BUNDLE_MAGIC);
+        match std::str::from_utf8(&bytes) {
+            Ok(text) if !binary => ShareBundle::decode_from_text(text),
+            _ => ShareBundle::decode(&bytes),
+        }
+    };
+    bundle.map(Loaded::Bundle).map_err(|e| anyhow::anyhow!("{} is not a share: {e}", path.display()))
+}
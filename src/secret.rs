@@ -0,0 +1,340 @@
+use std::time::Duration;
+
+use iced::{
+    Element, Subscription, Task, alignment::Vertical, color, widget::{Column, Text, button, checkbox, column, row, text, text_input}
+};
+use iced_font_awesome as ifa;
+use iced_modern_theme::Modern;
+use iced_optional_element_shim::to_elem;
+use zeroize::Zeroizing;
+
+use crate::crypto::{self, Share, ShareBundle, ShareId};
+use crate::shares::parse_policy;
+
+/// How long a combined secret stays on screen
+const REVEAL_SECONDS: u32 = 20;
+
+/// Splits a short secret typed or pasted in, e.g. a root password, and puts one back together from its shares
+///
+/// Nothing is written to disk, shares are copied out one at a time and the combined secret is only shown
+/// for `REVEAL_SECONDS`. Everything lives here and is wiped when the panel is dropped.
+pub struct SecretPanel {
+    combining: bool,
+    secret: Zeroizing<String>,
+    n_shares: String,
+    k_thres: String,
+    verifiable: bool,
+    words: bool,
+    /// shares of the last split, as hex or words
+    issued: Vec<Zeroizing<String>>,
+    pasted: Zeroizing<String>,
+    shares: Zeroizing<Vec<Share>>,
+    /// combined secret and the seconds it has left on screen
+    revealed: Option<(Zeroizing<String>, u32)>,
+    bad_shares: Vec<ShareId>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    CombiningToggled(bool),
+    SecretChanged(String),
+    SharesChanged(String),
+    ThresholdChanged(String),
+    VerifiableToggled(bool),
+    WordsToggled(bool),
+    Split,
+    CopyShare(usize),
+    PastedChanged(String),
+    AddPasted,
+    RemoveShare(ShareId),
+    Combine,
+    Tick,
+    Hide,
+    Close,
+}
+
+impl SecretPanel {
+    pub fn new() -> Self {
+        Self {
+            combining: false,
+            secret: Zeroizing::new(String::new()),
+            n_shares: "5".to_string(),
+            k_thres: "3".to_string(),
+            verifiable: true,
+            words: false,
+            issued: Vec::new(),
+            pasted: Zeroizing::new(String::new()),
+            shares: Zeroizing::new(Vec::new()),
+            revealed: None,
+            bad_shares: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn add_share(&mut self, share: Share) {
+        if share.is_verifiable() && let Err(e) = share.verify() {
+            self.error = Some(format!("{e}"));
+        } else if self.shares.iter().any(|x| x.id() == share.id()) {
+            self.error = Some(format!("Share {} was already added", share.id()));
+        } else if let Some(Err(e)) = self.shares.first().map(|x| x.check_same_split(&share)) {
+            self.error = Some(format!("{e}"));
+        } else {
+            self.shares.push(share);
+            self.shares.sort_by_key(|x| x.id());
+            self.error = None;
+        }
+    }
+
+    fn can_combine(&self) -> bool {
+        self.shares.first().is_some_and(|x| self.shares.len() >= x.threshold() as usize)
+    }
+
+    /// Counts down the seconds a combined secret has left on screen
+    pub fn subscription(&self) -> Subscription<Message> {
+        if self.revealed.is_some() {
+            iced::time::every(Duration::from_secs(1)).map(|_| Message::Tick)
+        } else {
+            Subscription::none()
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::CombiningToggled(combining) => {
+                self.combining = combining;
+                self.error = None;
+                Task::none()
+            }
+            Message::SecretChanged(secret) => {
+                self.secret = Zeroizing::new(secret);
+                self.error = None;
+                Task::none()
+            }
+            Message::SharesChanged(n_shares) => {
+                self.n_shares = n_shares;
+                self.error = None;
+                Task::none()
+            }
+            Message::ThresholdChanged(k_thres) => {
+                self.k_thres = k_thres;
+                self.error = None;
+                Task::none()
+            }
+            Message::VerifiableToggled(verifiable) => {
+                self.verifiable = verifiable;
+                Task::none()
+            }
+            Message::WordsToggled(words) => {
+                self.words = words;
+                Task::none()
+            }
+            Message::Split => {
+                let policy = match parse_policy(&self.n_shares, &self.k_thres, self.verifiable, self.words) {
+                    Ok(policy) => policy,
+                    Err(e) => {
+                        self.error = Some(e);
+                        return Task::none();
+                    }
+                };
+                match crypto::split_secret(self.secret.as_bytes(), policy.n_shares, policy.k_thres, policy.verifiable) {
+                    Ok(shares) => {
+                        self.issued = shares.iter()
+                            .map(|x| if policy.words { x.encode_to_mnemonic() } else { Zeroizing::new(x.encode_to_string()) })
+                            .collect();
+                        // the shares are all that is left of it
+                        self.secret = Zeroizing::new(String::new());
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(format!("{e}")),
+                }
+                Task::none()
+            }
+            Message::CopyShare(i) => match self.issued.get(i) {
+                Some(share) => iced::clipboard::write(share.to_string()),
+                None => Task::none(),
+            },
+            Message::PastedChanged(pasted) => {
+                self.pasted = Zeroizing::new(pasted);
+                Task::none()
+            }
+            Message::AddPasted => {
+                match ShareBundle::decode_from_text(&self.pasted) {
+                    Ok(bundle) => {
+                        self.pasted = Zeroizing::new(String::new());
+                        for share in bundle.into_shares() {
+                            self.add_share(share);
+                        }
+                    }
+                    Err(e) => self.error = Some(format!("Not a share: {e}")),
+                }
+                Task::none()
+            }
+            Message::RemoveShare(id) => {
+                self.shares.retain(|x| x.id() != id);
+                Task::none()
+            }
+            Message::Combine => {
+                if !self.can_combine() {
+                    return Task::none();
+                }
+                let combined = Share::combine_robust(&self.shares).and_then(|(secret, bad)| {
+                    let text = std::str::from_utf8(&secret)
+                        .map_err(|_| anyhow::anyhow!("The shares don't hold text, shares of a file key are recovered from the file list"))?;
+                    Ok((Zeroizing::new(text.to_string()), bad))
+                });
+                match combined {
+                    Ok((secret, bad)) => {
+                        // the secret is out, nothing left to collect shares for
+                        self.shares = Zeroizing::new(Vec::new());
+                        self.revealed = Some((secret, REVEAL_SECONDS));
+                        self.bad_shares = bad;
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(format!("{e}")),
+                }
+                Task::none()
+            }
+            Message::Tick => {
+                match self.revealed.as_mut() {
+                    Some((_, left)) if *left > 1 => *left -= 1,
+                    _ => return Task::done(Message::Hide),
+                }
+                Task::none()
+            }
+            Message::Hide => {
+                self.revealed = None;
+                self.bad_shares.clear();
+                Task::none()
+            }
+            // the owner drops the panel
+            Message::Close => Task::none(),
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        column!(
+            row!(
+                ifa::fa_icon_solid("user-secret").size(16.0).color(color!(0, 255, 0)),
+                text(if self.combining {
+                    "Put a secret back together from its shares, it is shown briefly and never written to disk:"
+                } else {
+                    "Split a password or recovery phrase into shares, any threshold of them give it back:"
+                }),
+                checkbox("combine", self.combining)
+                    .on_toggle(Message::CombiningToggled),
+                button(text("close"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::Close),
+            ).spacing(10).align_y(Vertical::Center),
+            if self.combining {
+                self.view_combine()
+            } else {
+                self.view_split()
+            },
+            if let Some(error) = &self.error {
+                to_elem(Some(text(error).color(color!(255, 80, 80))))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+        )
+        .spacing(10)
+        .into()
+    }
+
+    fn view_split(&self) -> Element<'_, Message> {
+        let issued = Column::with_children(self.issued.iter().enumerate().map(|(i, share)| {
+            row!(
+                ifa::fa_icon_solid("puzzle-piece").size(16.0).color(color!(0, 255, 0)),
+                text(format!("share #{}", i + 1)),
+                button(text("copy"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::CopyShare(i)),
+                text(share.as_str()).width(800),
+            ).spacing(10).align_y(Vertical::Center).into()
+        })).spacing(5);
+        column!(
+            row!(
+                text_input("secret", &self.secret)
+                    .style(Modern::text_input())
+                    .on_input(Message::SecretChanged)
+                    .on_submit(Message::Split)
+                    .secure(true)
+                    .width(400),
+                text("shares (n)"),
+                text_input("5", &self.n_shares)
+                    .style(Modern::text_input())
+                    .on_input(Message::SharesChanged)
+                    .width(80),
+                text("threshold (k)"),
+                text_input("3", &self.k_thres)
+                    .style(Modern::text_input())
+                    .on_input(Message::ThresholdChanged)
+                    .width(80),
+                checkbox("verifiable", self.verifiable)
+                    .on_toggle(Message::VerifiableToggled),
+                checkbox("words", self.words)
+                    .on_toggle(Message::WordsToggled),
+                button(text("split"))
+                    .style(Modern::primary_button())
+                    .on_press_maybe((!self.secret.is_empty()).then_some(Message::Split)),
+            ).spacing(10).align_y(Vertical::Center),
+            issued,
+        )
+        .spacing(10)
+        .into()
+    }
+
+    fn view_combine(&self) -> Element<'_, Message> {
+        let collected = match self.shares.first() {
+            Some(share) => format!("{} of {} added, {} shares in total", self.shares.len(), share.threshold(), share.total()),
+            None => "0 shares added".to_string(),
+        };
+        let shares = Column::with_children(self.shares.iter().map(|share| {
+            row!(
+                ifa::fa_icon_solid("puzzle-piece").size(16.0).color(color!(0, 255, 0)),
+                text(format!("share {}", share.id())),
+                button(text("remove"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::RemoveShare(share.id())),
+            ).spacing(10).align_y(Vertical::Center).into()
+        })).spacing(5);
+        column!(
+            row!(
+                text_input("paste a share in hex or words", &self.pasted)
+                    .style(Modern::text_input())
+                    .on_input(Message::PastedChanged)
+                    .on_submit(Message::AddPasted)
+                    .secure(true)
+                    .width(400),
+                button(text("add"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::AddPasted),
+                text(collected),
+                button(text("combine"))
+                    .style(Modern::primary_button())
+                    .on_press_maybe(self.can_combine().then_some(Message::Combine)),
+            ).spacing(10).align_y(Vertical::Center),
+            shares,
+            if let Some((secret, left)) = &self.revealed {
+                to_elem(Some(row!(
+                    text(secret.as_str()).color(color!(0, 255, 0)),
+                    text(format!("hidden in {left}s")),
+                    button(text("hide"))
+                        .style(Modern::secondary_button())
+                        .on_press(Message::Hide),
+                ).spacing(10).align_y(Vertical::Center)))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+            if self.bad_shares.is_empty() {
+                to_elem::<Message, Text>(None)
+            } else {
+                let bad = self.bad_shares.iter().map(ShareId::to_string).collect::<Vec<_>>().join(", ");
+                to_elem(Some(text(format!("Left out bad shares {bad}, they disagree with the others")).color(color!(255, 180, 0))))
+            },
+        )
+        .spacing(10)
+        .into()
+    }
+}
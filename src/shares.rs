@@ -0,0 +1,637 @@
+use std::path::PathBuf;
+
+use iced::{
+    Element, Task, alignment::Vertical, color, widget::{Text, button, checkbox, column, row, text, text_input}
+};
+use iced_font_awesome as ifa;
+use iced_modern_theme::Modern;
+use iced_optional_element_shim::to_elem;
+use zeroize::Zeroizing;
+
+use crate::crypto::{self, Passphrase, X25519Recipient};
+use crate::custodians;
+
+/// Asks how many shares to split a file key into and how many it takes to recover it
+pub struct SharesPrompt {
+    pub target: Target,
+    n_shares: String,
+    k_thres: String,
+    /// publish pedersen commitments so holders can check their shares
+    verifiable: bool,
+    words: bool,
+    sheets: bool,
+    /// every holder protects their share with a passphrase of their own
+    passwords: bool,
+    /// set once the policy is in and the holders are typing their passphrases
+    typing: Option<Passwords>,
+    /// comma separated, `name:votes` gives a holder several shares
+    holders: String,
+    /// custodians loaded from a roster file, they replace `holders`
+    roster: Option<(String, Vec<Holder>)>,
+    /// where every holder's packet goes, next to the file when None
+    packets_dir: Option<PathBuf>,
+    /// comma separated `name k/n`, when set the key is split across these groups instead
+    groups: String,
+    /// blank for all groups
+    k_groups: String,
+    error: Option<String>,
+}
+
+/// What the key being split opens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// the file at this file list index
+    File(usize),
+    /// every file of a new project vault in this directory
+    Vault(PathBuf),
+}
+
+/// Passphrases typed by every holder in turn, twice so typos don't lock a share away
+struct Passwords {
+    policy: Policy,
+    labels: Vec<String>,
+    typed: Vec<(Zeroizing<String>, Zeroizing<String>)>,
+    vault: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SharesChanged(String),
+    ThresholdChanged(String),
+    VerifiableToggled(bool),
+    WordsToggled(bool),
+    SheetsToggled(bool),
+    PasswordsToggled(bool),
+    PassphraseChanged(usize, String),
+    ConfirmChanged(usize, String),
+    VaultToggled(bool),
+    HoldersChanged(String),
+    GroupsChanged(String),
+    GroupsNeededChanged(String),
+    PickRoster,
+    RosterLoaded(Option<Result<(String, Vec<Holder>), String>>),
+    ClearRoster,
+    PickPacketsDir,
+    PacketsDirPicked(Option<PathBuf>),
+    Submit,
+    Cancel,
+}
+
+/// How to split a key into share files
+#[derive(Debug, Clone)]
+pub struct Policy {
+    /// split into `n_shares`, any `k_thres` of them recover the key
+    pub n_shares: u16,
+    pub k_thres: u16,
+    pub verifiable: bool,
+    /// also write every share as words, for paper backups
+    pub words: bool,
+    /// also write a printable sheet per share, see `sheet::share_sheet`
+    pub sheets: bool,
+    /// named holders first, the shares left over go to one generic holder each
+    pub holders: Vec<Holder>,
+    /// when set the key is split across these groups instead of into `n_shares`,
+    /// any `k_groups` of them recover it, see `crypto::Share::split_groups`
+    pub groups: Vec<GroupPolicy>,
+    pub k_groups: u16,
+    /// write every holder's files into a packet directory of their own in here, along with a manifest
+    pub packets_dir: Option<PathBuf>,
+    /// one per holding, every holder's share is protected with their passphrase, see `crypto::ShareBundle::protect`
+    pub passwords: Vec<Passphrase>,
+    /// put the protected shares in one passwords vault file instead of a file per holder
+    pub vault: bool,
+}
+
+/// A group whose share is split again across its members, any `k_members` of them recover it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupPolicy {
+    /// blank for the generic label
+    pub name: String,
+    pub n_members: u16,
+    pub k_members: u16,
+}
+
+/// Someone trusted with `votes` of the shares, all in one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    /// blank for the generic label
+    pub name: String,
+    pub votes: u16,
+    /// how to reach the holder, only ever written to the manifest
+    pub contact: String,
+    pub public_key: Option<X25519Recipient>,
+}
+
+/// The shares written to one file, and who they are for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holding {
+    pub label: String,
+    pub shares: Vec<crypto::ShareId>,
+    /// None for generic holders and members of groups
+    pub holder: Option<Holder>,
+}
+
+impl Policy {
+    /// Every share file, in order, members of groups get one file each
+    pub fn holdings(&self) -> Vec<Holding> {
+        if !self.groups.is_empty() {
+            return self.groups.iter().zip(1..).flat_map(|(group, g)| {
+                let name = if group.name.is_empty() { format!("group {g}") } else { group.name.clone() };
+                (1..=group.n_members).map(move |index| Holding {
+                    label: format!("{name} member #{index}"),
+                    shares: vec![crypto::ShareId { group: Some(g), index }],
+                    holder: None,
+                })
+            }).collect();
+        }
+        let mut next = 1u16;
+        let mut res = Vec::new();
+        let listed = self.holders.iter().map(Some);
+        let generic = std::iter::repeat(None);
+        for holder in listed.chain(generic) {
+            if next > self.n_shares {
+                break;
+            }
+            let (name, votes) = holder.map_or(("", 1), |x| (x.name.as_str(), x.votes));
+            let indices: Vec<u16> = (next..next.saturating_add(votes).min(self.n_shares + 1)).collect();
+            next += indices.len() as u16;
+            let label = match (name, indices.as_slice()) {
+                ("", [index]) => format!("holder of share #{index}"),
+                ("", [first, .., last]) => format!("holder of shares #{first} to #{last}"),
+                (name, _) => name.to_string(),
+            };
+            res.push(Holding {
+                label,
+                shares: indices.into_iter().map(|index| crypto::ShareId { group: None, index }).collect(),
+                holder: holder.cloned(),
+            });
+        }
+        res
+    }
+
+    /// `(n_members, k_members)` of every group, as `crypto::Share::split_groups` takes them
+    pub fn group_sizes(&self) -> Vec<(u16, u16)> {
+        self.groups.iter().map(|x| (x.n_members, x.k_members)).collect()
+    }
+}
+
+impl std::fmt::Display for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.groups.is_empty() {
+            write!(f, "{} of {} shares", self.k_thres, self.n_shares)
+        } else {
+            write!(f, "{} of {} groups", self.k_groups, self.groups.len())
+        }
+    }
+}
+
+/// What the owner of the prompt should do after an update
+pub enum Outcome {
+    Pending,
+    /// run it and hand the result back to `update`
+    Task(Task<Message>),
+    Submitted(Policy),
+    Cancelled,
+}
+
+impl SharesPrompt {
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            n_shares: "5".to_string(),
+            k_thres: "3".to_string(),
+            verifiable: true,
+            words: false,
+            sheets: false,
+            passwords: false,
+            typing: None,
+            holders: String::new(),
+            roster: None,
+            packets_dir: None,
+            groups: String::new(),
+            k_groups: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> Outcome {
+        match message {
+            Message::SharesChanged(n_shares) => {
+                self.n_shares = n_shares;
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::ThresholdChanged(k_thres) => {
+                self.k_thres = k_thres;
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::VerifiableToggled(verifiable) => {
+                self.verifiable = verifiable;
+                Outcome::Pending
+            }
+            Message::WordsToggled(words) => {
+                self.words = words;
+                Outcome::Pending
+            }
+            Message::SheetsToggled(sheets) => {
+                self.sheets = sheets;
+                Outcome::Pending
+            }
+            Message::PasswordsToggled(passwords) => {
+                self.passwords = passwords;
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::PassphraseChanged(i, passphrase) => {
+                if let Some(typed) = self.typing.as_mut().and_then(|x| x.typed.get_mut(i)) {
+                    typed.0 = Zeroizing::new(passphrase);
+                    self.error = None;
+                }
+                Outcome::Pending
+            }
+            Message::ConfirmChanged(i, confirm) => {
+                if let Some(typed) = self.typing.as_mut().and_then(|x| x.typed.get_mut(i)) {
+                    typed.1 = Zeroizing::new(confirm);
+                    self.error = None;
+                }
+                Outcome::Pending
+            }
+            Message::VaultToggled(vault) => {
+                if let Some(typing) = self.typing.as_mut() {
+                    typing.vault = vault;
+                }
+                Outcome::Pending
+            }
+            Message::Submit if self.typing.is_some() => match self.typed_passwords() {
+                Ok(policy) => Outcome::Submitted(policy),
+                Err(e) => {
+                    self.error = Some(e);
+                    Outcome::Pending
+                }
+            },
+            Message::HoldersChanged(holders) => {
+                self.holders = holders;
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::GroupsChanged(groups) => {
+                self.groups = groups;
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::GroupsNeededChanged(k_groups) => {
+                self.k_groups = k_groups;
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::PickRoster => Outcome::Task(Task::future(async {
+                let picked = rfd::AsyncFileDialog::new()
+                    .set_title("Pick a custodian roster")
+                    .add_filter("roster", &["txt"])
+                    .pick_file()
+                    .await;
+                let Some(picked) = picked else {
+                    return Message::RosterLoaded(None);
+                };
+                let file_name = picked.file_name();
+                let roster = match tokio::fs::read_to_string(picked.path()).await {
+                    Ok(roster) => custodians::parse_roster(&roster).map_err(|e| format!("{file_name}: {e}")),
+                    Err(e) => Err(format!("Failed to read {file_name}: {e}")),
+                };
+                Message::RosterLoaded(Some(roster.map(|x| (file_name, x))))
+            })),
+            Message::RosterLoaded(None) => Outcome::Pending,
+            Message::RosterLoaded(Some(Ok(roster))) => {
+                self.roster = Some(roster);
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::RosterLoaded(Some(Err(e))) => {
+                self.error = Some(e);
+                Outcome::Pending
+            }
+            Message::ClearRoster => {
+                self.roster = None;
+                Outcome::Pending
+            }
+            Message::PickPacketsDir => Outcome::Task(Task::future(async {
+                let picked = rfd::AsyncFileDialog::new()
+                    .set_title("Pick where the share packets go")
+                    .pick_folder()
+                    .await;
+                Message::PacketsDirPicked(picked.map(|x| x.path().to_path_buf()))
+            })),
+            Message::PacketsDirPicked(None) => Outcome::Pending,
+            Message::PacketsDirPicked(Some(dir)) => {
+                self.packets_dir = Some(dir);
+                self.error = None;
+                Outcome::Pending
+            }
+            Message::Submit => match if self.groups.trim().is_empty() {
+                parse_policy(&self.n_shares, &self.k_thres, self.verifiable, self.words)
+            } else {
+                parse_groups(&self.groups, &self.k_groups, self.verifiable, self.words)
+            }
+                .and_then(|policy| match &self.roster {
+                    Some((_, roster)) => with_holder_list(policy, roster.clone()),
+                    None => with_holders(policy, &self.holders),
+                })
+                .and_then(|policy| match &self.packets_dir {
+                    None if self.roster.is_some() => Err("Pick where the custodians' packets go".to_string()),
+                    packets_dir => Ok(Policy { packets_dir: packets_dir.clone(), ..policy }),
+                })
+                .and_then(|policy| self.check_passwords(policy))
+            {
+                Ok(policy) if self.passwords => {
+                    let labels = policy.holdings().into_iter().map(|x| x.label).collect::<Vec<_>>();
+                    let typed = labels.iter().map(|_| Default::default()).collect();
+                    self.typing = Some(Passwords { policy, labels, typed, vault: true });
+                    Outcome::Pending
+                }
+                Ok(policy) => Outcome::Submitted(Policy { sheets: self.sheets, ..policy }),
+                Err(e) => {
+                    self.error = Some(e);
+                    Outcome::Pending
+                }
+            },
+            Message::Cancel => Outcome::Cancelled,
+        }
+    }
+
+    /// Errors when protecting shares with passphrases would be undone by the rest of the policy
+    fn check_passwords(&self, policy: Policy) -> Result<Policy, String> {
+        if !self.passwords {
+            return Ok(policy);
+        }
+        if policy.words || self.sheets {
+            return Err("Words and sheets would hold the shares in the clear, uncheck them to protect shares with passphrases".to_string());
+        }
+        if policy.holders.iter().any(|x| x.public_key.is_some()) {
+            return Err("Custodians with a public key get their share sealed to it, drop the keys from the roster to use passphrases".to_string());
+        }
+        Ok(policy)
+    }
+
+    /// The policy along with every holder's passphrase, once all of them are typed in twice
+    fn typed_passwords(&self) -> Result<Policy, String> {
+        let Some(typing) = &self.typing else {
+            return Err("No passphrases typed yet".to_string());
+        };
+        let mut passwords = Vec::with_capacity(typing.typed.len());
+        for (label, (passphrase, confirm)) in typing.labels.iter().zip(&typing.typed) {
+            if passphrase.is_empty() {
+                return Err(format!("{label} has no passphrase yet"));
+            }
+            if passphrase != confirm {
+                return Err(format!("The passphrases of {label} don't match"));
+            }
+            passwords.push(Passphrase::new(passphrase.to_string()));
+        }
+        Ok(Policy { passwords, vault: typing.vault, ..typing.policy.clone() })
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        if let Some(typing) = &self.typing {
+            return self.view_passwords(typing);
+        }
+        column!(
+            row!(
+                ifa::fa_icon_solid("puzzle-piece").size(16.0).color(color!(0, 255, 0)),
+                text(match &self.target {
+                    Target::File(_) => "Split the file key into shares, any threshold of them can decrypt:".to_string(),
+                    Target::Vault(dir) => format!("Make {} a vault, split its key into shares, any threshold of them unlock every file in it:", dir.display()),
+                }),
+            ).spacing(10).align_y(Vertical::Center),
+            row!(
+                text("shares (n)"),
+                text_input("5", &self.n_shares)
+                    .style(Modern::text_input())
+                    .on_input(Message::SharesChanged)
+                    .on_submit(Message::Submit)
+                    .width(80),
+                text("threshold (k)"),
+                text_input("3", &self.k_thres)
+                    .style(Modern::text_input())
+                    .on_input(Message::ThresholdChanged)
+                    .on_submit(Message::Submit)
+                    .width(80),
+                checkbox("verifiable", self.verifiable)
+                    .on_toggle(Message::VerifiableToggled),
+                checkbox("words", self.words)
+                    .on_toggle(Message::WordsToggled),
+                checkbox("sheets", self.sheets)
+                    .on_toggle(Message::SheetsToggled),
+                checkbox("passwords", self.passwords)
+                    .on_toggle(Message::PasswordsToggled),
+                button(text(if self.passwords { "next" } else { "encrypt" }))
+                    .style(Modern::primary_button())
+                    .on_press(Message::Submit),
+                button(text("cancel"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::Cancel),
+            ).spacing(10).align_y(Vertical::Center),
+            if let Some((file_name, roster)) = &self.roster {
+                // shares of custodians with a public key are sealed to it
+                let names = roster.iter()
+                    .map(|x| if x.public_key.is_some() { format!("{} (sealed)", x.name) } else { x.name.clone() })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                to_elem(Some(row!(
+                    text(format!("custodians from {file_name}: {names}")),
+                    button(text("clear"))
+                        .style(Modern::secondary_button())
+                        .on_press(Message::ClearRoster),
+                ).spacing(10).align_y(Vertical::Center)))
+            } else {
+                to_elem(Some(row!(
+                    text("holders"),
+                    text_input("alice, bob, cto:2 ... (optional, a count after the colon gives one holder several shares)", &self.holders)
+                        .style(Modern::text_input())
+                        .on_input(Message::HoldersChanged)
+                        .on_submit(Message::Submit)
+                        .width(560),
+                    button(text("load roster..."))
+                        .style(Modern::secondary_button())
+                        .on_press(Message::PickRoster),
+                ).spacing(10).align_y(Vertical::Center)))
+            },
+            row!(
+                button(text("packets to..."))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::PickPacketsDir),
+                text(match &self.packets_dir {
+                    Some(dir) => format!("one packet per holder in {}, with a manifest of who holds which share", dir.display()),
+                    None => "share files go next to the file".to_string(),
+                }),
+            ).spacing(10).align_y(Vertical::Center),
+            row!(
+                text("groups"),
+                text_input("security 2/3, management 1/2 ... (optional, members needed / members of every group)", &self.groups)
+                    .style(Modern::text_input())
+                    .on_input(Message::GroupsChanged)
+                    .on_submit(Message::Submit)
+                    .width(560),
+                text("groups needed"),
+                text_input("all", &self.k_groups)
+                    .style(Modern::text_input())
+                    .on_input(Message::GroupsNeededChanged)
+                    .on_submit(Message::Submit)
+                    .width(80),
+            ).spacing(10).align_y(Vertical::Center),
+            if let Some(error) = &self.error {
+                to_elem(Some(text(error).color(color!(255, 80, 80))))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+        )
+        .spacing(10)
+        .into()
+    }
+}
+
+impl SharesPrompt {
+    fn view_passwords<'a>(&'a self, typing: &'a Passwords) -> Element<'a, Message> {
+        let rows = typing.labels.iter().zip(&typing.typed).enumerate().map(|(i, (label, (passphrase, confirm)))| {
+            row!(
+                text(label).width(240),
+                text_input("passphrase", passphrase)
+                    .style(Modern::text_input())
+                    .on_input(move |x| Message::PassphraseChanged(i, x))
+                    .secure(true)
+                    .width(240),
+                text_input("again", confirm)
+                    .style(Modern::text_input())
+                    .on_input(move |x| Message::ConfirmChanged(i, x))
+                    .on_submit(Message::Submit)
+                    .secure(true)
+                    .width(240),
+            ).spacing(10).align_y(Vertical::Center).into()
+        });
+        column!(
+            row!(
+                ifa::fa_icon_solid("key").size(16.0).color(color!(0, 255, 0)),
+                text("Every holder types a passphrase for their share in turn, any threshold of them unlock it later:"),
+            ).spacing(10).align_y(Vertical::Center),
+            iced::widget::Column::with_children(rows).spacing(5),
+            row!(
+                checkbox("one passwords vault file for all shares", typing.vault)
+                    .on_toggle(Message::VaultToggled),
+                button(text("encrypt"))
+                    .style(Modern::primary_button())
+                    .on_press(Message::Submit),
+                button(text("cancel"))
+                    .style(Modern::secondary_button())
+                    .on_press(Message::Cancel),
+            ).spacing(10).align_y(Vertical::Center),
+            if let Some(error) = &self.error {
+                to_elem(Some(text(error).color(color!(255, 80, 80))))
+            } else {
+                to_elem::<Message, Text>(None)
+            },
+        )
+        .spacing(10)
+        .into()
+    }
+}
+
+/// Parses a typed in number of shares and threshold, the error is meant for the user
+pub fn parse_policy(n_shares: &str, k_thres: &str, verifiable: bool, words: bool) -> Result<Policy, String> {
+    let n_shares: u16 = n_shares.trim().parse()
+        .map_err(|_| "Number of shares must be a whole number".to_string())?;
+    let k_thres: u16 = k_thres.trim().parse()
+        .map_err(|_| "Threshold must be a whole number".to_string())?;
+    if k_thres < 2 {
+        return Err("Threshold must be at least 2, otherwise every share is the whole key".to_string());
+    }
+    if n_shares > crypto::MAX_SHARES {
+        return Err(format!("At most {} shares", crypto::MAX_SHARES));
+    }
+    if k_thres > n_shares {
+        return Err(format!("Threshold ({k_thres}) can't be more than the number of shares ({n_shares})"));
+    }
+    Ok(Policy { n_shares, k_thres, verifiable, words, sheets: false, holders: Vec::new(), groups: Vec::new(), k_groups: 0, packets_dir: None,
+        passwords: Vec::new(), vault: false })
+}
+
+/// Parses groups typed as `security 2/3, management 1/2` and how many of them are needed,
+/// blank for all of them. The error is meant for the user
+pub fn parse_groups(groups: &str, k_groups: &str, verifiable: bool, words: bool) -> Result<Policy, String> {
+    if verifiable {
+        return Err("Shares split across groups can't be verifiable yet, uncheck verifiable".to_string());
+    }
+    let groups = groups.split(',')
+        .map(|x| {
+            let x = x.trim();
+            let (name, counts) = x.rsplit_once(char::is_whitespace).unwrap_or(("", x));
+            let (k_members, n_members) = counts.split_once('/')
+                .and_then(|(k, n)| Some((k.trim().parse::<u16>().ok()?, n.trim().parse::<u16>().ok()?)))
+                .ok_or_else(|| format!("Groups look like `security 2/3`, members needed / members, not `{x}`"))?;
+            let name = name.trim().to_string();
+            if k_members == 0 || k_members > n_members {
+                return Err(format!("{name} needs between 1 and all of its {n_members} members, not {k_members}"));
+            }
+            if n_members > crypto::MAX_SHARES {
+                return Err(format!("At most {} members in {name}", crypto::MAX_SHARES));
+            }
+            Ok(GroupPolicy { name, n_members, k_members })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if groups.len() < 2 {
+        return Err("Split across at least two groups, or leave groups blank".to_string());
+    }
+    if groups.len() > crypto::MAX_SHARES as usize {
+        return Err(format!("At most {} groups", crypto::MAX_SHARES));
+    }
+    let k_groups = match k_groups.trim() {
+        "" | "all" => groups.len() as u16,
+        k => k.parse::<u16>().map_err(|_| "Groups needed must be a whole number".to_string())?,
+    };
+    if k_groups < 2 || k_groups as usize > groups.len() {
+        return Err(format!("Groups needed must be between 2 and the number of groups ({})", groups.len()));
+    }
+    let n_shares = groups.iter().map(|x| x.n_members).sum();
+    Ok(Policy { n_shares, k_thres: 0, verifiable, words, sheets: false, holders: Vec::new(), groups, k_groups, packets_dir: None,
+        passwords: Vec::new(), vault: false })
+}
+
+/// Adds holders typed as a comma separated list to `policy`, `cto:2` gives the cto two shares,
+/// blanks keep the generic label
+pub fn with_holders(policy: Policy, holders: &str) -> Result<Policy, String> {
+    if holders.trim().is_empty() {
+        return Ok(policy);
+    }
+    let holders = holders.split(',').map(parse_holder).collect::<Result<Vec<_>, String>>()?;
+    with_holder_list(policy, holders)
+}
+
+/// Parses one holder, `cto:2` gives the cto two shares
+///
+/// `|` separates the fields of a custodian manifest line, names can't contain it.
+pub fn parse_holder(holder: &str) -> Result<Holder, String> {
+    let holder = holder.trim();
+    if holder.contains('|') {
+        return Err(format!("Holder names can't contain `|`: {holder}"));
+    }
+    let (name, votes) = match holder.rsplit_once(':').map(|(name, votes)| (name.trim(), votes.trim().parse::<u16>())) {
+        Some((name, Ok(votes))) if votes > 0 => (name, votes),
+        Some((name, Ok(_))) => return Err(format!("{name} must hold at least one share")),
+        _ => (holder, 1),
+    };
+    Ok(Holder { name: name.to_string(), votes, contact: String::new(), public_key: None })
+}
+
+/// Adds `holders` to `policy`, errors when they don't fit it
+pub fn with_holder_list(policy: Policy, holders: Vec<Holder>) -> Result<Policy, String> {
+    if !policy.groups.is_empty() {
+        return Err("Holders don't mix with groups, every member gets a file of their own".to_string());
+    }
+    let votes: u32 = holders.iter().map(|x| x.votes as u32).sum();
+    if votes > policy.n_shares as u32 {
+        return Err(format!("Holders get {votes} shares, but there are only {}", policy.n_shares));
+    }
+    if let Some(holder) = holders.iter().find(|x| x.votes >= policy.k_thres) {
+        return Err(format!("{} alone would hold {} shares, enough to decrypt without anyone else", holder.name, holder.votes));
+    }
+    Ok(Policy { holders, ..policy })
+}
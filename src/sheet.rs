@@ -0,0 +1,163 @@
+use qrcode::{EcLevel, QrCode, render::svg};
+use zeroize::Zeroizing;
+
+use crate::crypto::ShareBundle;
+
+/// Printable HTML page for one holder's shares: holder, policy, the shares as a QR code and as words, how to recover
+///
+/// Everything is inline, the page loads nothing so it can be printed from an offline machine.
+pub fn share_sheet(bundle: &ShareBundle, holder: &str, enc_file_name: &str) -> anyhow::Result<Zeroizing<String>> {
+    let share = bundle.first();
+    let encoded = bundle.encode()?;
+    let qr = match QrCode::with_error_correction_level(encoded.as_slice(), EcLevel::M) {
+        Ok(code) => {
+            let svg = code.render::<svg::Color>()
+                .min_dimensions(320, 320)
+                .quiet_zone(true)
+                .build();
+            // inline svg takes no xml declaration
+            let svg = svg.split_once("?>").map_or(svg.as_str(), |(_, x)| x).to_string();
+            Zeroizing::new(svg)
+        }
+        Err(_) => Zeroizing::new("<p class=\"note\">This is too large for a QR code, use the words below.</p>".to_string()),
+    };
+    let words = Zeroizing::new(bundle.encode_to_mnemonic()?
+        .split(' ')
+        .map(|x| format!("<li>{x}</li>"))
+        .collect::<String>());
+    let commitments = share.commitments_fingerprint()
+        .map(|x| format!("<tr><th>commitments</th><td>{x}, every holder's sheet shows the same</td></tr>"))
+        .unwrap_or_default();
+
+    let (k, n, votes) = (share.threshold(), share.total(), bundle.votes());
+    let indices = bundle.shares().iter().map(|x| format!("#{}", x.index())).collect::<Vec<_>>().join(", ");
+    let of_group = share.group().map(|x| format!(", group {}", x.index)).unwrap_or_default();
+    let title = match votes {
+        1 => format!("Share {indices} of {n}{of_group}"),
+        _ => format!("Shares {indices} of {n}{of_group}"),
+    };
+    let (policy, bring) = match share.group() {
+        Some(group) => (
+            format!("any {k} of the {n} members of group {} recover the group's share, any {} of the {} groups recover the file key",
+                group.index, group.threshold, group.total),
+            format!("{} more of the {n} members of group {}, and enough members of {} more groups,", k.saturating_sub(votes as u16), group.index, group.threshold.saturating_sub(1)),
+        ),
+        None => (
+            format!("any {k} of the {n} shares recover the file key"),
+            format!("{} more of the {n} shares", k.saturating_sub(votes as u16)),
+        ),
+    };
+    let holder = escape(holder);
+    let file_name = escape(enc_file_name);
+    let file_id = hex::encode(share.file_id());
+    let epoch = share.epoch();
+
+    Ok(Zeroizing::new(format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title} for {file_name}</title>
+<style>
+  body {{ font-family: sans-serif; max-width: 46em; margin: 2em auto; color: #000; background: #fff; }}
+  th {{ text-align: left; padding-right: 1em; }}
+  .qr svg {{ width: 320px; height: 320px; }}
+  ol.words {{ columns: 4; font-family: monospace; font-size: 1.1em; }}
+  .note {{ font-style: italic; }}
+  @media print {{ body {{ margin: 0; }} }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<table>
+  <tr><th>holder</th><td>{holder}</td></tr>
+  <tr><th>file</th><td>{file_name}</td></tr>
+  <tr><th>votes</th><td>{votes}, this sheet counts as {votes} of the {k} shares needed</td></tr>
+  <tr><th>policy</th><td>{policy}</td></tr>
+  <tr><th>epoch</th><td>{epoch}, sheets of older epochs no longer work</td></tr>
+  <tr><th>file id</th><td>{file_id}</td></tr>
+  {commitments}
+</table>
+<div class="qr">{qr}</div>
+<h2>Words</h2>
+<p class="note">If the QR code can't be scanned, type these words in order. A mistyped word is caught by the checksum at the end.</p>
+<ol class="words">{words}</ol>
+<h2>Recovering the file</h2>
+<ol>
+  <li>Bring this sheet and {bring} to whoever holds the keystore the file was encrypted with.</li>
+  <li>In encryption-app, pick {file_name} and press <em>recover</em>.</li>
+  <li>Add this share by scanning the QR code into an image file and dropping it on the window, or by typing the words.</li>
+  <li>Once enough shares are collected, press <em>decrypt</em>.</li>
+</ol>
+<p class="note">This sheet alone reveals nothing about the file. Keep it offline and apart from the other shares.</p>
+</body>
+</html>
+"#, qr = qr.as_str(), words = words.as_str())))
+}
+
+fn escape(s: &str) -> String {
+    s.chars().map(|c| match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        '\'' => "&#39;".to_string(),
+        c => c.to_string(),
+    }).collect()
+}
+
+
+// #region --------  tests  --------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Share;
+    use crate::qr;
+    use image::{GrayImage, ImageFormat, Luma};
+    use std::io::Cursor;
+
+    /// Draws the inline svg of a sheet one pixel per svg unit, the code is made of `M{x} {y}h{w}v{h}H{x}V{y}` rects
+    fn rasterize(svg: &str) -> anyhow::Result<Vec<u8>> {
+        let attr = |name: &str| -> anyhow::Result<u32> {
+            let value = svg.split_once(&format!(" {name}=\"")).and_then(|(_, x)| x.split_once('"'))
+                .ok_or_else(|| anyhow::anyhow!("svg has no {name}"))?.0;
+            Ok(value.parse()?)
+        };
+        let (width, height) = (attr("width")?, attr("height")?);
+        let path = svg.split_once(" d=\"").and_then(|(_, x)| x.split_once('"'))
+            .ok_or_else(|| anyhow::anyhow!("svg has no path"))?.0;
+        let mut image = GrayImage::from_pixel(width, height, Luma([255]));
+        for rect in path.split('M').filter(|x| !x.is_empty()) {
+            let numbers = rect.split(|c: char| !c.is_ascii_digit()).filter(|x| !x.is_empty())
+                .map(str::parse).collect::<Result<Vec<u32>, _>>()?;
+            let [left, top, w, h, ..] = numbers[..] else {
+                return Err(anyhow::anyhow!("Unexpected path segment: {rect}"));
+            };
+            for (x, y) in (left..left + w).flat_map(|x| (top..top + h).map(move |y| (x, y))) {
+                image.put_pixel(x, y, Luma([0]));
+            }
+        }
+        let mut png = Cursor::new(vec![]);
+        image.write_to(&mut png, ImageFormat::Png)?;
+        Ok(png.into_inner())
+    }
+
+    #[test]
+    fn test_share_sheet() -> anyhow::Result<()> {
+        let shares = Share::split_verifiable(&[7; 60], 5, 3, [1; 16])?;
+        let bundle = ShareBundle::new(shares[..2].to_vec())?;
+        let sheet = share_sheet(&bundle, "Alice <script>alert('x')</script> & co", "q3 \"plans\"_enc.bin")?;
+
+        assert!(sheet.contains("Alice &lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; co"));
+        assert!(sheet.contains("q3 &quot;plans&quot;_enc.bin"));
+        assert!(!sheet.contains("<script>"));
+        assert!(!sheet.contains("\"plans\""));
+
+        let svg = sheet.split_once("<div class=\"qr\">").and_then(|(_, x)| x.split_once("</div>"))
+            .ok_or_else(|| anyhow::anyhow!("sheet has no qr code"))?.0;
+        assert_eq!(bundle.encode()?, qr::decode_image(&rasterize(svg)?)?);
+
+        Ok(())
+    }
+}
+
+// #endregion ----------------